@@ -1,4 +1,5 @@
 use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Mutex, Once};
 
 use slab::Slab;
@@ -9,6 +10,12 @@ static LIVE_GUARDS: Mutex<GuardTable> = Mutex::new(Slab::new());
 
 type GuardTable = Slab<Box<dyn FnOnce() + Send>>;
 
+/// Number of currently-live [`InterruptGuard`]s
+static LIVE_INTERRUPT_GUARDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set by `SIGINT` while an [`InterruptGuard`] is live
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
 /// Prepare to run [`CleanupGuard`]s on `SIGINT`/`SIGTERM`
 pub fn init() {
     // Safety: `` ensures at most one call
@@ -44,12 +51,41 @@ impl Drop for CleanupGuard {
     }
 }
 
+/// A guard that makes the first `SIGINT` interrupt the current operation
+/// instead of terminating the process
+///
+/// The operation is expected to poll [`InterruptGuard::is_interrupted()`] and
+/// bail out. A second `SIGINT` terminates the process as usual.
+pub struct InterruptGuard {
+    _private: (),
+}
+
+impl InterruptGuard {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        LIVE_INTERRUPT_GUARDS.fetch_add(1, Ordering::SeqCst);
+        Self { _private: () }
+    }
+
+    /// Whether `SIGINT` was received since the outermost guard was created
+    pub fn is_interrupted(&self) -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        if LIVE_INTERRUPT_GUARDS.fetch_sub(1, Ordering::SeqCst) == 1 {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
 #[cfg(unix)]
 mod platform {
     use std::os::unix::io::{IntoRawFd as _, RawFd};
     use std::os::unix::net::UnixDatagram;
     use std::panic::AssertUnwindSafe;
-    use std::sync::atomic::{AtomicBool, Ordering};
     use std::thread;
 
     use libc::{c_int, SIGINT, SIGTERM};
@@ -95,6 +131,15 @@ mod platform {
     }
 
     unsafe extern "C" fn handler(signal: c_int) {
+        // Let the interruptible operation cancel itself, unless it's already
+        // been asked to.
+        if signal == SIGINT
+            && LIVE_INTERRUPT_GUARDS.load(Ordering::SeqCst) > 0
+            && !INTERRUPTED.swap(true, Ordering::SeqCst)
+        {
+            return;
+        }
+
         // Treat the second signal as instantly fatal.
         static SIGNALED: AtomicBool = AtomicBool::new(false);
         if SIGNALED.swap(true, Ordering::Relaxed) {
@@ -84,7 +84,8 @@ use crate::config::{
 };
 use crate::formatter::{FormatRecorder, Formatter, PlainTextFormatter};
 use crate::git_util::{
-    is_colocated_git_workspace, print_failed_git_export, print_git_import_stats,
+    import_max_ref_name_width, is_colocated_git_workspace, list_abandoned_commits,
+    print_failed_git_export, print_git_import_stats, GitImportStatsOptions,
 };
 use crate::merge_tools::{DiffEditor, MergeEditor, MergeToolConfigError};
 use crate::template_builder::TemplateLanguage;
@@ -542,6 +543,14 @@ impl WorkspaceCommandHelper {
     #[instrument(skip_all)]
     fn import_git_refs(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
         let git_settings = self.settings.git_settings();
+        let import_stats_options = GitImportStatsOptions {
+            show_ref_stats: false,
+            summary_threshold: None,
+            list_abandoned_commits: list_abandoned_commits(&self.settings),
+            verify_signatures: false,
+            verbose: false,
+            max_ref_name_width: import_max_ref_name_width(&self.settings),
+        };
         let mut tx = self.start_transaction();
         // Automated import shouldn't fail because of reserved remote name.
         let stats = git::import_some_refs(tx.mut_repo(), &git_settings, |ref_name| {
@@ -551,7 +560,7 @@ impl WorkspaceCommandHelper {
             return Ok(());
         }
 
-        print_git_import_stats(ui, tx.repo(), &stats, false)?;
+        print_git_import_stats(ui, tx.repo(), &stats, &import_stats_options)?;
         let mut tx = tx.into_inner();
         // Rebase here to show slightly different status message.
         let num_rebased = tx.mut_repo().rebase_descendants(&self.settings)?;
@@ -1084,7 +1093,7 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
 
             if self.working_copy_shared_with_git {
                 let failed_branches = git::export_refs(mut_repo)?;
-                print_failed_git_export(ui, &failed_branches)?;
+                print_failed_git_export(ui, mut_repo, &failed_branches)?;
             }
 
             self.user_repo = ReadonlyUserRepo::new(tx.commit("snapshot working copy"));
@@ -1159,12 +1168,12 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
             .map(|commit_id| tx.repo().store().get_commit(commit_id))
             .transpose()?;
         if self.working_copy_shared_with_git {
-            let git_repo = self.git_backend().unwrap().open_git_repo()?;
+            let git_repo = self.git_backend().unwrap().pooled_git_repo()?;
             if let Some(wc_commit) = &maybe_new_wc_commit {
                 git::reset_head(tx.mut_repo(), &git_repo, wc_commit)?;
             }
             let failed_branches = git::export_refs(tx.mut_repo())?;
-            print_failed_git_export(ui, &failed_branches)?;
+            print_failed_git_export(ui, tx.repo(), &failed_branches)?;
         }
         self.user_repo = ReadonlyUserRepo::new(tx.commit(description));
         self.report_repo_changes(ui, &old_repo)?;
@@ -2145,6 +2154,9 @@ pub struct EarlyArgs {
     // Parsing with ignore_errors will crash if this is bool, so use
     // Option<bool>.
     pub no_pager: Option<bool>,
+    /// Don't display progress, e.g. while fetching or snapshotting
+    #[arg(long, value_name = "WHEN", global = true, action = ArgAction::SetTrue)]
+    pub no_progress: Option<bool>,
     /// Additional configuration options (can be repeated)
     //  TODO: Introduce a `--config` option with simpler syntax for simple
     //  cases, designed so that `--config ui.color=auto` works
@@ -2312,6 +2324,10 @@ fn handle_early_args(
     if args.no_pager.unwrap_or_default() {
         args.config_toml.push(r#"ui.paginate="never""#.to_owned());
     }
+    if args.no_progress.unwrap_or_default() {
+        args.config_toml
+            .push("ui.progress-indicator=false".to_owned());
+    }
     if !args.config_toml.is_empty() {
         layered_configs.parse_config_args(&args.config_toml)?;
         ui.reset(&layered_configs.merge())?;
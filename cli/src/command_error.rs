@@ -35,6 +35,7 @@ use jj_lib::working_copy::{ResetError, SnapshotError, WorkingCopyStateError};
 use jj_lib::workspace::WorkspaceInitError;
 use thiserror::Error;
 
+use crate::git_util::redact_url_credentials;
 use crate::merge_tools::{
     ConflictResolveError, DiffEditError, DiffGenerateError, MergeToolConfigError,
 };
@@ -296,7 +297,10 @@ impl From<MergeToolConfigError> for CommandError {
 
 impl From<git2::Error> for CommandError {
     fn from(err: git2::Error) -> Self {
-        user_error_with_message("Git operation failed", err)
+        user_error_with_message(
+            "Git operation failed",
+            redact_url_credentials(&err.to_string()),
+        )
     }
 }
 
@@ -318,6 +322,7 @@ repository contents."
             GitImportError::RemoteReservedForLocalGitRepo => {
                 Some("Run `jj git remote rename` to give different name.".to_string())
             }
+            GitImportError::DuplicateRefName { .. } => None,
             GitImportError::InternalBackend(_) => None,
             GitImportError::InternalGitError(_) => None,
             GitImportError::UnexpectedBackend => None,
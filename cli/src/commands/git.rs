@@ -12,29 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::io::Write;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{fmt, fs, io};
 
 use clap::{ArgGroup, Subcommand};
 use itertools::Itertools;
-use jj_lib::backend::TreeValue;
+use jj_lib::backend::{CommitId, TreeValue};
 use jj_lib::file_util;
 use jj_lib::git::{
-    self, parse_gitmodules, GitBranchPushTargets, GitFetchError, GitFetchStats, GitPushError,
+    self, parse_gitmodules, GitBranchPushTargets, GitFetchError, GitFetchStats, GitMirrorUpdate,
+    GitPushError, RefName,
 };
 use jj_lib::object_id::ObjectId;
-use jj_lib::op_store::RefTarget;
+use jj_lib::op_store::{RefTarget, RemoteRef};
 use jj_lib::refs::{
     classify_branch_push_action, BranchPushAction, BranchPushUpdate, LocalAndRemoteRef,
 };
 use jj_lib::repo::{ReadonlyRepo, Repo};
 use jj_lib::repo_path::RepoPath;
 use jj_lib::revset::{self, RevsetExpression, RevsetIteratorExt as _};
-use jj_lib::settings::{ConfigResultExt as _, UserSettings};
+use jj_lib::settings::{ConfigResultExt as _, GitSettings, UserSettings};
 use jj_lib::str_util::StringPattern;
 use jj_lib::view::View;
 use jj_lib::workspace::Workspace;
@@ -50,9 +52,15 @@ use crate::command_error::{
     CommandError,
 };
 use crate::git_util::{
-    get_git_repo, is_colocated_git_workspace, print_failed_git_export, print_git_import_stats,
-    with_remote_git_callbacks,
+    check_remote, colocate_git_workspace, configured_push_refspecs, fetched_ref_namespace,
+    get_git_remotes, get_git_repo, import_max_ref_name_width, is_colocated_git_workspace,
+    keychain_clear_host, map_git_error, print_failed_git_export, print_git_dir_symlink_hint,
+    print_git_import_stats, redact_url_credentials, reindex_if_deepened, retry_on_network_error,
+    run_post_fetch_hook, use_configured_ssh_command, with_remote_git_callbacks_and_hooks,
+    GitImportStatsOptions, GitRemoteUrls, RemoteCallbackHooks, RemoteCheckError, RemoteTimeouts,
+    RetryPolicy,
 };
+use crate::progress::{format_fetch_timings, AggregateProgress, FetchTimings};
 use crate::ui::Ui;
 
 /// Commands for working with the underlying Git repo
@@ -69,6 +77,9 @@ pub enum GitCommand {
     Push(GitPushArgs),
     Import(GitImportArgs),
     Export(GitExportArgs),
+    Colocate(GitColocateArgs),
+    #[command(subcommand)]
+    Credential(GitCredentialCommand),
     #[command(subcommand, hide = true)]
     Submodule(GitSubmoduleCommand),
 }
@@ -82,6 +93,7 @@ pub enum GitRemoteCommand {
     Remove(GitRemoteRemoveArgs),
     Rename(GitRemoteRenameArgs),
     List(GitRemoteListArgs),
+    Check(GitRemoteCheckArgs),
 }
 
 /// Add a Git remote
@@ -113,6 +125,17 @@ pub struct GitRemoteRenameArgs {
 #[derive(clap::Args, Clone, Debug)]
 pub struct GitRemoteListArgs {}
 
+/// Check that a Git remote can be connected to
+///
+/// Connects and authenticates to the remote like `jj git fetch` does, but
+/// doesn't fetch anything. This helps debugging network and credential
+/// issues.
+#[derive(clap::Args, Clone, Debug)]
+pub struct GitRemoteCheckArgs {
+    /// The remote's name
+    remote: String,
+}
+
 /// Create a new Git backed repo.
 #[derive(clap::Args, Clone, Debug)]
 pub struct GitInitArgs {
@@ -161,6 +184,24 @@ pub struct GitFetchArgs {
     /// expand `*` as a glob. The other wildcard characters aren't supported.
     #[arg(long, short, default_value = "glob:*", value_parser = parse_string_pattern)]
     branch: Vec<StringPattern>,
+    /// Fetch with this refspec instead of the branch refspecs (can be
+    /// repeated)
+    ///
+    /// The destination must be under `refs/remotes/<remote>/`, and the fetched
+    /// refs become remote branches named by the rest of the destination. For
+    /// example, `+refs/pull/*/head:refs/remotes/origin/pr/*` fetches pull
+    /// requests as `pr/*@origin` branches.
+    #[arg(long, value_name = "REFSPEC", conflicts_with = "branch")]
+    refspec: Vec<String>,
+    /// Fetch a ref which isn't a branch, or a commit by its full id (can be
+    /// repeated)
+    ///
+    /// The fetched ref becomes a remote branch named after it under the
+    /// namespace set by `git.fetched-ref-namespace` ("fetched" by default),
+    /// without the leading `refs/`. For example, `refs/pull/1/head` is fetched
+    /// as `fetched/pull/1/head@origin`.
+    #[arg(long = "ref", value_name = "REF", conflicts_with = "branch")]
+    refs: Vec<String>,
     /// The remote to fetch from (only named remotes are supported, can be
     /// repeated)
     #[arg(long = "remote", value_name = "remote")]
@@ -168,6 +209,71 @@ pub struct GitFetchArgs {
     /// Fetch from all remotes
     #[arg(long, conflicts_with = "remotes")]
     all_remotes: bool,
+    #[command(flatten)]
+    depth_args: FetchDepthArgs,
+    #[command(flatten)]
+    timeout_args: RemoteTimeoutArgs,
+    /// Show more details, such as how long each phase of the fetch took
+    ///
+    /// Every changed ref is listed even if there are more than
+    /// `git.import-summary-threshold` of them.
+    #[arg(long)]
+    verbose: bool,
+}
+
+/// Options for fetching only the recent history
+#[derive(clap::Args, Clone, Debug)]
+pub struct FetchDepthArgs {
+    /// Fetch only this many commits from the tip of each branch
+    ///
+    /// The parents of the oldest fetched commits are left out, so these
+    /// commits appear as children of the root commit. Fetching again with a
+    /// greater depth deepens the history. Defaults to `git.fetch-depth`.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    depth: Option<u32>,
+    /// Fetch only the commit at the tip of each branch, like `--depth 1`
+    #[arg(long, conflicts_with = "depth")]
+    shallow: bool,
+}
+
+impl FetchDepthArgs {
+    fn resolve(&self, settings: &UserSettings) -> GitSettings {
+        let mut git_settings = settings.git_settings();
+        if self.shallow {
+            git_settings.fetch_depth = Some(1);
+        } else if let Some(depth) = self.depth {
+            git_settings.fetch_depth = Some(depth);
+        }
+        git_settings
+    }
+}
+
+/// Overrides of the timeouts for network operations
+#[derive(clap::Args, Clone, Debug)]
+pub struct RemoteTimeoutArgs {
+    /// Seconds to wait for the connection to the remote (0 waits indefinitely)
+    ///
+    /// Defaults to `git.connect-timeout`.
+    #[arg(long, value_name = "SECONDS")]
+    connect_timeout: Option<u64>,
+    /// Seconds to wait for the remote to send or accept data before aborting
+    /// (0 waits indefinitely)
+    ///
+    /// The countdown starts over whenever data is transferred. Defaults to
+    /// `git.timeout`.
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+}
+
+impl RemoteTimeoutArgs {
+    fn resolve(&self, settings: &UserSettings) -> RemoteTimeouts {
+        let to_duration = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+        let configured = RemoteTimeouts::from_settings(settings);
+        RemoteTimeouts {
+            connect: self.connect_timeout.map_or(configured.connect, to_duration),
+            idle: self.timeout.map_or(configured.idle, to_duration),
+        }
+    }
 }
 
 /// Create a new repo backed by a clone of a Git repo
@@ -184,6 +290,10 @@ pub struct GitCloneArgs {
     /// Whether or not to colocate the Jujutsu repo with the git repo
     #[arg(long)]
     colocate: bool,
+    #[command(flatten)]
+    depth_args: FetchDepthArgs,
+    #[command(flatten)]
+    timeout_args: RemoteTimeoutArgs,
 }
 
 /// Push to a Git remote
@@ -191,7 +301,8 @@ pub struct GitCloneArgs {
 /// By default, pushes any branches pointing to
 /// `remote_branches(remote=<remote>)..@`. Use `--branch` to push specific
 /// branches. Use `--all` to push all branches. Use `--change` to generate
-/// branch names based on the change IDs of specific commits.
+/// branch names based on the change IDs of specific commits. Use `--mirror`
+/// to push the refs configured in `git.push-refspecs`.
 #[derive(clap::Args, Clone, Debug)]
 #[command(group(ArgGroup::new("specific").args(&["branch", "change", "revisions"]).multiple(true)))]
 #[command(group(ArgGroup::new("what").args(&["all", "deleted", "tracked"]).conflicts_with("specific")))]
@@ -233,6 +344,31 @@ pub struct GitPushArgs {
     /// Only display what will change on the remote
     #[arg(long)]
     dry_run: bool,
+    /// Refuse to push branches which changed on the remote since they were
+    /// last fetched
+    ///
+    /// Branches which aren't fast-forwarded are otherwise overwritten on the
+    /// remote, even if someone else pushed to them in the meantime.
+    #[arg(long)]
+    force_with_lease: bool,
+    /// Push the refs mapped by the refspecs configured for the remote in
+    /// `git.push-refspecs`, or all branches and tags
+    ///
+    /// The branches are exported to the underlying Git repo first, and its
+    /// refs are pushed. With the default refspecs, the branches and tags on
+    /// the remote are overwritten even if they changed there, unless
+    /// `--force-with-lease` is used. For details, see
+    /// https://martinvonz.github.io/jj/latest/config#mirroring-to-a-remote.
+    #[arg(long, conflicts_with_all = ["specific", "what"])]
+    mirror: bool,
+    /// Also delete the branches on the remote which no ref is pushed to
+    ///
+    /// Only the branches fetched from or pushed to the remote before are
+    /// deleted.
+    #[arg(long, requires = "mirror")]
+    prune: bool,
+    #[command(flatten)]
+    timeout_args: RemoteTimeoutArgs,
 }
 
 /// Update repo with changes made in the underlying Git repo
@@ -240,11 +376,66 @@ pub struct GitPushArgs {
 /// If a working-copy commit gets abandoned, it will be given a new, empty
 /// commit. This is true in general; it is not specific to this command.
 #[derive(clap::Args, Clone, Debug)]
-pub struct GitImportArgs {}
+pub struct GitImportArgs {
+    /// Import only some of the remote branches
+    ///
+    /// By default, the specified name matches exactly. Use `glob:` prefix to
+    /// expand `*` as a glob. The other wildcard characters aren't supported.
+    /// Remote branches which don't match are left as they are. Local branches
+    /// and tags are imported unless `--tags-only` or `--branches-only` is
+    /// used. The notes refs listed in `git.import-notes` are imported unless
+    /// either of them is used.
+    #[arg(long, short, default_value = "glob:*", value_parser = parse_string_pattern)]
+    branch: Vec<StringPattern>,
+    /// Import only tags, leaving the local and remote branches as they are
+    #[arg(long, conflicts_with = "branches_only")]
+    tags_only: bool,
+    /// Import only local and remote branches, leaving the tags as they are
+    #[arg(long)]
+    branches_only: bool,
+}
+
+/// Move the Git repo into the workspace, making it colocated
+///
+/// The Git repo stored inside the `.jj/` directory is moved to the `.git`
+/// directory of the workspace, as if the repo had been created with `jj git
+/// init --colocate`. Git tools can then be used in the workspace.
+#[derive(clap::Args, Clone, Debug)]
+pub struct GitColocateArgs {
+    /// Only display where the Git repo would be moved to
+    #[arg(long)]
+    dry_run: bool,
+}
 
 /// Update the underlying Git repo with changes made in the repo
 #[derive(clap::Args, Clone, Debug)]
-pub struct GitExportArgs {}
+pub struct GitExportArgs {
+    /// Export only these branches
+    ///
+    /// The local branches and the remote branches of these names are exported.
+    /// The other refs in the Git repo are left untouched.
+    #[arg(value_name = "BRANCH")]
+    branches: Vec<String>,
+    /// Only display the branches that would fail to export
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Manage the credentials stored in the keychain
+///
+/// HTTPS credentials are stored in the platform's secret store if
+/// `git.keychain` is set to "read-write".
+#[derive(Subcommand, Clone, Debug)]
+pub enum GitCredentialCommand {
+    Clear(GitCredentialClearArgs),
+}
+
+/// Remove the credentials stored in the keychain for a host
+#[derive(clap::Args, Clone, Debug)]
+pub struct GitCredentialClearArgs {
+    /// The host, including the port if it isn't the default one
+    host: String,
+}
 
 /// FOR INTERNAL USE ONLY Interact with git submodules
 #[derive(Subcommand, Clone, Debug)]
@@ -270,24 +461,6 @@ fn make_branch_term(branch_names: &[impl fmt::Display]) -> String {
     }
 }
 
-fn map_git_error(err: git2::Error) -> CommandError {
-    if err.class() == git2::ErrorClass::Ssh {
-        let hint =
-            if err.code() == git2::ErrorCode::Certificate && std::env::var_os("HOME").is_none() {
-                "The HOME environment variable is not set, and might be required for Git to \
-                 successfully load certificates. Try setting it to the path of a directory that \
-                 contains a `.ssh` directory."
-            } else {
-                "Jujutsu uses libssh2, which doesn't respect ~/.ssh/config. Does `ssh -F \
-                 /dev/null` to the host work?"
-            };
-
-        user_error_with_hint(err, hint)
-    } else {
-        user_error(err.to_string())
-    }
-}
-
 pub fn maybe_add_gitignore(workspace_command: &WorkspaceCommandHelper) -> Result<(), CommandError> {
     if workspace_command.working_copy_shared_with_git() {
         std::fs::write(
@@ -312,6 +485,7 @@ fn cmd_git_remote_add(
     let repo = workspace_command.repo();
     let git_repo = get_git_repo(repo.store())?;
     git::add_remote(&git_repo, &args.remote, &args.url)?;
+    git_repo.clear_pool();
     Ok(())
 }
 
@@ -325,6 +499,7 @@ fn cmd_git_remote_remove(
     let git_repo = get_git_repo(repo.store())?;
     let mut tx = workspace_command.start_transaction();
     git::remove_remote(tx.mut_repo(), &git_repo, &args.remote)?;
+    git_repo.clear_pool();
     if tx.mut_repo().has_changes() {
         tx.finish(ui, format!("remove git remote {}", &args.remote))
     } else {
@@ -342,6 +517,7 @@ fn cmd_git_remote_rename(
     let git_repo = get_git_repo(repo.store())?;
     let mut tx = workspace_command.start_transaction();
     git::rename_remote(tx.mut_repo(), &git_repo, &args.old, &args.new)?;
+    git_repo.clear_pool();
     if tx.mut_repo().has_changes() {
         tx.finish(
             ui,
@@ -352,6 +528,34 @@ fn cmd_git_remote_rename(
     }
 }
 
+fn cmd_git_remote_check(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &GitRemoteCheckArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let repo = workspace_command.repo();
+    let git_repo = get_git_repo(repo.store())?;
+    let refs =
+        check_remote(ui, command.settings(), &git_repo, &args.remote)?.map_err(
+            |err| match err {
+                RemoteCheckError::NotFound(_) => user_error_with_hint(
+                    err,
+                    "Check the URL of the remote with `jj git remote list`.",
+                ),
+                _ => user_error(err),
+            },
+        )?;
+    writeln!(
+        ui.stderr(),
+        "Connected to remote {}, which advertises {} {}",
+        args.remote,
+        refs.len(),
+        if refs.len() == 1 { "ref" } else { "refs" }
+    )?;
+    Ok(())
+}
+
 fn cmd_git_remote_list(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -359,15 +563,26 @@ fn cmd_git_remote_list(
 ) -> Result<(), CommandError> {
     let workspace_command = command.workspace_helper(ui)?;
     let repo = workspace_command.repo();
-    let git_repo = get_git_repo(repo.store())?;
-    for remote_name in git_repo.remotes()?.iter().flatten() {
-        let remote = git_repo.find_remote(remote_name)?;
+    for GitRemoteUrls {
+        name: remote_name,
+        fetch_url,
+        push_url,
+    } in get_git_remotes(repo.store())?
+    {
         writeln!(
             ui.stdout(),
             "{} {}",
             remote_name,
-            remote.url().unwrap_or("<no URL>")
+            fetch_url.as_deref().unwrap_or("<no URL>")
         )?;
+        if push_url != fetch_url {
+            writeln!(
+                ui.stdout(),
+                "{} {} (push)",
+                remote_name,
+                push_url.as_deref().unwrap_or("<no URL>")
+            )?;
+        }
     }
     Ok(())
 }
@@ -420,6 +635,7 @@ pub fn git_init(
                 Workspace::init_colocated_git(command.settings(), workspace_root)?;
             let workspace_command = command.for_loaded_repo(ui, workspace, repo)?;
             maybe_add_gitignore(&workspace_command)?;
+            print_git_dir_symlink_hint(ui, workspace_root)?;
         }
         GitInitMode::External(git_repo_path) => {
             let (workspace, repo) =
@@ -443,6 +659,9 @@ pub fn git_init(
                 }
             }
             print_trackable_remote_branches(ui, workspace_command.repo().view())?;
+            if colocated {
+                print_git_dir_symlink_hint(ui, workspace_root)?;
+            }
         }
         GitInitMode::Internal => {
             Workspace::init_internal_git(command.settings(), workspace_root)?;
@@ -475,12 +694,20 @@ fn init_git_refs(
     if !tx.mut_repo().has_changes() {
         return Ok(repo);
     }
-    print_git_import_stats(ui, tx.repo(), &stats, false)?;
+    let import_stats_options = GitImportStatsOptions {
+        show_ref_stats: false,
+        summary_threshold: None,
+        list_abandoned_commits: false,
+        verify_signatures: false,
+        verbose: false,
+        max_ref_name_width: import_max_ref_name_width(command.settings()),
+    };
+    print_git_import_stats(ui, tx.repo(), &stats, &import_stats_options)?;
     if colocated {
         // If git.auto-local-branch = true, local branches could be created for
         // the imported remote branches.
         let failed_branches = git::export_refs(tx.mut_repo())?;
-        print_failed_git_export(ui, &failed_branches)?;
+        print_failed_git_export(ui, tx.repo(), &failed_branches)?;
     }
     let repo = tx.commit("import git refs");
     writeln!(
@@ -527,6 +754,7 @@ fn cmd_git_fetch(
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
     let git_repo = get_git_repo(workspace_command.repo().store())?;
+    use_configured_ssh_command(command.settings(), &git_repo)?;
     let remotes = if args.all_remotes {
         get_all_remotes(&git_repo)?
     } else if args.remotes.is_empty() {
@@ -534,19 +762,69 @@ fn cmd_git_fetch(
     } else {
         args.remotes.clone()
     };
+    let git_settings = args.depth_args.resolve(command.settings());
+    let shallow_root_ids = git::shallow_root_ids(&git_repo);
     let mut tx = workspace_command.start_transaction();
+    let mut aggregate_progress = AggregateProgress::new(remotes.len());
+    let mut changed_remote_refs = BTreeMap::new();
+    let mut default_branches = vec![];
+    let retry_policy = RetryPolicy::from_settings(command.settings());
+    let fetched_ref_namespace = fetched_ref_namespace(command.settings());
+    let import_stats_options =
+        GitImportStatsOptions::from_settings(command.settings(), args.verbose);
     for remote in &remotes {
-        let stats = with_remote_git_callbacks(ui, |cb| {
-            git::fetch(
-                tx.mut_repo(),
-                &git_repo,
-                remote,
-                &args.branch,
-                cb,
-                &command.settings().git_settings(),
+        let mut refspecs = args.refspec.clone();
+        for source in &args.refs {
+            let refspec = git::fetched_ref_refspec(remote, &fetched_ref_namespace, source)
+                .ok_or_else(|| user_error(format!("Invalid ref to fetch: {source}")))?;
+            refspecs.push(refspec);
+        }
+        aggregate_progress.start_remote(remote);
+        let mut timings = FetchTimings::new(Instant::now());
+        let fetch = |ui: &mut Ui| {
+            // Discard the bytes received by a failed attempt.
+            aggregate_progress.restart_remote();
+            timings = FetchTimings::new(Instant::now());
+            let mut record_timing = |progress: &git::Progress| {
+                timings.update(Instant::now(), progress);
+            };
+            let hooks = RemoteCallbackHooks {
+                progress: Some(&mut record_timing),
+                aggregate_progress: Some(&mut aggregate_progress),
+                timeouts: Some(args.timeout_args.resolve(command.settings())),
+                ..Default::default()
+            };
+            with_remote_git_callbacks_and_hooks(
+                ui,
+                command.settings(),
+                hooks,
+                |cb| {
+                    if refspecs.is_empty() {
+                        git::fetch(
+                            tx.mut_repo(),
+                            &git_repo,
+                            remote,
+                            &args.branch,
+                            cb,
+                            &git_settings,
+                        )
+                    } else {
+                        git::fetch_refspecs(
+                            tx.mut_repo(),
+                            &git_repo,
+                            remote,
+                            &refspecs,
+                            cb,
+                            &git_settings,
+                        )
+                    }
+                },
+                fetch_git_error,
             )
-        })
-        .map_err(|err| match err {
+        };
+        let result = retry_on_network_error(ui, &retry_policy, fetch, fetch_git_error)?;
+        let fetch_end = Instant::now();
+        let stats = result.map_err(|err| match err {
             GitFetchError::InvalidBranchPattern => {
                 if args
                     .branch
@@ -565,12 +843,78 @@ fn cmd_git_fetch(
             GitFetchError::InternalGitError(err) => map_git_error(err),
             _ => user_error(err),
         })?;
-        print_git_import_stats(ui, tx.repo(), &stats.import_stats, true)?;
+        print_git_import_stats(ui, tx.repo(), &stats.import_stats, &import_stats_options)?;
+        if args.verbose {
+            writeln!(
+                ui.stderr(),
+                "Fetched from {remote} in {}",
+                format_fetch_timings(&timings, fetch_end)
+            )?;
+        }
+        changed_remote_refs.extend(stats.import_stats.changed_remote_refs);
+        if let Some(branch) = stats.default_branch {
+            default_branches.push((remote.as_str(), branch));
+        }
     }
     tx.finish(
         ui,
         format!("fetch from git remote(s) {}", remotes.iter().join(",")),
     )?;
+    reindex_if_deepened(ui, workspace_command.repo(), &git_repo, &shallow_root_ids)?;
+    print_default_branch_hint(
+        ui,
+        &workspace_command,
+        &default_branches,
+        &changed_remote_refs,
+    )?;
+    run_post_fetch_hook(
+        ui,
+        command.settings(),
+        workspace_command.workspace_root(),
+        workspace_command.repo().as_ref(),
+        &remotes,
+        &changed_remote_refs,
+    )?;
+    Ok(())
+}
+
+/// Suggests starting to work on the default branch of a remote when it's
+/// fetched for the first time, if the working copy is still an empty commit on
+/// top of the root commit, as in a repo that was just initialized.
+fn print_default_branch_hint(
+    ui: &Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    default_branches: &[(&str, String)],
+    changed_remote_refs: &BTreeMap<RefName, (RemoteRef, RefTarget)>,
+) -> Result<(), CommandError> {
+    let repo = workspace_command.repo();
+    let Some(wc_commit_id) = workspace_command.get_wc_commit_id() else {
+        return Ok(());
+    };
+    let wc_commit = repo.store().get_commit(wc_commit_id)?;
+    if wc_commit.parent_ids() != [repo.store().root_commit_id().clone()]
+        || wc_commit.tree_id() != &repo.store().empty_merged_tree_id()
+    {
+        return Ok(());
+    }
+    let fetched_default_branch = default_branches.iter().find(|(remote, branch)| {
+        let ref_name = RefName::RemoteBranch {
+            branch: branch.clone(),
+            remote: remote.to_string(),
+        };
+        changed_remote_refs
+            .get(&ref_name)
+            .is_some_and(|(old_remote_ref, new_target)| {
+                old_remote_ref.target.is_absent() && new_target.is_present()
+            })
+    });
+    if let Some((remote, branch)) = fetched_default_branch {
+        writeln!(
+            ui.hint(),
+            "Hint: The default branch of remote {remote} is {branch}. Run `jj new \
+             {branch}@{remote}` to start working on it."
+        )?;
+    }
     Ok(())
 }
 
@@ -684,14 +1028,7 @@ fn cmd_git_clone(
     let canonical_wc_path: PathBuf = wc_path
         .canonicalize()
         .map_err(|err| user_error_with_message(format!("Failed to create {wc_path_str}"), err))?;
-    let clone_result = do_git_clone(
-        ui,
-        command,
-        args.colocate,
-        remote_name,
-        &source,
-        &canonical_wc_path,
-    );
+    let clone_result = do_git_clone(ui, command, args, remote_name, &source, &canonical_wc_path);
     if clone_result.is_err() {
         let clean_up_dirs = || -> io::Result<()> {
             fs::remove_dir_all(canonical_wc_path.join(".jj"))?;
@@ -738,12 +1075,12 @@ fn cmd_git_clone(
 fn do_git_clone(
     ui: &mut Ui,
     command: &CommandHelper,
-    colocate: bool,
+    args: &GitCloneArgs,
     remote_name: &str,
     source: &str,
     wc_path: &Path,
 ) -> Result<(WorkspaceCommandHelper, GitFetchStats), CommandError> {
-    let (workspace, repo) = if colocate {
+    let (workspace, repo) = if args.colocate {
         Workspace::init_colocated_git(command.settings(), wc_path)?
     } else {
         Workspace::init_internal_git(command.settings(), wc_path)?
@@ -757,29 +1094,47 @@ fn do_git_clone(
     let mut workspace_command = command.for_loaded_repo(ui, workspace, repo)?;
     maybe_add_gitignore(&workspace_command)?;
     git_repo.remote(remote_name, source).unwrap();
+    git_repo.clear_pool();
+    use_configured_ssh_command(command.settings(), &git_repo)?;
     let mut fetch_tx = workspace_command.start_transaction();
 
-    let stats = with_remote_git_callbacks(ui, |cb| {
-        git::fetch(
-            fetch_tx.mut_repo(),
-            &git_repo,
-            remote_name,
-            &[StringPattern::everything()],
-            cb,
-            &command.settings().git_settings(),
-        )
-    })
+    let hooks = RemoteCallbackHooks {
+        timeouts: Some(args.timeout_args.resolve(command.settings())),
+        ..Default::default()
+    };
+    let stats = with_remote_git_callbacks_and_hooks(
+        ui,
+        command.settings(),
+        hooks,
+        |cb| {
+            git::fetch(
+                fetch_tx.mut_repo(),
+                &git_repo,
+                remote_name,
+                &[StringPattern::everything()],
+                cb,
+                &args.depth_args.resolve(command.settings()),
+            )
+        },
+        fetch_git_error,
+    )?
     .map_err(|err| match err {
         GitFetchError::NoSuchRemote(_) => {
             panic!("shouldn't happen as we just created the git remote")
         }
         GitFetchError::GitImportError(err) => CommandError::from(err),
         GitFetchError::InternalGitError(err) => map_git_error(err),
-        GitFetchError::InvalidBranchPattern => {
-            unreachable!("we didn't provide any globs")
+        GitFetchError::InvalidBranchPattern | GitFetchError::InvalidRefspec(_) => {
+            unreachable!("we didn't provide any globs or refspecs")
         }
     })?;
-    print_git_import_stats(ui, fetch_tx.repo(), &stats.import_stats, true)?;
+    let import_stats_options = GitImportStatsOptions::from_settings(command.settings(), false);
+    print_git_import_stats(
+        ui,
+        fetch_tx.repo(),
+        &stats.import_stats,
+        &import_stats_options,
+    )?;
     fetch_tx.finish(ui, "fetch from git remote into empty repo")?;
     Ok((workspace_command, stats))
 }
@@ -791,12 +1146,16 @@ fn cmd_git_push(
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
     let git_repo = get_git_repo(workspace_command.repo().store())?;
+    use_configured_ssh_command(command.settings(), &git_repo)?;
 
     let remote = if let Some(name) = &args.remote {
         name.clone()
     } else {
         get_default_push_remote(ui, command.settings(), &git_repo)?
     };
+    if args.mirror {
+        return cmd_git_push_mirror(ui, command, args, workspace_command, &git_repo, &remote);
+    }
 
     let repo = workspace_command.repo().clone();
     let wc_commit_id = workspace_command.get_wc_commit_id().cloned();
@@ -997,48 +1356,7 @@ fn cmd_git_push(
         }
     }
 
-    // Check if there are conflicts in any commits we're about to push that haven't
-    // already been pushed.
-    let mut old_heads = repo
-        .view()
-        .remote_branches(&remote)
-        .flat_map(|(_, old_head)| old_head.target.added_ids())
-        .cloned()
-        .collect_vec();
-    if old_heads.is_empty() {
-        old_heads.push(repo.store().root_commit_id().clone());
-    }
-    for commit in revset::walk_revs(repo.as_ref(), &new_heads, &old_heads)?
-        .iter()
-        .commits(repo.store())
-    {
-        let commit = commit?;
-        let mut reasons = vec![];
-        if commit.description().is_empty() {
-            reasons.push("it has no description");
-        }
-        if commit.author().name.is_empty()
-            || commit.author().name == UserSettings::USER_NAME_PLACEHOLDER
-            || commit.author().email.is_empty()
-            || commit.author().email == UserSettings::USER_EMAIL_PLACEHOLDER
-            || commit.committer().name.is_empty()
-            || commit.committer().name == UserSettings::USER_NAME_PLACEHOLDER
-            || commit.committer().email.is_empty()
-            || commit.committer().email == UserSettings::USER_EMAIL_PLACEHOLDER
-        {
-            reasons.push("it has no author and/or committer set");
-        }
-        if commit.has_conflict()? {
-            reasons.push("it has conflicts");
-        }
-        if !reasons.is_empty() {
-            return Err(user_error(format!(
-                "Won't push commit {} since {}",
-                short_commit_hash(commit.id()),
-                reasons.join(" and ")
-            )));
-        }
-    }
+    check_commits_to_push(repo.as_ref(), &remote, &new_heads)?;
 
     writeln!(ui.stderr(), "Branch changes to push to {}:", &remote)?;
     for (branch_name, update) in &branch_updates {
@@ -1088,21 +1406,301 @@ fn cmd_git_push(
     let targets = GitBranchPushTargets {
         branch_updates,
         force_pushed_branches,
+        force_with_lease: args.force_with_lease,
     };
-    with_remote_git_callbacks(ui, |cb| {
-        git::push_branches(tx.mut_repo(), &git_repo, &remote, &targets, cb)
-    })
-    .map_err(|err| match err {
+    let retry_policy = RetryPolicy::from_settings(command.settings());
+    retry_on_network_error(
+        ui,
+        &retry_policy,
+        |ui| {
+            let hooks = RemoteCallbackHooks {
+                timeouts: Some(args.timeout_args.resolve(command.settings())),
+                ..Default::default()
+            };
+            with_remote_git_callbacks_and_hooks(
+                ui,
+                command.settings(),
+                hooks,
+                |cb| git::push_branches(tx.mut_repo(), &git_repo, &remote, &targets, cb),
+                retryable_push_error,
+            )
+        },
+        retryable_push_error,
+    )?
+    .map_err(map_push_error)?;
+    tx.finish(ui, tx_description)?;
+    Ok(())
+}
+
+fn cmd_git_push_mirror(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &GitPushArgs,
+    mut workspace_command: WorkspaceCommandHelper,
+    git_repo: &git2::Repository,
+    remote: &str,
+) -> Result<(), CommandError> {
+    let refspecs = match configured_push_refspecs(command.settings(), remote)? {
+        Some(refspecs) => refspecs,
+        None => git::DEFAULT_MIRROR_REFSPECS.map(str::to_owned).to_vec(),
+    };
+    if args.dry_run {
+        // Show what would be pushed if the refs were exported, without
+        // exporting them.
+        let repo = workspace_command.repo().as_ref();
+        let failed_branches = git::preview_export_refs(repo)?;
+        print_failed_git_export(ui, repo, &failed_branches)?;
+        let exported_refs = git::preview_exported_refs(repo)?;
+        let updates = git::preview_mirror_push_updates(
+            repo,
+            git_repo,
+            remote,
+            &refspecs,
+            args.prune,
+            &exported_refs,
+        )
+        .map_err(map_push_error)?;
+        if updates.is_empty() {
+            writeln!(ui.stderr(), "Nothing changed.")?;
+            return Ok(());
+        }
+        check_mirror_updates(ui, repo, remote, &updates)?;
+        writeln!(ui.stderr(), "Dry-run requested, not pushing.")?;
+        return Ok(());
+    }
+
+    let mut tx = workspace_command.start_transaction();
+    // The refs are pushed from the Git repo, so it must be up to date.
+    let failed_branches = git::export_refs(tx.mut_repo())?;
+    print_failed_git_export(ui, tx.repo(), &failed_branches)?;
+    let updates = git::mirror_push_updates(tx.repo(), git_repo, remote, &refspecs, args.prune)
+        .map_err(map_push_error)?;
+    if updates.is_empty() {
+        writeln!(ui.stderr(), "Nothing changed.")?;
+        if tx.mut_repo().has_changes() {
+            tx.finish(ui, "export git refs")?;
+        }
+        return Ok(());
+    }
+    check_mirror_updates(ui, tx.base_repo().as_ref(), remote, &updates)?;
+
+    let retry_policy = RetryPolicy::from_settings(command.settings());
+    retry_on_network_error(
+        ui,
+        &retry_policy,
+        |ui| {
+            let hooks = RemoteCallbackHooks {
+                timeouts: Some(args.timeout_args.resolve(command.settings())),
+                ..Default::default()
+            };
+            with_remote_git_callbacks_and_hooks(
+                ui,
+                command.settings(),
+                hooks,
+                |cb| {
+                    git::push_mirror(
+                        tx.mut_repo(),
+                        git_repo,
+                        remote,
+                        &updates,
+                        args.force_with_lease,
+                        cb,
+                    )
+                },
+                retryable_push_error,
+            )
+        },
+        retryable_push_error,
+    )?
+    .map_err(map_push_error)?;
+    // Pushing only tags doesn't change the repo.
+    if tx.mut_repo().has_changes() {
+        tx.finish(ui, format!("mirror refs to git remote {remote}"))?;
+    }
+    Ok(())
+}
+
+/// Checks the commits the mirror updates would push, and prints the updates.
+fn check_mirror_updates(
+    ui: &Ui,
+    repo: &dyn Repo,
+    remote: &str,
+    updates: &[GitMirrorUpdate],
+) -> Result<(), CommandError> {
+    // Refs may point to commits which were never imported, e.g. tags which
+    // aren't imported. They can't be checked, and walking from them would
+    // fail.
+    let index = repo.index();
+    let new_heads = updates
+        .iter()
+        .filter_map(|update| update.new_target.clone())
+        .filter(|id| index.has_id(id))
+        .collect_vec();
+    check_commits_to_push(repo, remote, &new_heads)?;
+
+    writeln!(ui.stderr(), "Ref changes to mirror to {remote}:")?;
+    for update in updates {
+        print_mirror_update(ui, repo, update)?;
+    }
+    Ok(())
+}
+
+fn print_mirror_update(ui: &Ui, repo: &dyn Repo, update: &GitMirrorUpdate) -> io::Result<()> {
+    let Some(branch_name) = &update.branch else {
+        if let Some(new_target) = &update.new_target {
+            writeln!(
+                ui.stderr(),
+                "  Push {} to {}",
+                update.qualified_name,
+                short_commit_hash(new_target)
+            )?;
+        }
+        return Ok(());
+    };
+    match (&update.old_target, &update.new_target) {
+        (Some(old_target), Some(new_target)) => {
+            let index = repo.index();
+            // The pushed ref may point to a commit which was never imported,
+            // so whether the update is fast-forward isn't known.
+            if !index.has_id(old_target) || !index.has_id(new_target) {
+                writeln!(
+                    ui.stderr(),
+                    "  {} branch {branch_name} from {} to {}",
+                    if update.force { "Force" } else { "Push" },
+                    short_commit_hash(old_target),
+                    short_commit_hash(new_target)
+                )?;
+            } else if index.is_ancestor(old_target, new_target) {
+                writeln!(
+                    ui.stderr(),
+                    "  Move branch {branch_name} from {} to {}",
+                    short_commit_hash(old_target),
+                    short_commit_hash(new_target)
+                )?;
+            } else {
+                writeln!(
+                    ui.stderr(),
+                    "  Force branch {branch_name} from {} to {}",
+                    short_commit_hash(old_target),
+                    short_commit_hash(new_target)
+                )?;
+            }
+        }
+        (Some(old_target), None) => {
+            writeln!(
+                ui.stderr(),
+                "  Delete branch {branch_name} from {}",
+                short_commit_hash(old_target)
+            )?;
+        }
+        (None, Some(new_target)) => {
+            writeln!(
+                ui.stderr(),
+                "  Add branch {branch_name} to {}",
+                short_commit_hash(new_target)
+            )?;
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+/// Refuses to push commits which haven't already been pushed to the remote if
+/// they have conflicts or no description, author, or committer.
+fn check_commits_to_push(
+    repo: &dyn Repo,
+    remote: &str,
+    new_heads: &[CommitId],
+) -> Result<(), CommandError> {
+    let mut old_heads = repo
+        .view()
+        .remote_branches(remote)
+        .flat_map(|(_, old_head)| old_head.target.added_ids())
+        .cloned()
+        .collect_vec();
+    if old_heads.is_empty() {
+        old_heads.push(repo.store().root_commit_id().clone());
+    }
+    for commit in revset::walk_revs(repo, new_heads, &old_heads)?
+        .iter()
+        .commits(repo.store())
+    {
+        let commit = commit?;
+        let mut reasons = vec![];
+        if commit.description().is_empty() {
+            reasons.push("it has no description");
+        }
+        if commit.author().name.is_empty()
+            || commit.author().name == UserSettings::USER_NAME_PLACEHOLDER
+            || commit.author().email.is_empty()
+            || commit.author().email == UserSettings::USER_EMAIL_PLACEHOLDER
+            || commit.committer().name.is_empty()
+            || commit.committer().name == UserSettings::USER_NAME_PLACEHOLDER
+            || commit.committer().email.is_empty()
+            || commit.committer().email == UserSettings::USER_EMAIL_PLACEHOLDER
+        {
+            reasons.push("it has no author and/or committer set");
+        }
+        if commit.has_conflict()? {
+            reasons.push("it has conflicts");
+        }
+        if !reasons.is_empty() {
+            return Err(user_error(format!(
+                "Won't push commit {} since {}",
+                short_commit_hash(commit.id()),
+                reasons.join(" and ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn fetch_git_error(err: &GitFetchError) -> Option<&git2::Error> {
+    match err {
+        GitFetchError::InternalGitError(err) => Some(err),
+        _ => None,
+    }
+}
+
+/// Returns the Git error of a push which may be retried. A push which failed
+/// after the ref updates were sent (`GitPushError::Interrupted`) isn't retried,
+/// since the remote may have applied them.
+fn retryable_push_error(err: &GitPushError) -> Option<&git2::Error> {
+    match err {
+        GitPushError::InternalGitError(err) => Some(err),
+        _ => None,
+    }
+}
+
+fn map_push_error(err: GitPushError) -> CommandError {
+    match err {
         GitPushError::InternalGitError(err) => map_git_error(err),
         GitPushError::NotFastForward => user_error_with_hint(
             "The push conflicts with changes made on the remote (it is not fast-forwardable).",
             "Try fetching from the remote, then make the branch point to where you want it to be, \
              and push again.",
         ),
+        GitPushError::Interrupted(err) => user_error_with_hint(
+            format!(
+                "The connection failed while pushing: {}",
+                redact_url_credentials(err.message())
+            ),
+            "The remote may have been updated, so the push wasn't retried. Fetch from the remote \
+             to see its current state, then push again if needed.",
+        ),
+        GitPushError::RefChangedOnRemote(refs) => user_error_with_hint(
+            format!(
+                "Refusing to push branches which changed on the remote since they were last \
+                 fetched: {}",
+                refs.iter()
+                    .map(|name| name.strip_prefix("refs/heads/").unwrap_or(name))
+                    .join(", ")
+            ),
+            "Try fetching from the remote, then make the branch point to where you want it to be, \
+             and push again.",
+        ),
         _ => user_error(err),
-    })?;
-    tx.finish(ui, tx_description)?;
-    Ok(())
+    }
 }
 
 fn get_default_push_remote(
@@ -1210,15 +1808,27 @@ fn find_branches_to_push<'a>(
 fn cmd_git_import(
     ui: &mut Ui,
     command: &CommandHelper,
-    _args: &GitImportArgs,
+    args: &GitImportArgs,
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
     let mut tx = workspace_command.start_transaction();
     // In non-colocated repo, HEAD@git will never be moved internally by jj.
     // That's why cmd_git_export() doesn't export the HEAD ref.
     git::import_head(tx.mut_repo())?;
-    let stats = git::import_refs(tx.mut_repo(), &command.settings().git_settings())?;
-    print_git_import_stats(ui, tx.repo(), &stats, true)?;
+    let stats = git::import_some_refs(
+        tx.mut_repo(),
+        &command.settings().git_settings(),
+        |ref_name| match ref_name {
+            RefName::RemoteBranch { branch, .. } => {
+                !args.tags_only && args.branch.iter().any(|pattern| pattern.matches(branch))
+            }
+            RefName::LocalBranch(_) => !args.tags_only,
+            RefName::Tag(_) => !args.branches_only,
+            RefName::Note(_) => !args.tags_only && !args.branches_only,
+        },
+    )?;
+    let import_stats_options = GitImportStatsOptions::from_settings(command.settings(), false);
+    print_git_import_stats(ui, tx.repo(), &stats, &import_stats_options)?;
     tx.finish(ui, "import git refs")?;
     Ok(())
 }
@@ -1226,13 +1836,105 @@ fn cmd_git_import(
 fn cmd_git_export(
     ui: &mut Ui,
     command: &CommandHelper,
-    _args: &GitExportArgs,
+    args: &GitExportArgs,
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
+    let view = workspace_command.repo().view();
+    for name in &args.branches {
+        // A branch which was deleted in jj is still known as a Git ref.
+        let exists = view.get_local_branch(name).is_present()
+            || view
+                .all_remote_branches()
+                .any(|((branch, _), _)| branch == name)
+            || view.get_git_ref(&format!("refs/heads/{name}")).is_present();
+        if !exists {
+            writeln!(ui.warning(), "No such branch: {name}")?;
+        }
+    }
+    if args.dry_run {
+        let repo = workspace_command.repo();
+        let failed_branches = if args.branches.is_empty() {
+            git::preview_export_refs(repo.as_ref())?
+        } else {
+            git::preview_export_branches(repo.as_ref(), &args.branches)?
+        };
+        print_failed_git_export(ui, repo.as_ref(), &failed_branches)?;
+        writeln!(ui.stderr(), "Dry-run requested, not exporting.")?;
+        return Ok(());
+    }
     let mut tx = workspace_command.start_transaction();
-    let failed_branches = git::export_refs(tx.mut_repo())?;
+    let failed_branches = if args.branches.is_empty() {
+        git::export_refs(tx.mut_repo())?
+    } else {
+        git::export_branches(tx.mut_repo(), &args.branches)?
+    };
     tx.finish(ui, "export git refs")?;
-    print_failed_git_export(ui, &failed_branches)?;
+    print_failed_git_export(ui, workspace_command.repo().as_ref(), &failed_branches)?;
+    Ok(())
+}
+
+fn cmd_git_colocate(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &GitColocateArgs,
+) -> Result<(), CommandError> {
+    // Snapshot the working copy before the Git repo starts tracking it.
+    let workspace_command = command.workspace_helper(ui)?;
+    let workspace = command.load_workspace()?;
+    let plan = colocate_git_workspace(&workspace, workspace_command.repo().store(), args.dry_run)
+        .map_err(user_error)?;
+    if args.dry_run {
+        writeln!(
+            ui.stderr(),
+            r#"Would move the Git repo from "{}" to "{}""#,
+            plan.git_repo_path.display(),
+            plan.dot_git_path.display()
+        )?;
+        writeln!(ui.stderr(), "Dry-run requested, not moving.")?;
+        return Ok(());
+    }
+    drop(workspace_command);
+
+    // Reload the repo to use the moved Git repo, whose HEAD and index are then
+    // set up like in a workspace created with `jj git init --colocate`.
+    let mut workspace_command = command.workspace_helper_no_snapshot(ui)?;
+    maybe_add_gitignore(&workspace_command)?;
+    let git_repo = get_git_repo(workspace_command.repo().store())?;
+    let wc_commit = workspace_command
+        .get_wc_commit_id()
+        .map(|id| workspace_command.repo().store().get_commit(id))
+        .transpose()?;
+    let mut tx = workspace_command.start_transaction();
+    if let Some(wc_commit) = &wc_commit {
+        git::reset_head(tx.mut_repo(), &git_repo, wc_commit)?;
+    }
+    if tx.mut_repo().has_changes() {
+        tx.finish(ui, "colocate git repo")?;
+    }
+    writeln!(
+        ui.stderr(),
+        r#"Moved the Git repo to "{}""#,
+        plan.dot_git_path.display()
+    )?;
+    Ok(())
+}
+
+fn cmd_git_credential_clear(
+    ui: &mut Ui,
+    _command: &CommandHelper,
+    args: &GitCredentialClearArgs,
+) -> Result<(), CommandError> {
+    keychain_clear_host(&args.host).map_err(|message| {
+        user_error(format!(
+            "Failed to clear the credentials for {}: {message}",
+            args.host
+        ))
+    })?;
+    writeln!(
+        ui.stderr(),
+        "Cleared the credentials stored for {}",
+        args.host
+    )?;
     Ok(())
 }
 
@@ -1287,9 +1989,16 @@ pub fn cmd_git(
             cmd_git_remote_rename(ui, command, args)
         }
         GitCommand::Remote(GitRemoteCommand::List(args)) => cmd_git_remote_list(ui, command, args),
+        GitCommand::Remote(GitRemoteCommand::Check(args)) => {
+            cmd_git_remote_check(ui, command, args)
+        }
         GitCommand::Push(args) => cmd_git_push(ui, command, args),
         GitCommand::Import(args) => cmd_git_import(ui, command, args),
         GitCommand::Export(args) => cmd_git_export(ui, command, args),
+        GitCommand::Colocate(args) => cmd_git_colocate(ui, command, args),
+        GitCommand::Credential(GitCredentialCommand::Clear(args)) => {
+            cmd_git_credential_clear(ui, command, args)
+        }
         GitCommand::Submodule(GitSubmoduleCommand::PrintGitmodules(args)) => {
             cmd_git_submodule_print_gitmodules(ui, command, args)
         }
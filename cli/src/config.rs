@@ -391,6 +391,9 @@ fn env_overrides() -> config::Config {
     if let Ok(value) = env::var("JJ_EDITOR") {
         builder = builder.set_override("ui.editor", value).unwrap();
     }
+    if let Ok(value) = env::var("JJ_SSH_PROXY") {
+        builder = builder.set_override("git.ssh-proxy", value).unwrap();
+    }
     builder.build().unwrap()
 }
 
@@ -0,0 +1,820 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Write;
+#[cfg(target_os = "macos")]
+use std::iter;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use itertools::Itertools;
+use jj_lib::settings::UserSettings;
+
+use super::remote_url::RemoteUrl;
+
+/// Username and password for an HTTP(S) remote, as understood by
+/// `git credential`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GitCredential {
+    pub protocol: String,
+    pub host: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl GitCredential {
+    pub fn to_description(&self) -> String {
+        let GitCredential {
+            protocol,
+            host,
+            username,
+            password,
+        } = self;
+        format!("protocol={protocol}\nhost={host}\nusername={username}\npassword={password}\n\n")
+    }
+}
+
+/// Parses the `key=value` lines printed by `git credential fill`.
+pub fn parse_git_credential_output(output: &str) -> Vec<(&str, &str)> {
+    output
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
+
+/// Runs `git credential <action>`, feeding `input` to its stdin.
+pub fn run_git_credential(action: &str, input: &str) -> std::io::Result<String> {
+    let mut child = std::process::Command::new("git")
+        .args(["credential", action])
+        // Let the helpers answer, but don't let git prompt by itself.
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(input.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "git credential {action} exited with {}",
+            output.status
+        )));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// Asks the configured git credential helpers for the credentials of an
+/// HTTP(S) `url`.
+pub fn git_credential_fill(url: &str, username: Option<&str>) -> Option<GitCredential> {
+    let url = RemoteUrl::parse(url).filter(RemoteUrl::is_http)?;
+    let (protocol, host) = (url.scheme, url.host_port()?);
+    let mut input = format!("protocol={protocol}\nhost={host}\n");
+    if let Some(username) = username {
+        input.push_str(&format!("username={username}\n"));
+    }
+    input.push('\n');
+    let output = match run_git_credential("fill", &input) {
+        Ok(output) => output,
+        Err(err) => {
+            tracing::info!(%err, "git credential fill failed");
+            return None;
+        }
+    };
+    let mut credential = GitCredential {
+        protocol: protocol.to_owned(),
+        host: host.clone(),
+        username: username.unwrap_or_default().to_owned(),
+        password: String::new(),
+    };
+    for (key, value) in parse_git_credential_output(&output) {
+        match key {
+            "username" => credential.username = value.to_owned(),
+            "password" => credential.password = value.to_owned(),
+            _ => {}
+        }
+    }
+    if credential.username.is_empty() || credential.password.is_empty() {
+        return None;
+    }
+    tracing::info!(host, username = credential.username, "using git credential");
+    Some(credential)
+}
+
+/// What the git credential helpers are told about a credential once the
+/// remote operation is done.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GitCredentialAction {
+    /// The credential worked, so the helpers can store it.
+    Approve,
+    /// The credential was rejected, so the helpers should forget it.
+    Reject,
+}
+
+impl GitCredentialAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GitCredentialAction::Approve => "approve",
+            GitCredentialAction::Reject => "reject",
+        }
+    }
+}
+
+/// Runs `git credential approve` or `git credential reject` for `credential`.
+pub fn git_credential_report(action: GitCredentialAction, credential: &GitCredential) {
+    let action = action.as_str();
+    if let Err(err) = run_git_credential(action, &credential.to_description()) {
+        tracing::info!(%err, action, "git credential report failed");
+    }
+}
+
+/// Where an HTTP(S) credential handed out to libgit2 came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HttpCredentialSource {
+    /// The git credential helpers.
+    Helper,
+    /// The platform's secret store.
+    Keychain,
+    /// The user, who was prompted for it.
+    Prompt,
+}
+
+/// Keeps track of the HTTP(S) credentials handed out during a remote operation
+/// to tell the git credential helpers (and the secret store) which of them
+/// worked once it's done. libgit2 asks for credentials again only if the
+/// remote rejected the previous ones.
+#[derive(Debug, Default)]
+pub struct HttpCredentialOutcomes {
+    /// Stored credentials which the remote rejected.
+    pub rejected: Vec<(GitCredential, HttpCredentialSource)>,
+    /// The credential handed out last. If the operation succeeds, it's the one
+    /// that worked.
+    pub last: Option<(GitCredential, HttpCredentialSource)>,
+}
+
+impl HttpCredentialOutcomes {
+    /// Records that credentials are asked for, which means that the remote
+    /// rejected the last ones handed out, if any.
+    pub fn request(&mut self) {
+        if let Some((credential, source)) = self.last.take() {
+            let rejected = (credential, source);
+            if source != HttpCredentialSource::Prompt && !self.rejected.contains(&rejected) {
+                self.rejected.push(rejected);
+            }
+        }
+    }
+
+    /// Records that `credential` is handed out for `url`.
+    pub fn hand_out(
+        &mut self,
+        url: &str,
+        username: &str,
+        password: &str,
+        source: HttpCredentialSource,
+    ) {
+        let Some(url) = RemoteUrl::parse(url).filter(RemoteUrl::is_http) else {
+            return;
+        };
+        if let Some(host) = url.host_port() {
+            let credential = GitCredential {
+                protocol: url.scheme.to_owned(),
+                host,
+                username: username.to_owned(),
+                password: password.to_owned(),
+            };
+            self.last = Some((credential, source));
+        }
+    }
+
+    /// Returns what to tell the helpers about each credential, along with
+    /// where it came from. The last credential is approved if the operation
+    /// `succeeded`, and left alone otherwise since it may have failed for other
+    /// reasons. Stored credentials which were rejected are rejected, so that a
+    /// stale password doesn't stay cached.
+    pub fn into_actions(
+        self,
+        succeeded: bool,
+    ) -> Vec<(GitCredentialAction, GitCredential, HttpCredentialSource)> {
+        let approved = self.last.filter(|_| succeeded);
+        let mut actions = self
+            .rejected
+            .into_iter()
+            .filter(|(credential, _)| Some(credential) != approved.as_ref().map(|(c, _)| c))
+            .map(|(credential, source)| (GitCredentialAction::Reject, credential, source))
+            .collect_vec();
+        actions.extend(
+            approved.map(|(credential, source)| (GitCredentialAction::Approve, credential, source)),
+        );
+        actions
+    }
+}
+
+/// Whether HTTP(S) credentials are taken from and kept in the platform's
+/// secret store, as configured by `git.keychain`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeychainMode {
+    /// The secret store isn't used.
+    Off,
+    /// Credentials are looked up in the secret store before prompting.
+    Read,
+    /// Like `Read`, and prompted credentials are stored once they worked.
+    /// Stored credentials which the remote rejects are removed.
+    ReadWrite,
+}
+
+pub fn keychain_mode(settings: &UserSettings) -> KeychainMode {
+    let value = settings
+        .config()
+        .get_string("git.keychain")
+        .unwrap_or_default();
+    match value.as_str() {
+        "" | "off" => KeychainMode::Off,
+        "read" => KeychainMode::Read,
+        "read-write" => KeychainMode::ReadWrite,
+        _ => {
+            tracing::warn!(value, "invalid git.keychain");
+            KeychainMode::Off
+        }
+    }
+}
+
+/// Whether the platform's secret store can be used. It's accessed through
+/// `security` on macOS, and through `secret-tool` (the Secret Service) on
+/// other Unix systems.
+pub const KEYCHAIN_SUPPORTED: bool = cfg!(unix);
+
+/// Runs a command line tool of the secret store, feeding `input` to its
+/// stdin. Returns what it printed to stdout and stderr, or the error it
+/// reported.
+#[cfg(unix)]
+pub fn run_keychain_tool(
+    program: &str,
+    args: &[&str],
+    input: &str,
+) -> Result<(String, String), String> {
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Failed to run {program}: {err}"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    _ = stdin.write_all(input.as_bytes());
+    drop(stdin);
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Failed to run {program}: {err}"))?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if output.status.success() {
+        Ok((stdout, stderr))
+    } else if stderr.trim().is_empty() {
+        Err(format!("{program} exited with {}", output.status))
+    } else {
+        Err(stderr.trim().to_owned())
+    }
+}
+
+/// Parses the item printed by `secret-tool search`, returning its user name
+/// and secret. Depending on the version, the attributes are printed to stderr,
+/// so both outputs are passed in.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn parse_secret_tool_search(output: &str) -> Option<(String, String)> {
+    let mut username = None;
+    let mut secret = None;
+    for line in output.lines() {
+        match line.split_once(" = ") {
+            Some(("attribute.user", value)) => username = username.or(Some(value)),
+            Some(("secret", value)) => secret = secret.or(Some(value)),
+            _ => {}
+        }
+    }
+    Some((username?.to_owned(), secret?.to_owned()))
+}
+
+/// Looks up the credentials for `host` (including the port, if any) in the
+/// Secret Service, optionally for a specific `username`.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn keychain_get(
+    protocol: &str,
+    host: &str,
+    username: Option<&str>,
+) -> Option<(String, String)> {
+    let mut args = vec![
+        "search", "--unlock", "service", "jj", "protocol", protocol, "host", host,
+    ];
+    args.extend(username.iter().flat_map(|username| ["user", username]));
+    match run_keychain_tool("secret-tool", &args, "") {
+        Ok((stdout, stderr)) => parse_secret_tool_search(&format!("{stdout}\n{stderr}")),
+        Err(message) => {
+            tracing::info!(message, "keychain lookup failed");
+            None
+        }
+    }
+}
+
+/// Stores `credential` in the Secret Service, replacing the password stored
+/// for the same user and host.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn keychain_store(credential: &GitCredential) -> Result<(), String> {
+    let label = format!("--label=jj: {}://{}", credential.protocol, credential.host);
+    let args = [
+        "store",
+        &label,
+        "service",
+        "jj",
+        "protocol",
+        &credential.protocol,
+        "host",
+        &credential.host,
+        "user",
+        &credential.username,
+    ];
+    run_keychain_tool("secret-tool", &args, &credential.password).map(|_| ())
+}
+
+/// Removes `credential` from the Secret Service.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn keychain_erase(credential: &GitCredential) -> Result<(), String> {
+    let args = [
+        "clear",
+        "service",
+        "jj",
+        "protocol",
+        &credential.protocol,
+        "host",
+        &credential.host,
+        "user",
+        &credential.username,
+    ];
+    run_keychain_tool("secret-tool", &args, "").map(|_| ())
+}
+
+/// Removes all credentials stored for `host` from the Secret Service.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn keychain_clear_host(host: &str) -> Result<(), String> {
+    let args = ["clear", "service", "jj", "host", host];
+    run_keychain_tool("secret-tool", &args, "").map(|_| ())
+}
+
+/// The creator code of the keychain items stored by `jj`, so that passwords
+/// stored by other programs for the same hosts aren't touched.
+#[cfg(target_os = "macos")]
+pub const KEYCHAIN_CREATOR: &str = "jjvc";
+
+/// Returns the arguments of `security` which select the items for `host`
+/// (including the port, if any) stored by `jj`.
+#[cfg(target_os = "macos")]
+pub fn security_item_args(protocol: Option<&str>, host: &str) -> Vec<String> {
+    let (server, port) = match host.rsplit_once(':') {
+        Some((server, port)) if port.bytes().all(|b| b.is_ascii_digit()) => (server, Some(port)),
+        _ => (host, None),
+    };
+    let mut args = vec!["-c".to_owned(), KEYCHAIN_CREATOR.to_owned()];
+    args.extend(["-s".to_owned(), server.to_owned()]);
+    if let Some(port) = port {
+        args.extend(["-P".to_owned(), port.to_owned()]);
+    }
+    // The protocol is a four-character code.
+    match protocol {
+        Some("https") => args.extend(["-r".to_owned(), "htps".to_owned()]),
+        Some(protocol) => args.extend(["-r".to_owned(), protocol.to_owned()]),
+        None => {}
+    }
+    args
+}
+
+/// Parses the account name of the item printed by
+/// `security find-internet-password`.
+#[cfg(target_os = "macos")]
+pub fn parse_security_account(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("\"acct\"<blob>=\"")?;
+        Some(value.strip_suffix('"')?.to_owned())
+    })
+}
+
+/// Quotes an argument of a command run by `security -i`.
+#[cfg(target_os = "macos")]
+pub fn quote_security_arg(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Looks up the credentials for `host` (including the port, if any) in the
+/// login keychain, optionally for a specific `username`.
+#[cfg(target_os = "macos")]
+pub fn keychain_get(
+    protocol: &str,
+    host: &str,
+    username: Option<&str>,
+) -> Option<(String, String)> {
+    let find = |extra_args: &[&str]| {
+        let item_args = security_item_args(Some(protocol), host);
+        let args = iter::once("find-internet-password")
+            .chain(item_args.iter().map(String::as_str))
+            .chain(extra_args.iter().copied())
+            .collect_vec();
+        run_keychain_tool("security", &args, "")
+            .map_err(|message| tracing::info!(message, "keychain lookup failed"))
+            .ok()
+    };
+    let username = match username {
+        Some(username) => username.to_owned(),
+        None => parse_security_account(&find(&[])?.0)?,
+    };
+    let (stdout, _) = find(&["-a", &username, "-w"])?;
+    let password = stdout.strip_suffix('\n').unwrap_or(&stdout).to_owned();
+    Some((username, password))
+}
+
+/// Stores `credential` in the login keychain, replacing the password stored
+/// for the same user and host. The command is passed through stdin so that
+/// the password doesn't show up in the process list.
+#[cfg(target_os = "macos")]
+pub fn keychain_store(credential: &GitCredential) -> Result<(), String> {
+    let args = iter::once("add-internet-password -U".to_owned())
+        .chain(
+            security_item_args(Some(&credential.protocol), &credential.host)
+                .iter()
+                .map(|arg| quote_security_arg(arg)),
+        )
+        .chain(["-a".to_owned(), quote_security_arg(&credential.username)])
+        .chain(["-w".to_owned(), quote_security_arg(&credential.password)])
+        .join(" ");
+    run_keychain_tool("security", &["-i"], &format!("{args}\n")).map(|_| ())
+}
+
+/// Removes `credential` from the login keychain.
+#[cfg(target_os = "macos")]
+pub fn keychain_erase(credential: &GitCredential) -> Result<(), String> {
+    let item_args = security_item_args(Some(&credential.protocol), &credential.host);
+    let args = iter::once("delete-internet-password")
+        .chain(item_args.iter().map(String::as_str))
+        .chain(["-a", &credential.username])
+        .collect_vec();
+    run_keychain_tool("security", &args, "").map(|_| ())
+}
+
+/// Removes all credentials stored for `host` from the login keychain.
+#[cfg(target_os = "macos")]
+pub fn keychain_clear_host(host: &str) -> Result<(), String> {
+    let item_args = security_item_args(None, host);
+    let args = iter::once("delete-internet-password")
+        .chain(item_args.iter().map(String::as_str))
+        .collect_vec();
+    // Only one item is deleted at a time, and deleting fails once there are
+    // none left.
+    let mut deleted = 0;
+    while deleted < 100 && run_keychain_tool("security", &args, "").is_ok() {
+        deleted += 1;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn keychain_get(
+    _protocol: &str,
+    _host: &str,
+    _username: Option<&str>,
+) -> Option<(String, String)> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn keychain_store(_credential: &GitCredential) -> Result<(), String> {
+    Err("The keychain isn't supported on this platform".to_owned())
+}
+
+#[cfg(not(unix))]
+pub fn keychain_erase(_credential: &GitCredential) -> Result<(), String> {
+    Err("The keychain isn't supported on this platform".to_owned())
+}
+
+#[cfg(not(unix))]
+pub fn keychain_clear_host(_host: &str) -> Result<(), String> {
+    Err("The keychain isn't supported on this platform".to_owned())
+}
+
+/// Looks up the credentials for an HTTP(S) `url` in the platform's secret
+/// store.
+pub fn keychain_get_credential(url: &str, username: Option<&str>) -> Option<GitCredential> {
+    let url = RemoteUrl::parse(url).filter(RemoteUrl::is_http)?;
+    let host = url.host_port()?;
+    let (username, password) = keychain_get(url.scheme, &host, username)?;
+    tracing::info!(host, username, "using keychain credential");
+    Some(GitCredential {
+        protocol: url.scheme.to_owned(),
+        host,
+        username,
+        password,
+    })
+}
+
+/// An entry of a `.netrc` file. `machine` is `None` for the `default` entry.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct NetrcEntry {
+    pub machine: Option<String>,
+    pub login: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Parses the contents of a `.netrc` file. Macro definitions are skipped.
+pub fn parse_netrc(content: &str) -> Vec<NetrcEntry> {
+    // Tokens may be separated by any whitespace including newlines, but a macro
+    // body runs until the next empty line.
+    let mut tokens = vec![];
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        for token in line.split_whitespace() {
+            if token == "macdef" {
+                for line in lines.by_ref() {
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                break;
+            }
+            tokens.push(token);
+        }
+    }
+
+    let mut entries: Vec<NetrcEntry> = vec![];
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => entries.push(NetrcEntry {
+                machine: tokens.next().map(str::to_owned),
+                ..Default::default()
+            }),
+            "default" => entries.push(NetrcEntry::default()),
+            "login" | "password" | "account" => {
+                let value = tokens.next().map(|value| {
+                    let value = value.strip_prefix('"').unwrap_or(value);
+                    value.strip_suffix('"').unwrap_or(value).to_owned()
+                });
+                if let Some(entry) = entries.last_mut() {
+                    match token {
+                        "login" => entry.login = value,
+                        "password" => entry.password = value,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Returns the path of the user's netrc file, as curl would find it.
+pub fn netrc_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("NETRC") {
+        return Some(PathBuf::from(path));
+    }
+    let home_dir = dirs::home_dir()?;
+    let path = home_dir.join(".netrc");
+    if cfg!(windows) && !path.exists() {
+        return Some(home_dir.join("_netrc"));
+    }
+    Some(path)
+}
+
+/// Finds the first entry for `host` (and `username` if specified), or the
+/// `default` entry, which comes last.
+pub fn find_netrc_entry<'a>(
+    entries: &'a [NetrcEntry],
+    host: &str,
+    username: Option<&str>,
+) -> Option<&'a NetrcEntry> {
+    entries
+        .iter()
+        .filter(|entry| match username {
+            Some(username) => entry.login.as_deref() == Some(username),
+            None => true,
+        })
+        .find(|entry| {
+            entry
+                .machine
+                .as_deref()
+                .map_or(true, |machine| machine.eq_ignore_ascii_case(host))
+        })
+}
+
+/// Looks up the login and password for an HTTP(S) `url` in the netrc file.
+pub fn netrc_get_username_password(url: &str, username: Option<&str>) -> Option<(String, String)> {
+    let host = RemoteUrl::parse(url).filter(RemoteUrl::is_http)?.host?;
+    let path = netrc_path()?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) => {
+            tracing::debug!(?path, %err, "failed to read netrc");
+            return None;
+        }
+    };
+    let entries = parse_netrc(&content);
+    let entry = find_netrc_entry(&entries, host, username)?;
+    let login = entry.login.clone().or(username.map(str::to_owned))?;
+    let password = entry.password.clone()?;
+    tracing::info!(?path, host, "using credentials from netrc");
+    Some((login, password))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_credential_outcomes() {
+        use GitCredentialAction::{Approve, Reject};
+        use HttpCredentialSource::{Helper, Keychain, Prompt};
+
+        let url = "https://example.com/repo";
+        let credential = |password: &str| GitCredential {
+            protocol: "https".to_owned(),
+            host: "example.com".to_owned(),
+            username: "alice".to_owned(),
+            password: password.to_owned(),
+        };
+
+        // A stale password from the helper is rejected, and the prompted one
+        // which replaced it is approved.
+        let mut outcomes = HttpCredentialOutcomes::default();
+        outcomes.request();
+        outcomes.hand_out(url, "alice", "stale", Helper);
+        outcomes.request();
+        outcomes.hand_out(url, "alice", "stale", Helper);
+        outcomes.request();
+        outcomes.hand_out(url, "alice", "typed", Prompt);
+        assert_eq!(
+            outcomes.into_actions(true),
+            [
+                (Reject, credential("stale"), Helper),
+                (Approve, credential("typed"), Prompt)
+            ]
+        );
+
+        // Rejected prompted passwords aren't reported. The last credential
+        // isn't reported if the operation failed.
+        let mut outcomes = HttpCredentialOutcomes::default();
+        outcomes.request();
+        outcomes.hand_out(url, "alice", "typo", Prompt);
+        outcomes.request();
+        outcomes.hand_out(url, "alice", "stale", Helper);
+        assert_eq!(outcomes.into_actions(false), []);
+
+        // A credential which worked in the end isn't rejected.
+        let mut outcomes = HttpCredentialOutcomes::default();
+        outcomes.hand_out(url, "alice", "good", Helper);
+        outcomes.request();
+        outcomes.hand_out(url, "alice", "good", Helper);
+        assert_eq!(
+            outcomes.into_actions(true),
+            [(Approve, credential("good"), Helper)]
+        );
+
+        // Rejected credentials from the keychain are reported along with their
+        // source.
+        let mut outcomes = HttpCredentialOutcomes::default();
+        outcomes.hand_out(url, "alice", "stale", Keychain);
+        outcomes.request();
+        outcomes.hand_out(url, "alice", "typed", Prompt);
+        assert_eq!(
+            outcomes.into_actions(true),
+            [
+                (Reject, credential("stale"), Keychain),
+                (Approve, credential("typed"), Prompt)
+            ]
+        );
+
+        // Credentials for other kinds of remotes aren't tracked.
+        let mut outcomes = HttpCredentialOutcomes::default();
+        outcomes.hand_out("ssh://example.com/repo", "alice", "pw", Helper);
+        assert_eq!(outcomes.into_actions(true), []);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_parse_secret_tool_search() {
+        let stdout = "\
+[/org/freedesktop/secrets/collection/login/12]
+label = jj: https://example.com
+secret = hunter2
+created = 2024-01-01 00:00:00
+modified = 2024-01-01 00:00:00
+schema = org.freedesktop.Secret.Generic
+";
+        let stderr = "\
+attribute.host = example.com
+attribute.user = alice
+attribute.protocol = https
+attribute.service = jj
+";
+        assert_eq!(
+            parse_secret_tool_search(&format!("{stdout}\n{stderr}")),
+            Some(("alice".to_owned(), "hunter2".to_owned()))
+        );
+        assert_eq!(parse_secret_tool_search(stdout), None);
+        assert_eq!(parse_secret_tool_search(""), None);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_parse_security_account() {
+        let output = "\
+keychain: \"/Users/alice/Library/Keychains/login.keychain-db\"
+class: \"inet\"
+attributes:
+    \"acct\"<blob>=\"alice\"
+    \"srvr\"<blob>=\"example.com\"
+";
+        assert_eq!(parse_security_account(output), Some("alice".to_owned()));
+        assert_eq!(parse_security_account("class: \"inet\"\n"), None);
+        assert_eq!(
+            security_item_args(Some("https"), "example.com:8443"),
+            [
+                "-c",
+                "jjvc",
+                "-s",
+                "example.com",
+                "-P",
+                "8443",
+                "-r",
+                "htps"
+            ]
+        );
+        assert_eq!(quote_security_arg(r#"a"b\c"#), r#""a\"b\\c""#);
+    }
+
+    #[test]
+    fn test_parse_git_credential_output() {
+        assert_eq!(
+            parse_git_credential_output(
+                "protocol=https\nhost=example.com\nusername=me\npassword=a=b\n\nignored=1\n"
+            ),
+            [
+                ("protocol", "https"),
+                ("host", "example.com"),
+                ("username", "me"),
+                ("password", "a=b"),
+            ]
+        );
+        assert_eq!(parse_git_credential_output(""), []);
+    }
+
+    #[test]
+    fn test_parse_netrc() {
+        let entries = parse_netrc(
+            "machine example.com login me password \"secret\"\nmacdef init\nmachine fake login in \
+             password macro\n\nmachine\nother.example.com\nlogin you\npassword pw2 account \
+             acct\ndefault login anonymous password guest\n",
+        );
+        assert_eq!(
+            entries,
+            [
+                NetrcEntry {
+                    machine: Some("example.com".to_owned()),
+                    login: Some("me".to_owned()),
+                    password: Some("secret".to_owned()),
+                },
+                NetrcEntry {
+                    machine: Some("other.example.com".to_owned()),
+                    login: Some("you".to_owned()),
+                    password: Some("pw2".to_owned()),
+                },
+                NetrcEntry {
+                    machine: None,
+                    login: Some("anonymous".to_owned()),
+                    password: Some("guest".to_owned()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_netrc_entry() {
+        let entries = parse_netrc(
+            "machine example.com login me password pw1\nmachine example.com login you password \
+             pw2\ndefault login anonymous password guest\n",
+        );
+        let find_password = |host, username| {
+            find_netrc_entry(&entries, host, username).and_then(|entry| entry.password.as_deref())
+        };
+        assert_eq!(find_password("example.com", None), Some("pw1"));
+        assert_eq!(find_password("EXAMPLE.com", Some("you")), Some("pw2"));
+        assert_eq!(find_password("other.com", None), Some("guest"));
+        assert_eq!(find_password("example.com", Some("nobody")), None);
+    }
+}
@@ -0,0 +1,507 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use jj_lib::git::{self};
+use jj_lib::settings::UserSettings;
+
+use super::ssh_config::ssh_host_patterns_match;
+use crate::ui::Ui;
+
+/// How SSH host keys are verified against `known_hosts`, as configured by
+/// `git.ssh-host-key-checking`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SshHostKeyChecking {
+    /// Ask whether to trust unknown hosts.
+    Ask,
+    /// Trust unknown hosts without asking.
+    AcceptNew,
+    /// Refuse to connect to unknown hosts.
+    Strict,
+    /// Don't verify host keys at all.
+    Off,
+}
+
+pub fn ssh_host_key_checking(settings: &UserSettings) -> SshHostKeyChecking {
+    let value = settings
+        .config()
+        .get_string("git.ssh-host-key-checking")
+        .unwrap_or_default();
+    match value.as_str() {
+        "" | "ask" => SshHostKeyChecking::Ask,
+        "accept-new" => SshHostKeyChecking::AcceptNew,
+        "strict" => SshHostKeyChecking::Strict,
+        "off" => SshHostKeyChecking::Off,
+        _ => {
+            tracing::warn!(value, "invalid git.ssh-host-key-checking");
+            SshHostKeyChecking::Ask
+        }
+    }
+}
+
+/// A host key line of a `known_hosts` file. `@cert-authority` lines are not
+/// represented.
+#[derive(Debug, Eq, PartialEq)]
+pub struct KnownHostsEntry {
+    pub host_patterns: Vec<String>,
+    pub key_type: String,
+    /// Base64-encoded key
+    pub key: String,
+    pub revoked: bool,
+}
+
+/// Parses the contents of a `known_hosts` file.
+pub fn parse_known_hosts(content: &str) -> Vec<KnownHostsEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let mut fields = line.split_whitespace().peekable();
+            let revoked = match fields.next_if(|field| field.starts_with('@')) {
+                None => false,
+                Some("@revoked") => true,
+                Some(_) => return None, // @cert-authority or unknown marker
+            };
+            let host_patterns = fields.next()?.split(',').map(str::to_owned).collect();
+            let key_type = fields.next()?.to_owned();
+            let key = fields.next()?.to_owned();
+            Some(KnownHostsEntry {
+                host_patterns,
+                key_type,
+                key,
+                revoked,
+            })
+        })
+        .collect()
+}
+
+/// Result of looking up a host key in `known_hosts`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum KnownHostStatus {
+    Known,
+    /// There's a key of the same type for the host, but it's different.
+    Changed,
+    Revoked,
+    Unknown,
+}
+
+pub fn check_known_host(
+    entries: &[KnownHostsEntry],
+    host: &str,
+    key_type: &str,
+    key: &str,
+) -> KnownHostStatus {
+    if entries
+        .iter()
+        .any(|entry| entry.revoked && entry.key == key)
+    {
+        return KnownHostStatus::Revoked;
+    }
+    let mut status = KnownHostStatus::Unknown;
+    for entry in entries {
+        if entry.revoked
+            || entry.key_type != key_type
+            || !known_host_patterns_match(&entry.host_patterns, host)
+        {
+            continue;
+        }
+        if entry.key == key {
+            return KnownHostStatus::Known;
+        }
+        status = KnownHostStatus::Changed;
+    }
+    status
+}
+
+/// Matches `host` against the host patterns of a `known_hosts` entry, which
+/// can be hashed like ssh writes them by default (`HashKnownHosts`).
+pub fn known_host_patterns_match(patterns: &[String], host: &str) -> bool {
+    ssh_host_patterns_match(patterns, host)
+        || patterns
+            .iter()
+            .any(|pattern| hashed_known_host_matches(pattern, host))
+}
+
+/// Matches `host` against a hashed host name of the form `|1|<salt>|<hash>`,
+/// where `hash` is the HMAC-SHA1 of the host name keyed with `salt`, both
+/// base64-encoded.
+pub fn hashed_known_host_matches(pattern: &str, host: &str) -> bool {
+    let Some((salt, hash)) = pattern
+        .strip_prefix("|1|")
+        .and_then(|rest| rest.split_once('|'))
+    else {
+        return false;
+    };
+    match (base64_decode(salt), base64_decode(hash)) {
+        (Some(salt), Some(hash)) => hmac_sha1(&salt, host.as_bytes()) == hash.as_slice(),
+        _ => false,
+    }
+}
+
+pub fn hmac_sha1(key: &[u8], message: &[u8]) -> [u8; 20] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..20].copy_from_slice(&sha1_smol::Sha1::from(key).digest().bytes());
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = sha1_smol::Sha1::new();
+    inner.update(&block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = sha1_smol::Sha1::new();
+    outer.update(&block.map(|b| b ^ 0x5c));
+    outer.update(&inner.digest().bytes());
+    outer.digest().bytes()
+}
+
+/// Returns the user's `known_hosts` file, which new keys are added to.
+pub fn user_known_hosts_path() -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(".ssh").join("known_hosts"))
+}
+
+pub fn load_known_hosts() -> Vec<KnownHostsEntry> {
+    let system_path = cfg!(unix).then(|| PathBuf::from("/etc/ssh/ssh_known_hosts"));
+    [user_known_hosts_path(), system_path]
+        .into_iter()
+        .flatten()
+        .flat_map(|path| match std::fs::read_to_string(&path) {
+            Ok(content) => parse_known_hosts(&content),
+            Err(err) => {
+                tracing::debug!(?path, %err, "failed to read known_hosts");
+                vec![]
+            }
+        })
+        .collect()
+}
+
+pub fn add_known_host(host: &str, key_type: &str, key: &str) -> std::io::Result<()> {
+    let path = user_known_hosts_path()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no home directory"))?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{host} {key_type} {key}")?;
+    tracing::info!(?path, host, "added ssh host key");
+    Ok(())
+}
+
+/// Encodes `data` as standard base64, optionally with padding.
+pub fn base64_encode(data: &[u8], pad: bool) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        if pad {
+            for _ in chunk.len()..3 {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes standard base64, with or without padding.
+pub fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return None,
+            };
+            n |= u32::from(value) << (18 - 6 * i);
+        }
+        decoded.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(decoded)
+}
+
+/// Verifies the host key of an SSH server against `known_hosts`, asking the
+/// user whether to trust it if it's unknown.
+pub fn verify_ssh_host_key(
+    ui: &mut Ui,
+    interactive: bool,
+    checking: SshHostKeyChecking,
+    host_key: &git::SshHostKey<'_>,
+) -> Result<(), String> {
+    if checking == SshHostKeyChecking::Off {
+        return Ok(());
+    }
+    let host = host_key.host;
+    let key_type = host_key.key_type;
+    let key = base64_encode(host_key.key, true);
+    match check_known_host(&load_known_hosts(), host, key_type, &key) {
+        KnownHostStatus::Known => return Ok(()),
+        KnownHostStatus::Changed => {
+            return Err(format!(
+                "The {key_type} host key for {host} has changed. Someone could be eavesdropping \
+                 on you, or the host key has just been replaced. Remove the old key from \
+                 ~/.ssh/known_hosts if you trust the new one."
+            ));
+        }
+        KnownHostStatus::Revoked => {
+            return Err(format!(
+                "The {key_type} host key for {host} has been revoked."
+            ));
+        }
+        KnownHostStatus::Unknown => {}
+    }
+    match checking {
+        SshHostKeyChecking::Ask => {
+            let fingerprint = host_key.sha256.map_or_else(
+                || "unknown".to_owned(),
+                |hash| format!("SHA256:{}", base64_encode(hash, false)),
+            );
+            confirm_unknown_host_key(host, key_type, &fingerprint, interactive, |prompt| {
+                ui.prompt_yes_no(prompt, Some(false))
+            })?;
+        }
+        SshHostKeyChecking::AcceptNew => {}
+        SshHostKeyChecking::Strict => {
+            return Err(format!(
+                "No {key_type} host key is known for {host}, and git.ssh-host-key-checking is \
+                 strict."
+            ));
+        }
+        SshHostKeyChecking::Off => unreachable!(),
+    }
+    if let Err(err) = add_known_host(host, key_type, &key) {
+        tracing::warn!(%err, "failed to add ssh host key");
+        _ = writeln!(
+            ui.warning(),
+            "Failed to add the host key for {host} to known_hosts: {err}"
+        );
+    }
+    Ok(())
+}
+
+/// Asks whether to trust the unknown host key of `host`. The key is refused
+/// without asking if prompting is disabled, and if the prompt fails, e.g.
+/// because there's no terminal.
+pub fn confirm_unknown_host_key(
+    host: &str,
+    key_type: &str,
+    fingerprint: &str,
+    interactive: bool,
+    prompt_yes_no: impl FnOnce(&str) -> io::Result<bool>,
+) -> Result<(), String> {
+    if !interactive {
+        return Err(format!(
+            "No {key_type} host key is known for {host}, and prompting is disabled."
+        ));
+    }
+    let prompt = format!(
+        "The authenticity of host {host} can't be established.\n{key_type} key fingerprint is \
+         {fingerprint}.\nAre you sure you want to continue connecting?"
+    );
+    match prompt_yes_no(&prompt) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!("Host key verification failed for {host}.")),
+        Err(err) => Err(format!(
+            "No {key_type} host key is known for {host}, and it couldn't be confirmed: {err}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_known_hosts() {
+        let entries = parse_known_hosts(
+            "# comment\n\ngithub.com,140.82.112.3 ssh-ed25519 AAAAkey1\n@cert-authority \
+             *.example.com ssh-rsa AAAAca\n@revoked * ssh-rsa AAAAbad comment\nincomplete \
+             ssh-rsa\n",
+        );
+        assert_eq!(
+            entries,
+            [
+                KnownHostsEntry {
+                    host_patterns: vec!["github.com".to_owned(), "140.82.112.3".to_owned()],
+                    key_type: "ssh-ed25519".to_owned(),
+                    key: "AAAAkey1".to_owned(),
+                    revoked: false,
+                },
+                KnownHostsEntry {
+                    host_patterns: vec!["*".to_owned()],
+                    key_type: "ssh-rsa".to_owned(),
+                    key: "AAAAbad".to_owned(),
+                    revoked: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_confirm_unknown_host_key() {
+        let confirm = |interactive, answer: io::Result<bool>| {
+            let mut asked = None;
+            let result = confirm_unknown_host_key(
+                "example.com",
+                "ssh-ed25519",
+                "SHA256:abc",
+                interactive,
+                |prompt| {
+                    asked = Some(prompt.to_owned());
+                    answer
+                },
+            );
+            (result, asked)
+        };
+
+        let (result, asked) = confirm(true, Ok(true));
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            asked.as_deref(),
+            Some(
+                "The authenticity of host example.com can't be established.\nssh-ed25519 key \
+                 fingerprint is SHA256:abc.\nAre you sure you want to continue connecting?"
+            )
+        );
+        assert_eq!(
+            confirm(true, Ok(false)).0,
+            Err("Host key verification failed for example.com.".to_owned())
+        );
+        let no_terminal = io::Error::new(io::ErrorKind::Unsupported, "no terminal");
+        assert_eq!(
+            confirm(true, Err(no_terminal)).0,
+            Err(
+                "No ssh-ed25519 host key is known for example.com, and it couldn't be \
+                 confirmed: no terminal"
+                    .to_owned()
+            )
+        );
+        // Non-interactive sessions refuse without asking
+        let (result, asked) = confirm(false, Ok(true));
+        assert_eq!(
+            result,
+            Err(
+                "No ssh-ed25519 host key is known for example.com, and prompting is disabled."
+                    .to_owned()
+            )
+        );
+        assert_eq!(asked, None);
+    }
+
+    #[test]
+    fn test_check_known_host() {
+        let entries = parse_known_hosts(
+            "github.com ssh-ed25519 AAAAkey1\n*.example.com,!bad.example.com ssh-rsa \
+             AAAAkey2\n@revoked * ssh-ed25519 AAAAbad\n",
+        );
+        let check = |host, key_type, key| check_known_host(&entries, host, key_type, key);
+        assert_eq!(
+            check("github.com", "ssh-ed25519", "AAAAkey1"),
+            KnownHostStatus::Known
+        );
+        assert_eq!(
+            check("github.com", "ssh-ed25519", "AAAAother"),
+            KnownHostStatus::Changed
+        );
+        assert_eq!(
+            check("github.com", "ssh-rsa", "AAAAother"),
+            KnownHostStatus::Unknown
+        );
+        assert_eq!(
+            check("git.example.com", "ssh-rsa", "AAAAkey2"),
+            KnownHostStatus::Known
+        );
+        assert_eq!(
+            check("bad.example.com", "ssh-rsa", "AAAAkey2"),
+            KnownHostStatus::Unknown
+        );
+        assert_eq!(
+            check("gitlab.com", "ssh-ed25519", "AAAAbad"),
+            KnownHostStatus::Revoked
+        );
+    }
+
+    #[test]
+    fn test_check_known_host_hashed() {
+        // Written by `ssh-keygen -H` for github.com
+        let entries = parse_known_hosts(
+            "|1|OBv7ywuXIpJzMyFV2sh0gll7148=|LinnGYQ9mXfLfP/BoNihU2AJHsw= ssh-ed25519 \
+             AAAAkey1\ngitlab.com ssh-ed25519 AAAAkey2\n",
+        );
+        let check = |host, key| check_known_host(&entries, host, "ssh-ed25519", key);
+        assert_eq!(check("github.com", "AAAAkey1"), KnownHostStatus::Known);
+        assert_eq!(check("github.com", "AAAAother"), KnownHostStatus::Changed);
+        assert_eq!(check("gitlab.com", "AAAAkey2"), KnownHostStatus::Known);
+        assert_eq!(check("example.com", "AAAAkey1"), KnownHostStatus::Unknown);
+        assert!(!hashed_known_host_matches("|1|!!!|???", "github.com"));
+    }
+
+    #[test]
+    fn test_hmac_sha1() {
+        // RFC 2202 test cases 1 and 6
+        assert_eq!(
+            hex::encode(hmac_sha1(&[0x0b; 20], b"Hi There")),
+            "b617318655057264e28bc0b6fb378c8ef146be00"
+        );
+        assert_eq!(
+            hex::encode(hmac_sha1(
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "aa4ae5e15272d00e95705637ce8a3b55ed402112"
+        );
+    }
+
+    #[test]
+    fn test_base64_decode() {
+        assert_eq!(base64_decode("").unwrap(), b"");
+        assert_eq!(base64_decode("Zg==").unwrap(), b"f");
+        assert_eq!(base64_decode("Zm8").unwrap(), b"fo");
+        assert_eq!(base64_decode("Zm9vYg").unwrap(), b"foob");
+        assert_eq!(base64_decode("//4=").unwrap(), [0xff, 0xfe]);
+        assert_eq!(base64_decode("Z"), None);
+        assert_eq!(base64_decode("Zm9*"), None);
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b"", true), "");
+        assert_eq!(base64_encode(b"f", true), "Zg==");
+        assert_eq!(base64_encode(b"fo", true), "Zm8=");
+        assert_eq!(base64_encode(b"foo", true), "Zm9v");
+        assert_eq!(base64_encode(b"foob", false), "Zm9vYg");
+        assert_eq!(base64_encode(&[0xff, 0xfe], true), "//4=");
+    }
+}
@@ -0,0 +1,469 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use itertools::Itertools;
+use jj_lib::settings::UserSettings;
+use sha2::{Digest as _, Sha256};
+
+/// Returns the pinentry programs to ask passphrases with, in the order they're
+/// tried. `git.pinentry` is either a single program or a list of them. Falls
+/// back to `$PINENTRY_PROGRAM`, then to `pinentry` in `$PATH`.
+pub fn pinentry_programs(
+    settings: &UserSettings,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Vec<String> {
+    const KEY: &str = "git.pinentry";
+    let config = settings.config();
+    let programs = config
+        .get::<Vec<String>>(KEY)
+        .or_else(|_| config.get_string(KEY).map(|program| vec![program]))
+        .ok()
+        .or_else(|| Some(vec![env_var("PINENTRY_PROGRAM")?]))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|program| !program.is_empty())
+        .collect_vec();
+    if programs.is_empty() {
+        vec!["pinentry".to_owned()]
+    } else {
+        programs
+    }
+}
+
+/// Returns how long to wait for pinentry before falling back to the terminal
+/// prompt.
+pub fn pinentry_timeout(settings: &UserSettings) -> Duration {
+    let secs = settings
+        .config()
+        .get::<u64>("git.pinentry-timeout")
+        .unwrap_or(60);
+    Duration::from_secs(secs)
+}
+
+pub const GPG_CONNECT_AGENT: &str = "gpg-connect-agent";
+
+/// Returns whether passphrases should be asked for through gpg-agent, which
+/// caches them, rather than by running pinentry directly.
+pub fn use_gpg_agent(settings: &UserSettings) -> bool {
+    settings
+        .config()
+        .get_bool("git.use-gpg-agent")
+        .unwrap_or(false)
+}
+
+// https://www.gnupg.org/documentation/manuals/assuan/Server-responses.html#Server-responses
+pub fn decode_assuan_data(encoded: &str) -> Option<String> {
+    let encoded = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] != b'%' {
+            decoded.push(encoded[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let byte =
+            u8::from_str_radix(std::str::from_utf8(encoded.get(i..i + 2)?).ok()?, 16).ok()?;
+        decoded.push(byte);
+        i += 2;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Escapes an argument of an Assuan command. gpg-agent expects spaces to be
+/// encoded as "+".
+pub fn encode_assuan_arg(arg: &str) -> String {
+    let mut encoded = Vec::with_capacity(arg.len());
+    for byte in arg.bytes() {
+        match byte {
+            b' ' => encoded.push(b'+'),
+            b'%' | b'+' | b'"' | b'\'' | 0..=0x1f | 0x7f => {
+                encoded.extend(format!("%{byte:02X}").bytes());
+            }
+            _ => encoded.push(byte),
+        }
+    }
+    // Only ASCII bytes were replaced.
+    String::from_utf8(encoded).unwrap()
+}
+
+/// Why pinentry or gpg-agent didn't provide a passphrase.
+#[derive(Debug, Eq, PartialEq)]
+pub enum PinentryError {
+    /// The program couldn't be run or didn't answer.
+    Unavailable,
+    /// The user cancelled the dialog.
+    Cancelled,
+    /// The program reported an error.
+    Failed(String),
+}
+
+/// Parses an Assuan server's response to a request for data.
+// https://www.gnupg.org/documentation/manuals/assuan/Server-responses.html#Server-responses
+pub fn parse_assuan_response(out: &str) -> Result<String, PinentryError> {
+    // GPG_ERR_CANCELED and GPG_ERR_FULLY_CANCELED
+    const CANCELED_CODES: [u32; 2] = [99, 198];
+    for line in out.lines() {
+        if let Some(encoded) = line.strip_prefix("D ") {
+            return decode_assuan_data(encoded)
+                .ok_or_else(|| PinentryError::Failed("Invalid data in the response".to_owned()));
+        } else if let Some(err) = line.strip_prefix("ERR ") {
+            let (code, description) = err.split_once(' ').unwrap_or((err, ""));
+            // The higher bits of the code tell which component failed.
+            let code = code.parse::<u32>().map(|code| code & 0xffff);
+            if code.is_ok_and(|code| CANCELED_CODES.contains(&code)) {
+                return Err(PinentryError::Cancelled);
+            }
+            let message = if description.is_empty() {
+                format!("Error {err}")
+            } else {
+                description.to_owned()
+            };
+            return Err(PinentryError::Failed(message));
+        }
+    }
+    Err(PinentryError::Unavailable)
+}
+
+/// Runs `program`, which talks the Assuan protocol, sends it `req`, and
+/// returns its response. Returns `None` if it couldn't be run or didn't answer
+/// within `timeout`.
+pub fn run_assuan_client(
+    program: &str,
+    args: &[&str],
+    req: String,
+    timeout: Duration,
+) -> Option<String> {
+    let mut child = match std::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            tracing::info!(program, %err, "failed to spawn assuan client");
+            return None;
+        }
+    };
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    // Talk to the program in a separate thread so we can stop waiting for it
+    // if it hangs, e.g. a GUI pinentry without a display.
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let interact = || -> std::io::Result<_> {
+            stdin.write_all(req.as_bytes())?;
+            drop(stdin);
+            let mut out = String::new();
+            stdout.read_to_string(&mut out)?;
+            Ok(out)
+        };
+        _ = sender.send(interact());
+    });
+    let maybe_out = match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::info!(program, ?timeout, "assuan client timed out");
+            _ = child.kill();
+            Err(std::io::ErrorKind::TimedOut.into())
+        }
+    };
+    _ = child.wait();
+    maybe_out.ok()
+}
+
+pub fn pinentry_get_pw(
+    program: &str,
+    timeout: Duration,
+    url: &str,
+) -> Result<String, PinentryError> {
+    #[rustfmt::skip]
+    let req = format!(
+        "SETTITLE jj passphrase\n\
+         SETDESC Enter passphrase for {url}\n\
+         SETPROMPT Passphrase:\n\
+         GETPIN\n"
+    );
+    let out = run_assuan_client(program, &[], req, timeout).ok_or(PinentryError::Unavailable)?;
+    parse_assuan_response(&out)
+}
+
+/// Asks for the passphrase with each of `programs` in turn until one of them
+/// answers. Programs which can't be run or report an error, like a graphical
+/// pinentry without a display, are skipped. If none of them answered, the
+/// error of the last one is returned.
+pub fn pinentry_get_pw_with_fallback(
+    programs: &[String],
+    timeout: Duration,
+    url: &str,
+) -> Result<String, PinentryError> {
+    let mut last_err = PinentryError::Unavailable;
+    for program in programs {
+        match pinentry_get_pw(program, timeout, url) {
+            Err(err @ (PinentryError::Unavailable | PinentryError::Failed(_))) => {
+                tracing::info!(program, ?err, "pinentry didn't answer");
+                last_err = err;
+            }
+            result => return result,
+        }
+    }
+    Err(last_err)
+}
+
+/// Returns the ID under which gpg-agent caches the passphrase of an SSH key.
+pub fn gpg_agent_cache_id(path: &Path) -> String {
+    let digest = Sha256::digest(path.as_os_str().as_encoded_bytes());
+    format!("jj:ssh-key:{}", hex::encode(digest))
+}
+
+/// Asks gpg-agent for the passphrase of `key_name`. The passphrase is taken
+/// from the agent's cache if it was entered before, and cached once entered.
+/// Fails with `PinentryError::Unavailable` if gpg-agent isn't running.
+pub fn gpg_agent_get_pw(
+    program: &str,
+    timeout: Duration,
+    cache_id: &str,
+    key_name: &str,
+) -> Result<String, PinentryError> {
+    let req = format!(
+        "GET_PASSPHRASE --data {} X {} {}\n",
+        encode_assuan_arg(cache_id),
+        encode_assuan_arg("Passphrase:"),
+        encode_assuan_arg(&format!("Enter passphrase for {key_name}")),
+    );
+    // The agent isn't started if it isn't running, so it's left to pinentry.
+    let out = run_assuan_client(program, &["--no-autostart"], req, timeout)
+        .ok_or(PinentryError::Unavailable)?;
+    parse_assuan_response(&out)
+}
+
+/// Makes gpg-agent forget a cached passphrase, e.g. because it was wrong.
+pub fn gpg_agent_clear_pw(program: &str, timeout: Duration, cache_id: &str) {
+    let req = format!("CLEAR_PASSPHRASE {}\n", encode_assuan_arg(cache_id));
+    run_assuan_client(program, &["--no-autostart"], req, timeout);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Instant;
+
+    use super::*;
+
+    #[cfg(unix)]
+    fn write_fake_pinentry(dir: &Path, script: &str) -> String {
+        use std::os::unix::fs::PermissionsExt as _;
+        let path = dir.join("fake-pinentry");
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.into_os_string().into_string().unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pinentry_get_pw() {
+        let temp_dir = testutils::new_temp_dir();
+        let program = write_fake_pinentry(
+            temp_dir.path(),
+            "cat >/dev/null; echo OK; echo 'D pass%25word'; echo OK",
+        );
+        assert_eq!(
+            pinentry_get_pw(&program, Duration::from_secs(10), "url"),
+            Ok("pass%word".to_owned())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pinentry_get_pw_timeout() {
+        let temp_dir = testutils::new_temp_dir();
+        let program = write_fake_pinentry(temp_dir.path(), "exec sleep 10");
+        let start = Instant::now();
+        assert_eq!(
+            pinentry_get_pw(&program, Duration::from_millis(100), "url"),
+            Err(PinentryError::Unavailable)
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_parse_assuan_response() {
+        assert_eq!(
+            parse_assuan_response("OK\nD pass%25word\nOK\n"),
+            Ok("pass%word".to_owned())
+        );
+        assert_eq!(
+            parse_assuan_response("OK\nERR 83886179 Operation cancelled <Pinentry>\n"),
+            Err(PinentryError::Cancelled)
+        );
+        assert_eq!(
+            parse_assuan_response("ERR 83886278 Fully canceled\n"),
+            Err(PinentryError::Cancelled)
+        );
+        assert_eq!(
+            parse_assuan_response("ERR 83886142 Timeout <Pinentry>\n"),
+            Err(PinentryError::Failed("Timeout <Pinentry>".to_owned()))
+        );
+        assert_eq!(
+            parse_assuan_response("ERR 1\n"),
+            Err(PinentryError::Failed("Error 1".to_owned()))
+        );
+        assert_eq!(
+            parse_assuan_response("OK\n"),
+            Err(PinentryError::Unavailable)
+        );
+        assert_eq!(parse_assuan_response(""), Err(PinentryError::Unavailable));
+    }
+
+    #[test]
+    fn test_encode_assuan_arg() {
+        assert_eq!(encode_assuan_arg("plain"), "plain");
+        assert_eq!(encode_assuan_arg("a b+c%d\n\u{e9}"), "a+b%2Bc%25d%0A\u{e9}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_gpg_agent_get_pw() {
+        let temp_dir = testutils::new_temp_dir();
+        let request_path = temp_dir.path().join("request");
+        let program = write_fake_pinentry(
+            temp_dir.path(),
+            &format!(
+                "cat >'{}'; echo 'D pass%25word'; echo OK",
+                request_path.display()
+            ),
+        );
+        assert_eq!(
+            gpg_agent_get_pw(&program, Duration::from_secs(10), "jj:id", "/key path"),
+            Ok("pass%word".to_owned())
+        );
+        assert_eq!(
+            fs::read_to_string(&request_path).unwrap(),
+            "GET_PASSPHRASE --data jj:id X Passphrase: Enter+passphrase+for+/key+path\n"
+        );
+
+        // Cancelled by the user
+        let program = write_fake_pinentry(
+            temp_dir.path(),
+            "cat >/dev/null; echo 'ERR 83886179 Operation cancelled <Pinentry>'",
+        );
+        assert_eq!(
+            gpg_agent_get_pw(&program, Duration::from_secs(10), "jj:id", "key"),
+            Err(PinentryError::Cancelled)
+        );
+
+        // No agent running
+        let program = write_fake_pinentry(temp_dir.path(), "cat >/dev/null; exit 1");
+        assert_eq!(
+            gpg_agent_get_pw(&program, Duration::from_secs(10), "jj:id", "key"),
+            Err(PinentryError::Unavailable)
+        );
+    }
+
+    #[test]
+    fn test_pinentry_programs() {
+        let settings_with_config = |config: &str| {
+            let config = config::Config::builder()
+                .add_source(config::File::from_str(config, config::FileFormat::Toml))
+                .build()
+                .unwrap();
+            UserSettings::from_config(config)
+        };
+        let env_var = |name: &str| (name == "PINENTRY_PROGRAM").then(|| "pinentry-env".to_owned());
+        let no_env_var = |_: &str| None;
+
+        let settings = settings_with_config(r#"git.pinentry = "pinentry-curses""#);
+        assert_eq!(pinentry_programs(&settings, env_var), ["pinentry-curses"]);
+        let settings = settings_with_config(
+            r#"git.pinentry = ["pinentry-gnome3", "", "pinentry-curses", "pinentry-tty"]"#,
+        );
+        assert_eq!(
+            pinentry_programs(&settings, env_var),
+            ["pinentry-gnome3", "pinentry-curses", "pinentry-tty"]
+        );
+        let settings = settings_with_config("");
+        assert_eq!(pinentry_programs(&settings, env_var), ["pinentry-env"]);
+        assert_eq!(pinentry_programs(&settings, no_env_var), ["pinentry"]);
+        let settings = settings_with_config("git.pinentry = []");
+        assert_eq!(pinentry_programs(&settings, env_var), ["pinentry"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pinentry_get_pw_with_fallback() {
+        let temp_dir = testutils::new_temp_dir();
+        let fake_pinentry = |name: &str, script: &str| {
+            let dir = temp_dir.path().join(name);
+            fs::create_dir(&dir).unwrap();
+            write_fake_pinentry(&dir, script)
+        };
+        let no_display = fake_pinentry(
+            "no-display",
+            "cat >/dev/null; echo OK; echo 'ERR 83918950 Inappropriate ioctl for device'",
+        );
+        let cancelled = fake_pinentry(
+            "cancelled",
+            "cat >/dev/null; echo OK; echo 'ERR 83886179 Operation cancelled'",
+        );
+        let working = fake_pinentry("working", "cat >/dev/null; echo OK; echo 'D pw'; echo OK");
+        let missing = "/nonexistent/pinentry".to_owned();
+        let timeout = Duration::from_secs(10);
+
+        // Programs which can't be run or fail are skipped
+        assert_eq!(
+            pinentry_get_pw_with_fallback(
+                &[missing.clone(), no_display.clone(), working.clone()],
+                timeout,
+                "url"
+            ),
+            Ok("pw".to_owned())
+        );
+        // Cancelling doesn't try the other programs
+        assert_eq!(
+            pinentry_get_pw_with_fallback(&[cancelled, working], timeout, "url"),
+            Err(PinentryError::Cancelled)
+        );
+        // The error of the last program is returned if none answered
+        assert_eq!(
+            pinentry_get_pw_with_fallback(&[missing.clone(), no_display], timeout, "url"),
+            Err(PinentryError::Failed(
+                "Inappropriate ioctl for device".to_owned()
+            ))
+        );
+        assert_eq!(
+            pinentry_get_pw_with_fallback(&[missing], timeout, "url"),
+            Err(PinentryError::Unavailable)
+        );
+        assert_eq!(
+            pinentry_get_pw_with_fallback(&[], timeout, "url"),
+            Err(PinentryError::Unavailable)
+        );
+    }
+
+    #[test]
+    fn test_pinentry_get_pw_spawn_failure() {
+        assert_eq!(
+            pinentry_get_pw("/nonexistent/pinentry", Duration::from_secs(1), "url"),
+            Err(PinentryError::Unavailable)
+        );
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{self};
+use std::net::{TcpStream, ToSocketAddrs as _};
+use std::path::Path;
+use std::time::Duration;
+
+use super::remote_url::RemoteUrl;
+
+/// Chooses the proxy for an HTTP(S) `url` the way Git does. The proxy
+/// configured in Git takes precedence over the `https_proxy` and `http_proxy`
+/// environment variables, and hosts listed in `no_proxy` are connected to
+/// directly. Credentials in the proxy URL are used to authenticate to the
+/// proxy.
+pub fn http_proxy_for_url(
+    url: &str,
+    configured_proxy: Option<&str>,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let url = RemoteUrl::parse(url).filter(RemoteUrl::is_http)?;
+    let host = url.host_port()?;
+    let no_proxy = env_var("no_proxy").or_else(|| env_var("NO_PROXY"));
+    if no_proxy.is_some_and(|no_proxy| no_proxy_matches(&no_proxy, &host)) {
+        tracing::debug!(host, "bypassing proxy");
+        return None;
+    }
+    let env_name = if url.scheme == "https" {
+        "https_proxy"
+    } else {
+        "http_proxy"
+    };
+    configured_proxy
+        .map(str::to_owned)
+        .or_else(|| env_var(env_name).or_else(|| env_var(&env_name.to_ascii_uppercase())))
+        .filter(|proxy| !proxy.is_empty())
+}
+
+/// Splits `host:port` into host and port.
+pub fn split_host_port(host: &str) -> (&str, Option<&str>) {
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => (name, Some(port)),
+        _ => (host, None),
+    }
+}
+
+/// Tells whether `host` (optionally with port) matches the comma-separated
+/// `no_proxy` list. An entry matches the host itself and its subdomains, and
+/// `*` matches all hosts.
+pub fn no_proxy_matches(no_proxy: &str, host: &str) -> bool {
+    let (name, port) = split_host_port(host);
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    no_proxy
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            let (entry_name, entry_port) = split_host_port(entry);
+            let entry_name = entry_name
+                .trim_start_matches('*')
+                .trim_start_matches('.')
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+            let name_matches = name.eq_ignore_ascii_case(entry_name)
+                || name.len() > entry_name.len()
+                    && name.as_bytes()[name.len() - entry_name.len() - 1] == b'.'
+                    && name[name.len() - entry_name.len()..].eq_ignore_ascii_case(entry_name);
+            name_matches && (entry_port.is_none() || entry_port == port)
+        })
+}
+
+/// The `ProxyCommand` to connect to SSH remotes through a SOCKS5 proxy. OpenSSH
+/// can't talk to SOCKS proxies by itself, so OpenBSD `nc` is used by default.
+pub const DEFAULT_SSH_PROXY_COMMAND: &str = "nc -X 5 -x $proxy %h %p";
+
+/// A SOCKS5 proxy to connect to SSH remotes through.
+#[derive(Clone, Debug)]
+pub struct SshProxy {
+    /// The `host:port` of the proxy.
+    pub address: String,
+    /// The `ProxyCommand` of the SSH command, in which `$proxy` is replaced by
+    /// the `address`.
+    pub command: String,
+    pub connect_timeout: Option<Duration>,
+}
+
+impl SshProxy {
+    /// Checks that the proxy accepts connections, so that an unreachable proxy
+    /// is reported as such rather than as a failure of the SSH command.
+    pub fn check_reachable(&self) -> io::Result<()> {
+        let mut last_err = None;
+        for addr in self.address.to_socket_addrs()? {
+            let result = match self.connect_timeout {
+                Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            match result {
+                Ok(_) => return Ok(()),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| io::Error::other("no address found")))
+    }
+
+    /// Returns the SSH options to connect through the proxy.
+    pub fn ssh_args(&self) -> [String; 2] {
+        [
+            "-o".to_owned(),
+            format!(
+                "ProxyCommand={}",
+                self.command.replace("$proxy", &self.address)
+            ),
+        ]
+    }
+
+    /// Checks that the program of the `ProxyCommand` is installed, since SSH
+    /// only reports that it couldn't run it through the shell.
+    pub fn check_command_exists(
+        &self,
+        env_var: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), String> {
+        let Some(program) = self.command.split_whitespace().next() else {
+            return Err("git.ssh-proxy-command is empty".to_owned());
+        };
+        let exists = if program.contains('/') {
+            Path::new(program).is_file()
+        } else {
+            env_var("PATH").is_some_and(|paths| {
+                std::env::split_paths(&paths).any(|dir| dir.join(program).is_file())
+            })
+        };
+        if exists {
+            Ok(())
+        } else if self.command == DEFAULT_SSH_PROXY_COMMAND {
+            Err(format!(
+                "The SOCKS5 proxy is connected to with `{program}`, which isn't installed. \
+                 Install OpenBSD netcat, or set git.ssh-proxy-command to another command."
+            ))
+        } else {
+            Err(format!(
+                "The command `{program}` of git.ssh-proxy-command isn't installed"
+            ))
+        }
+    }
+}
+
+/// Returns the SOCKS5 proxy to connect to SSH remotes through as `host:port`,
+/// as set by `git.ssh-proxy` (or `$JJ_SSH_PROXY`). Other kinds of proxies
+/// can't carry SSH, so they're an error. `$ALL_PROXY` isn't used, since it's
+/// typically meant for HTTP clients and may not be a SOCKS5 proxy.
+pub fn ssh_proxy(configured_proxy: Option<&str>) -> Result<Option<String>, String> {
+    let Some(proxy) = configured_proxy.filter(|proxy| !proxy.is_empty()) else {
+        return Ok(None);
+    };
+    match parse_socks5_proxy(proxy) {
+        Some(address) => Ok(Some(address)),
+        None => Err(format!(
+            "Invalid git.ssh-proxy \"{proxy}\": expected socks5://<host>[:<port>]"
+        )),
+    }
+}
+
+/// Parses a `socks5://<host>[:<port>]` (or `socks5h://`) URL into `host:port`.
+/// The port defaults to 1080. Credentials aren't supported.
+pub fn parse_socks5_proxy(proxy: &str) -> Option<String> {
+    let host = proxy
+        .strip_prefix("socks5://")
+        .or_else(|| proxy.strip_prefix("socks5h://"))?
+        .trim_end_matches('/');
+    if host.is_empty() || host.contains(['/', '@']) {
+        return None;
+    }
+    match split_host_port(host) {
+        (_, Some(port)) if port.parse::<u16>().is_err() => None,
+        (_, Some(_)) => Some(host.to_owned()),
+        (name, None) => Some(format!("{name}:1080")),
+    }
+}
+
+/// Returns the SOCKS5 `proxy` to connect to the SSH `url` through, unless its
+/// host is listed in `no_proxy`.
+pub fn ssh_proxy_for_url<'a>(
+    proxy: Option<&'a SshProxy>,
+    url: &RemoteUrl,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Option<&'a SshProxy> {
+    let proxy = proxy?;
+    let host = url.host_port()?;
+    let no_proxy = env_var("no_proxy").or_else(|| env_var("NO_PROXY"));
+    if no_proxy.is_some_and(|no_proxy| no_proxy_matches(&no_proxy, &host)) {
+        tracing::debug!(host, "bypassing ssh proxy");
+        return None;
+    }
+    Some(proxy)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_http_proxy_for_url() {
+        let env = HashMap::from([
+            ("https_proxy", "http://user:pw@proxy:3128"),
+            ("HTTP_PROXY", "proxy:8080"),
+            ("NO_PROXY", "internal.example.com, .corp:8443,localhost"),
+        ]);
+        let env_var = |name: &str| env.get(name).map(|value| value.to_string());
+        let proxy_for_url =
+            |url, configured_proxy| http_proxy_for_url(url, configured_proxy, env_var);
+        assert_eq!(
+            proxy_for_url("https://example.com/repo", None).as_deref(),
+            Some("http://user:pw@proxy:3128")
+        );
+        assert_eq!(
+            proxy_for_url("http://example.com/repo", None).as_deref(),
+            Some("proxy:8080")
+        );
+        assert_eq!(
+            proxy_for_url("https://example.com/repo", Some("git-proxy:80")).as_deref(),
+            Some("git-proxy:80")
+        );
+        assert_eq!(proxy_for_url("ssh://example.com/repo", None), None);
+        assert_eq!(
+            proxy_for_url("https://internal.example.com/repo", None),
+            None
+        );
+        assert_eq!(
+            proxy_for_url("https://git.Internal.example.com/repo", None),
+            None
+        );
+        assert_eq!(proxy_for_url("https://git.corp:8443/repo", None), None);
+        assert!(proxy_for_url("https://git.corp/repo", None).is_some());
+        assert!(proxy_for_url("https://notinternal.example.com/repo", None).is_some());
+        assert_eq!(proxy_for_url("http://localhost:8080/repo", None), None);
+    }
+
+    #[test]
+    fn test_ssh_proxy() {
+        assert_eq!(ssh_proxy(None), Ok(None));
+        assert_eq!(ssh_proxy(Some("")), Ok(None));
+        assert_eq!(
+            ssh_proxy(Some("socks5://proxy")),
+            Ok(Some("proxy:1080".to_owned()))
+        );
+        assert_eq!(
+            ssh_proxy(Some("socks5h://[::1]:9050/")),
+            Ok(Some("[::1]:9050".to_owned()))
+        );
+        assert!(ssh_proxy(Some("http://proxy:3128")).is_err());
+        assert!(ssh_proxy(Some("socks5://user:pw@proxy")).is_err());
+        assert!(ssh_proxy(Some("socks5://proxy:99999")).is_err());
+    }
+
+    #[test]
+    fn test_ssh_proxy_command() {
+        let temp_dir = testutils::new_temp_dir();
+        fs::write(temp_dir.path().join("nc"), "").unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_owned();
+        let env_var = |name: &str| (name == "PATH").then(|| path.clone());
+        let no_env = |_: &str| None;
+        let mut proxy = SshProxy {
+            address: "proxy:1080".to_owned(),
+            command: DEFAULT_SSH_PROXY_COMMAND.to_owned(),
+            connect_timeout: None,
+        };
+        assert_eq!(
+            proxy.ssh_args(),
+            ["-o", "ProxyCommand=nc -X 5 -x proxy:1080 %h %p"]
+        );
+        assert_eq!(proxy.check_command_exists(env_var), Ok(()));
+        assert!(proxy
+            .check_command_exists(no_env)
+            .unwrap_err()
+            .contains("git.ssh-proxy-command"));
+
+        proxy.command = "connect -S $proxy %h %p".to_owned();
+        assert_eq!(
+            proxy.ssh_args(),
+            ["-o", "ProxyCommand=connect -S proxy:1080 %h %p"]
+        );
+        assert_eq!(
+            proxy.check_command_exists(env_var),
+            Err("The command `connect` of git.ssh-proxy-command isn't installed".to_owned())
+        );
+        proxy.command = temp_dir.path().join("nc").to_str().unwrap().to_owned();
+        assert_eq!(proxy.check_command_exists(no_env), Ok(()));
+    }
+
+    #[test]
+    fn test_ssh_proxy_for_url() {
+        let proxy = SshProxy {
+            address: "proxy:1080".to_owned(),
+            command: DEFAULT_SSH_PROXY_COMMAND.to_owned(),
+            connect_timeout: None,
+        };
+        let env = HashMap::from([("NO_PROXY", "internal.example.com,git.corp:2222")]);
+        let env_var = |name: &str| env.get(name).map(|value| value.to_string());
+        let proxy_for_url = |url| {
+            let url = RemoteUrl::parse(url).unwrap();
+            ssh_proxy_for_url(Some(&proxy), &url, env_var).map(|proxy| proxy.address.as_str())
+        };
+        assert_eq!(proxy_for_url("ssh://example.com/repo"), Some("proxy:1080"));
+        assert_eq!(proxy_for_url("git@example.com:repo"), Some("proxy:1080"));
+        assert_eq!(proxy_for_url("ssh://git.internal.example.com/repo"), None);
+        assert_eq!(proxy_for_url("ssh://git.corp:2222/repo"), None);
+        assert_eq!(proxy_for_url("ssh://git.corp/repo"), Some("proxy:1080"));
+        let url = RemoteUrl::parse("ssh://example.com/repo").unwrap();
+        assert!(ssh_proxy_for_url(None, &url, env_var).is_none());
+    }
+
+    #[test]
+    fn test_ssh_proxy_check_reachable() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let proxy = SshProxy {
+            address,
+            command: DEFAULT_SSH_PROXY_COMMAND.to_owned(),
+            connect_timeout: Some(Duration::from_secs(5)),
+        };
+        assert!(proxy.check_reachable().is_ok());
+        drop(listener);
+        assert!(proxy.check_reachable().is_err());
+    }
+
+    #[test]
+    fn test_no_proxy_matches() {
+        assert!(no_proxy_matches("*", "example.com"));
+        assert!(no_proxy_matches("*.example.com", "git.example.com"));
+        assert!(no_proxy_matches("[::1]", "[::1]:8080"));
+        assert!(!no_proxy_matches("", "example.com"));
+        assert!(!no_proxy_matches("example.com:80", "example.com:443"));
+    }
+}
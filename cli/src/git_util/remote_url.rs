@@ -0,0 +1,367 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// A remote URL split into its parts. Besides `scheme://[user@]host[:port]/path`
+/// URLs, the scp-like `[user@]host:path` shorthand for SSH and local paths are
+/// understood, as in Git.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RemoteUrl<'a> {
+    /// `"ssh"` for the scp-like shorthand, and `"file"` for local paths.
+    pub scheme: &'a str,
+    pub user: Option<&'a str>,
+    /// The host name or IP address, without the brackets around IPv6
+    /// addresses. `None` for local paths.
+    pub host: Option<&'a str>,
+    pub port: Option<u16>,
+    pub path: &'a str,
+}
+
+impl<'a> RemoteUrl<'a> {
+    /// Parses `url`. Returns `None` if a host is required but missing, or if
+    /// the port isn't a number.
+    pub fn parse(url: &'a str) -> Option<Self> {
+        if let Some((scheme, rest)) = url.split_once("://") {
+            let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            let (user, host_port) = split_userinfo(authority);
+            if scheme == "file" {
+                let host = (!host_port.is_empty()).then_some(host_port);
+                return Some(RemoteUrl {
+                    scheme,
+                    user: None,
+                    host,
+                    port: None,
+                    path,
+                });
+            }
+            let (host, port) = split_bracketed_host_port(host_port)?;
+            Some(RemoteUrl {
+                scheme,
+                user,
+                host: Some(host),
+                port,
+                path,
+            })
+        } else if let Some((authority, path)) = split_scp_like_url(url) {
+            let (user, host, port) = if let Some(inner) = authority
+                .strip_prefix('[')
+                .and_then(|s| s.strip_suffix(']'))
+            {
+                // `[user@host:port]:path`, `[user@[addr]:port]:path`, or
+                // `[addr]:path` for an IPv6 address
+                let (user, host_port) = split_userinfo(inner);
+                if host_port.starts_with('[') {
+                    let (host, port) = split_bracketed_host_port(host_port)?;
+                    (user, host, port)
+                } else {
+                    match host_port.split_once(':') {
+                        Some((host, port)) if !port.contains(':') => {
+                            (user, host, Some(port.parse().ok()?))
+                        }
+                        _ => (user, host_port, None),
+                    }
+                }
+            } else {
+                let (user, host) = split_userinfo(authority);
+                let host = host
+                    .strip_prefix('[')
+                    .and_then(|s| s.strip_suffix(']'))
+                    .unwrap_or(host);
+                (user, host, None)
+            };
+            // Anything after the brackets of an IPv6 address other than the
+            // path separator is invalid, e.g. `user@[::1]x:path`.
+            if host.contains(['[', ']']) {
+                return None;
+            }
+            (!host.is_empty()).then_some(RemoteUrl {
+                scheme: "ssh",
+                user,
+                host: Some(host),
+                port,
+                path,
+            })
+        } else {
+            Some(RemoteUrl {
+                scheme: "file",
+                user: None,
+                host: None,
+                port: None,
+                path: url,
+            })
+        }
+    }
+
+    pub fn is_http(&self) -> bool {
+        matches!(self.scheme, "http" | "https")
+    }
+
+    pub fn is_ssh(&self) -> bool {
+        matches!(self.scheme, "ssh" | "git+ssh" | "ssh+git")
+    }
+
+    /// Returns the host followed by the port if there's one, e.g.
+    /// `example.com:8080` or `[::1]:22`.
+    pub fn host_port(&self) -> Option<String> {
+        let host = self.host?;
+        let host = if host.contains(':') {
+            format!("[{host}]")
+        } else {
+            host.to_owned()
+        };
+        Some(match self.port {
+            Some(port) => format!("{host}:{port}"),
+            None => host,
+        })
+    }
+
+    /// Returns the URL without the path and password, to tell the user which
+    /// remote credentials are needed for, e.g. `https://alice@example.com`.
+    pub fn origin(&self) -> String {
+        let Some(host_port) = self.host_port() else {
+            return format!("{}://{}", self.scheme, self.path);
+        };
+        match self.user {
+            Some(user) => format!("{}://{user}@{host_port}", self.scheme),
+            None => format!("{}://{host_port}", self.scheme),
+        }
+    }
+}
+
+/// Splits `[userinfo@]host` into the username, ignoring any password, and the
+/// host.
+pub fn split_userinfo(authority: &str) -> (Option<&str>, &str) {
+    match authority.rsplit_once('@') {
+        Some((userinfo, host)) => {
+            let user = userinfo.split(':').next().filter(|user| !user.is_empty());
+            (user, host)
+        }
+        None => (None, authority),
+    }
+}
+
+/// Splits `host[:port]` or `[addr][:port]` into the host and port. Returns
+/// `None` if the host is empty or the port isn't a number.
+pub fn split_bracketed_host_port(host_port: &str) -> Option<(&str, Option<u16>)> {
+    let (host, port) = if let Some(rest) = host_port.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        (host, rest.strip_prefix(':'))
+    } else {
+        match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        }
+    };
+    let port = match port {
+        None | Some("") => None,
+        Some(port) => Some(port.parse().ok()?),
+    };
+    (!host.is_empty()).then_some((host, port))
+}
+
+/// Splits the scp-like `authority:path` form of an SSH URL. Like Git, a URL is
+/// taken to be a local path if it has no colon, or if a slash comes before the
+/// first colon. Colons between brackets, e.g. in `user@[::1]:path` or
+/// `[user@[::1]:port]:path`, don't count.
+pub fn split_scp_like_url(url: &str) -> Option<(&str, &str)> {
+    let search_start = if url.starts_with('[') {
+        // The brackets around the authority may contain an IPv6 address in
+        // brackets.
+        let mut depth = 0;
+        url.find(|c| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })?
+    } else if let Some(start) = url.find("@[").filter(|&i| !url[..i].contains([':', '/'])) {
+        start + url[start..].find(']')?
+    } else {
+        0
+    };
+    let colon = search_start + url[search_start..].find(':')?;
+    let (authority, path) = (&url[..colon], &url[colon + 1..]);
+    if authority.contains('/') {
+        return None;
+    }
+    // `C:\path` is a local path on Windows.
+    if cfg!(windows) && authority.len() == 1 && authority.as_bytes()[0].is_ascii_alphabetic() {
+        return None;
+    }
+    Some((authority, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_url_parse() {
+        let url = |scheme, user, host, port, path| RemoteUrl {
+            scheme,
+            user,
+            host,
+            port,
+            path,
+        };
+        assert_eq!(
+            RemoteUrl::parse("https://github.com/foo/bar.git"),
+            Some(url("https", None, Some("github.com"), None, "/foo/bar.git"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("http://user:pw@localhost:8080/repo"),
+            Some(url(
+                "http",
+                Some("user"),
+                Some("localhost"),
+                Some(8080),
+                "/repo"
+            ))
+        );
+        assert_eq!(
+            RemoteUrl::parse("ssh://git@example.com/foo"),
+            Some(url("ssh", Some("git"), Some("example.com"), None, "/foo"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("ssh://[::1]:2222/foo"),
+            Some(url("ssh", None, Some("::1"), Some(2222), "/foo"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("ssh://user@alias"),
+            Some(url("ssh", Some("user"), Some("alias"), None, ""))
+        );
+        assert_eq!(
+            RemoteUrl::parse("file:///local/path"),
+            Some(url("file", None, None, None, "/local/path"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("/local/path"),
+            Some(url("file", None, None, None, "/local/path"))
+        );
+        // No host, or a port that isn't a number
+        assert_eq!(RemoteUrl::parse("https:///foo"), None);
+        assert_eq!(RemoteUrl::parse("https://example.com:http/foo"), None);
+        // The user is only taken from the authority
+        assert_eq!(
+            RemoteUrl::parse("https://example.com/foo@bar")
+                .unwrap()
+                .user,
+            None
+        );
+        assert_eq!(
+            RemoteUrl::parse("https://@example.com/foo").unwrap().user,
+            None
+        );
+    }
+
+    #[test]
+    fn test_remote_url_parse_scp_like() {
+        let url = |user, host, port, path| RemoteUrl {
+            scheme: "ssh",
+            user,
+            host,
+            port,
+            path,
+        };
+        assert_eq!(
+            RemoteUrl::parse("git@github.com:foo/bar.git"),
+            Some(url(Some("git"), Some("github.com"), None, "foo/bar.git"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("github.com:foo/bar.git"),
+            Some(url(None, Some("github.com"), None, "foo/bar.git"))
+        );
+        // Absolute path, and a path containing colons or `@`
+        assert_eq!(
+            RemoteUrl::parse("host:/srv/repo"),
+            Some(url(None, Some("host"), None, "/srv/repo"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("host:a:b@c"),
+            Some(url(None, Some("host"), None, "a:b@c"))
+        );
+        // Only the last `@` separates the user
+        assert_eq!(
+            RemoteUrl::parse("me@corp@host:repo"),
+            Some(url(Some("me@corp"), Some("host"), None, "repo"))
+        );
+        // Bracketed host with port, and IPv6 addresses
+        assert_eq!(
+            RemoteUrl::parse("[git@host:2222]:repo"),
+            Some(url(Some("git"), Some("host"), Some(2222), "repo"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("[::1]:repo"),
+            Some(url(None, Some("::1"), None, "repo"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("git@[fe80::1]:repo"),
+            Some(url(Some("git"), Some("fe80::1"), None, "repo"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("[git@fe80::1]:repo"),
+            Some(url(Some("git"), Some("fe80::1"), None, "repo"))
+        );
+        // IPv6 addresses with a port, and paths containing colons
+        assert_eq!(
+            RemoteUrl::parse("[[::1]:2222]:repo"),
+            Some(url(None, Some("::1"), Some(2222), "repo"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("[git@[fe80::1]:2222]:~/repo"),
+            Some(url(Some("git"), Some("fe80::1"), Some(2222), "~/repo"))
+        );
+        assert_eq!(
+            RemoteUrl::parse("git@[::1]:a:b"),
+            Some(url(Some("git"), Some("::1"), None, "a:b"))
+        );
+        assert_eq!(RemoteUrl::parse("[host:port]:repo"), None);
+        assert_eq!(RemoteUrl::parse("[[::1]:port]:repo"), None);
+        assert_eq!(RemoteUrl::parse("git@[::1]x:repo"), None);
+        // Unbalanced brackets make it a local path
+        assert_eq!(RemoteUrl::parse("[[::1]:2222:repo").unwrap().scheme, "file");
+        // Without brackets, the first colon of an IPv6 address ends the host
+        assert_eq!(RemoteUrl::parse("git@::1:repo"), None);
+        assert_eq!(RemoteUrl::parse(":repo"), None);
+        assert_eq!(RemoteUrl::parse("git@:repo"), None);
+        // A slash before the first colon makes it a local path
+        assert_eq!(RemoteUrl::parse("./host:repo").unwrap().scheme, "file");
+        assert_eq!(RemoteUrl::parse("dir/host:repo").unwrap().scheme, "file");
+        assert_eq!(RemoteUrl::parse("repo").unwrap().scheme, "file");
+    }
+
+    #[test]
+    fn test_remote_url_host_port() {
+        let host_port = |url| RemoteUrl::parse(url).unwrap().host_port();
+        assert_eq!(
+            host_port("https://github.com/foo").as_deref(),
+            Some("github.com")
+        );
+        assert_eq!(
+            host_port("http://user@localhost:8080/repo").as_deref(),
+            Some("localhost:8080")
+        );
+        assert_eq!(
+            host_port("ssh://[::1]:22/repo").as_deref(),
+            Some("[::1]:22")
+        );
+        assert_eq!(
+            host_port("[git@[::1]:2222]:repo").as_deref(),
+            Some("[::1]:2222")
+        );
+        assert_eq!(host_port("git@[::1]:repo").as_deref(), Some("[::1]"));
+        assert_eq!(host_port("/local/path"), None);
+    }
+}
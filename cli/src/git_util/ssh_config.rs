@@ -0,0 +1,477 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use itertools::Itertools;
+
+use super::expand_git_path;
+use super::remote_url::RemoteUrl;
+
+/// Maximum nesting level of `Include` directives, as in OpenSSH.
+pub const SSH_CONFIG_MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Subset of `ssh_config(5)` that's needed to pick SSH keys for a host.
+#[derive(Debug, Default)]
+pub struct SshConfig {
+    /// Directives in file order, along with the `Host` block they belong to.
+    pub directives: Vec<SshConfigDirective>,
+    /// Files the config was read from, with their modification times.
+    pub files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// Parsed ssh configs, keyed by path, so that the files aren't parsed again
+/// for every remote and authentication attempt.
+pub static SSH_CONFIGS: Mutex<BTreeMap<PathBuf, Arc<SshConfig>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug)]
+pub struct SshConfigDirective {
+    /// Patterns of the enclosing `Host` block, or `None` at top level. A
+    /// `Match` block is represented by an empty list, which never matches.
+    pub host_patterns: Option<Vec<String>>,
+    /// Lowercased keyword.
+    pub keyword: String,
+    pub value: String,
+}
+
+impl SshConfig {
+    /// Loads the config file at `path`. `Include`s are resolved relative to
+    /// `ssh_dir`.
+    pub fn load(path: &Path, ssh_dir: &Path) -> std::io::Result<Self> {
+        let mut config = SshConfig::default();
+        config.parse_file(path, ssh_dir, &mut None, 0)?;
+        Ok(config)
+    }
+
+    /// Like `load()`, but reuses the config loaded before by this process if
+    /// none of the files it was read from has been modified since. Files newly
+    /// matching an `Include` aren't noticed.
+    pub fn load_cached(path: &Path, ssh_dir: &Path) -> std::io::Result<Arc<Self>> {
+        let mut configs = SSH_CONFIGS.lock().unwrap();
+        if let Some(config) = configs.get(path).filter(|config| config.is_up_to_date()) {
+            return Ok(config.clone());
+        }
+        let config = Arc::new(SshConfig::load(path, ssh_dir)?);
+        configs.insert(path.to_owned(), config.clone());
+        Ok(config)
+    }
+
+    pub fn is_up_to_date(&self) -> bool {
+        self.files
+            .iter()
+            .all(|(path, modified)| file_modified_time(path) == *modified)
+    }
+
+    pub fn parse_file(
+        &mut self,
+        path: &Path,
+        ssh_dir: &Path,
+        host_patterns: &mut Option<Vec<String>>,
+        depth: usize,
+    ) -> std::io::Result<()> {
+        self.files.push((path.to_owned(), file_modified_time(path)));
+        let content = std::fs::read_to_string(path)?;
+        for line in content.lines() {
+            let Some((keyword, value)) = split_ssh_config_line(line) else {
+                continue;
+            };
+            match keyword.as_str() {
+                "host" => {
+                    *host_patterns = Some(value.split_whitespace().map(str::to_owned).collect());
+                }
+                "match" => {
+                    // Match criteria aren't supported. Ignore the whole block.
+                    *host_patterns = Some(vec![]);
+                }
+                "include" => {
+                    if depth >= SSH_CONFIG_MAX_INCLUDE_DEPTH {
+                        tracing::info!(?path, "ssh config includes nested too deeply");
+                        continue;
+                    }
+                    for include_path in resolve_ssh_config_include(&value, ssh_dir) {
+                        // An included file may start a new Host block, which
+                        // shouldn't leak to the including file.
+                        let mut included_host_patterns = host_patterns.clone();
+                        if let Err(err) = self.parse_file(
+                            &include_path,
+                            ssh_dir,
+                            &mut included_host_patterns,
+                            depth + 1,
+                        ) {
+                            tracing::info!(path = ?include_path, %err, "failed to read ssh config");
+                        }
+                    }
+                }
+                _ => self.directives.push(SshConfigDirective {
+                    host_patterns: host_patterns.clone(),
+                    keyword,
+                    value,
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the values of `keyword` that apply to `host`, in file order.
+    pub fn values_for_host<'a>(
+        &'a self,
+        host: &'a str,
+        keyword: &'a str,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        self.directives
+            .iter()
+            .filter(move |directive| directive.keyword == keyword)
+            .filter(move |directive| match &directive.host_patterns {
+                None => true,
+                Some(patterns) => ssh_host_patterns_match(patterns, host),
+            })
+            .map(|directive| directive.value.as_str())
+    }
+
+    /// Returns the `IdentityFile` paths configured for `host`.
+    pub fn identity_files(&self, host: &str) -> Vec<PathBuf> {
+        self.values_for_host(host, "identityfile")
+            .filter(|value| !value.eq_ignore_ascii_case("none"))
+            .map(expand_git_path)
+            .collect()
+    }
+
+    /// Returns the `Port` configured for `host`. As in ssh, the first value
+    /// obtained wins.
+    pub fn port(&self, host: &str) -> Option<u16> {
+        let value = self.values_for_host(host, "port").next()?;
+        let port = value.parse().ok();
+        if port.is_none() {
+            tracing::info!(host, value, "invalid port in ssh config");
+        }
+        port
+    }
+}
+
+/// Returns `url` with the port set for its host in the ssh `config` file in
+/// `ssh_dir`, if it's an SSH URL without a port. libgit2 doesn't read the ssh
+/// config, so it would connect to port 22 otherwise. Scp-like URLs are turned
+/// into `ssh://` URLs, since they can't have a port.
+pub fn ssh_url_with_configured_port(ssh_dir: &Path, url: &str) -> Option<String> {
+    let parsed_url = RemoteUrl::parse(url).filter(RemoteUrl::is_ssh)?;
+    let host = parsed_url.host.filter(|_| parsed_url.port.is_none())?;
+    let port = match SshConfig::load_cached(&ssh_dir.join("config"), ssh_dir) {
+        Ok(config) => config.port(host)?,
+        Err(err) => {
+            tracing::info!(%err, "failed to read ssh config");
+            return None;
+        }
+    };
+    if port == 22 {
+        return None;
+    }
+    let user = parsed_url
+        .user
+        .map(|user| format!("{user}@"))
+        .unwrap_or_default();
+    let host = if host.contains(':') {
+        format!("[{host}]")
+    } else {
+        host.to_owned()
+    };
+    // A relative path of an scp-like URL is relative to the home directory.
+    let path = parsed_url.path;
+    let path = if path.is_empty() || path.starts_with('/') {
+        path.to_owned()
+    } else if path.starts_with('~') {
+        format!("/{path}")
+    } else {
+        format!("/~/{path}")
+    };
+    tracing::info!(host, port, "using port from ssh config");
+    Some(format!("{}://{user}{host}:{port}{path}", parsed_url.scheme))
+}
+
+pub fn file_modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Splits `Keyword value` or `Keyword=value` into lowercased keyword and
+/// unquoted value. Returns `None` for blank lines and comments.
+pub fn split_ssh_config_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (keyword, value) = line
+        .split_once(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or((line, ""));
+    let value = value.trim_start_matches(|c: char| c.is_whitespace() || c == '=');
+    let value = value.trim_end();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    Some((keyword.to_ascii_lowercase(), value.to_owned()))
+}
+
+/// Expands the (possibly glob) path of an `Include` directive.
+pub fn resolve_ssh_config_include(value: &str, ssh_dir: &Path) -> Vec<PathBuf> {
+    value
+        .split_whitespace()
+        .flat_map(|pattern| {
+            let path = expand_git_path(pattern);
+            let path = if path.is_absolute() {
+                path
+            } else {
+                ssh_dir.join(path)
+            };
+            let Some(path_str) = path.to_str() else {
+                return vec![];
+            };
+            match glob::glob(path_str) {
+                Ok(paths) => paths.filter_map(Result::ok).sorted().collect(),
+                Err(_) => vec![path],
+            }
+        })
+        .collect()
+}
+
+/// Matches `host` against the patterns of a `Host` line. A matching negated
+/// pattern (`!pattern`) rejects the host regardless of the other patterns.
+pub fn ssh_host_patterns_match(patterns: &[String], host: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        if let Some(pattern) = pattern.strip_prefix('!') {
+            if ssh_host_pattern_matches(pattern, host) {
+                return false;
+            }
+        } else if ssh_host_pattern_matches(pattern, host) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// Matches `host` against a pattern which may contain `*` and `?` wildcards.
+pub fn ssh_host_pattern_matches(pattern: &str, host: &str) -> bool {
+    fn matches(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some((b'*', rest)) => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+            Some((b'?', rest)) => !text.is_empty() && matches(rest, &text[1..]),
+            Some((c, rest)) => text
+                .split_first()
+                .is_some_and(|(t, text)| c.eq_ignore_ascii_case(t) && matches(rest, text)),
+        }
+    }
+    matches(pattern.as_bytes(), host.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_ssh_host_patterns_match() {
+        let patterns = |s: &str| s.split_whitespace().map(str::to_owned).collect_vec();
+        assert!(ssh_host_patterns_match(&patterns("*"), "github.com"));
+        assert!(ssh_host_patterns_match(
+            &patterns("github.com"),
+            "GitHub.com"
+        ));
+        assert!(ssh_host_patterns_match(
+            &patterns("*.example.com"),
+            "git.example.com"
+        ));
+        assert!(!ssh_host_patterns_match(
+            &patterns("*.example.com"),
+            "example.com"
+        ));
+        assert!(ssh_host_patterns_match(&patterns("foo bar?"), "bar1"));
+        assert!(!ssh_host_patterns_match(&patterns("foo bar?"), "bar12"));
+        assert!(!ssh_host_patterns_match(
+            &patterns("*.example.com !internal.example.com"),
+            "internal.example.com"
+        ));
+        assert!(!ssh_host_patterns_match(&patterns("!foo"), "bar"));
+        assert!(!ssh_host_patterns_match(&[], "foo"));
+    }
+
+    #[test]
+    fn test_split_ssh_config_line() {
+        let split = split_ssh_config_line;
+        assert_eq!(split(""), None);
+        assert_eq!(split("  # comment"), None);
+        assert_eq!(
+            split("  IdentityFile ~/.ssh/id_work"),
+            Some(("identityfile".to_owned(), "~/.ssh/id_work".to_owned()))
+        );
+        assert_eq!(
+            split("IdentityFile=\"/path with/spaces\" "),
+            Some(("identityfile".to_owned(), "/path with/spaces".to_owned()))
+        );
+        assert_eq!(
+            split("Host = foo bar"),
+            Some(("host".to_owned(), "foo bar".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_ssh_config_identity_files() {
+        let temp_dir = testutils::new_temp_dir();
+        let ssh_dir = temp_dir.path();
+        fs::create_dir(ssh_dir.join("config.d")).unwrap();
+        fs::write(
+            ssh_dir.join("config"),
+            "
+                IdentityFile /keys/global
+
+                Host work
+                  HostName github.com
+                  IdentityFile /keys/work
+
+                Include config.d/*
+
+                Match host work
+                  IdentityFile /keys/match
+
+                Host *.example.com !skip.example.com
+                  IdentityFile none
+                  IdentityFile /keys/example
+            ",
+        )
+        .unwrap();
+        fs::write(
+            ssh_dir.join("config.d").join("extra"),
+            "
+                Host work
+                  IdentityFile /keys/included
+            ",
+        )
+        .unwrap();
+        let config = SshConfig::load(&ssh_dir.join("config"), ssh_dir).unwrap();
+        assert_eq!(
+            config.identity_files("work"),
+            ["/keys/global", "/keys/work", "/keys/included"].map(PathBuf::from)
+        );
+        assert_eq!(
+            config.identity_files("git.example.com"),
+            ["/keys/global", "/keys/example"].map(PathBuf::from)
+        );
+        assert_eq!(
+            config.identity_files("skip.example.com"),
+            ["/keys/global"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn test_ssh_url_with_configured_port() {
+        let temp_dir = testutils::new_temp_dir();
+        let ssh_dir = temp_dir.path();
+        fs::write(
+            ssh_dir.join("config"),
+            "
+                Host git.example.com ::1
+                  Port 2222
+                  Port 3333
+
+                Host default.example.com
+                  Port 22
+
+                Host invalid.example.com
+                  Port ssh
+            ",
+        )
+        .unwrap();
+        let url_with_port = |url| ssh_url_with_configured_port(ssh_dir, url);
+        assert_eq!(
+            url_with_port("ssh://me@git.example.com/repo").as_deref(),
+            Some("ssh://me@git.example.com:2222/repo")
+        );
+        assert_eq!(
+            url_with_port("git@git.example.com:org/repo.git").as_deref(),
+            Some("ssh://git@git.example.com:2222/~/org/repo.git")
+        );
+        assert_eq!(
+            url_with_port("git.example.com:/srv/repo").as_deref(),
+            Some("ssh://git.example.com:2222/srv/repo")
+        );
+        assert_eq!(
+            url_with_port("git.example.com:~alice/repo").as_deref(),
+            Some("ssh://git.example.com:2222/~alice/repo")
+        );
+        assert_eq!(
+            url_with_port("ssh://[::1]/repo").as_deref(),
+            Some("ssh://[::1]:2222/repo")
+        );
+        // The port in the URL takes precedence
+        assert_eq!(url_with_port("ssh://git.example.com:1234/repo"), None);
+        assert_eq!(url_with_port("ssh://default.example.com/repo"), None);
+        assert_eq!(url_with_port("ssh://invalid.example.com/repo"), None);
+        assert_eq!(url_with_port("ssh://other.example.com/repo"), None);
+        assert_eq!(url_with_port("https://git.example.com/repo"), None);
+    }
+
+    #[test]
+    fn test_ssh_config_include_loop() {
+        let temp_dir = testutils::new_temp_dir();
+        let ssh_dir = temp_dir.path();
+        fs::write(
+            ssh_dir.join("config"),
+            "Include config\nIdentityFile /keys/loop\n",
+        )
+        .unwrap();
+        let config = SshConfig::load(&ssh_dir.join("config"), ssh_dir).unwrap();
+        assert_eq!(
+            config.identity_files("any").len(),
+            SSH_CONFIG_MAX_INCLUDE_DEPTH + 1
+        );
+    }
+
+    #[test]
+    fn test_ssh_config_load_cached() {
+        let temp_dir = testutils::new_temp_dir();
+        let ssh_dir = temp_dir.path();
+        let config_path = ssh_dir.join("config");
+        let included_path = ssh_dir.join("included");
+        fs::write(&config_path, "IdentityFile /keys/a\nInclude included\n").unwrap();
+        fs::write(&included_path, "IdentityFile /keys/b\n").unwrap();
+        let config = SshConfig::load_cached(&config_path, ssh_dir).unwrap();
+        assert_eq!(
+            config.identity_files("any"),
+            ["/keys/a", "/keys/b"].map(PathBuf::from)
+        );
+        let cached = SshConfig::load_cached(&config_path, ssh_dir).unwrap();
+        assert!(Arc::ptr_eq(&config, &cached));
+
+        // Modifying an included file invalidates the cache
+        fs::write(&included_path, "IdentityFile /keys/c\n").unwrap();
+        let modified = SystemTime::now() + Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&included_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let config = SshConfig::load_cached(&config_path, ssh_dir).unwrap();
+        assert_eq!(
+            config.identity_files("any"),
+            ["/keys/a", "/keys/c"].map(PathBuf::from)
+        );
+    }
+}
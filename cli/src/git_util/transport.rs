@@ -0,0 +1,360 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{self, Read, Write};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, Once};
+
+use jj_lib::settings::UserSettings;
+
+use super::proxy::{ssh_proxy, ssh_proxy_for_url, SshProxy, DEFAULT_SSH_PROXY_COMMAND};
+use super::remote_url::RemoteUrl;
+use super::RemoteTimeouts;
+use crate::command_error::{user_error, user_error_with_message, CommandError};
+
+/// Returns the command to connect to SSH remotes with, which is
+/// `$GIT_SSH_COMMAND`, or else `core.sshCommand` in the Git config.
+pub fn ssh_command(
+    git_config: Option<&git2::Config>,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    env_var("GIT_SSH_COMMAND")
+        .or_else(|| git_config?.get_string("core.sshCommand").ok())
+        .filter(|command| !command.trim().is_empty())
+}
+
+/// Makes SSH remotes be connected to by running the command set by
+/// `$GIT_SSH_COMMAND` or `core.sshCommand`, as Git does. This allows for SSH
+/// features that libgit2 doesn't support, like `ProxyJump`. The command is
+/// also used, defaulting to `ssh`, to connect through the SOCKS5 proxy set by
+/// `git.ssh-proxy` or `$JJ_SSH_PROXY`. If there's neither a command nor a
+/// proxy, libgit2's built-in SSH transport is used.
+pub fn use_configured_ssh_command(
+    settings: &UserSettings,
+    git_repo: &git2::Repository,
+) -> Result<(), CommandError> {
+    static REGISTERED: Once = Once::new();
+    let git_config = git_repo.config().ok();
+    let env_var = |name: &str| std::env::var(name).ok();
+    let command = ssh_command(git_config.as_ref(), env_var);
+    let configured_proxy = settings.config().get_string("git.ssh-proxy").ok();
+    let proxy = ssh_proxy(configured_proxy.as_deref()).map_err(user_error)?;
+    if command.is_none() && proxy.is_none() {
+        return Ok(());
+    }
+    let command = command.unwrap_or_else(|| "ssh".to_owned());
+    let proxy = proxy.map(|address| SshProxy {
+        address,
+        command: settings
+            .config()
+            .get_string("git.ssh-proxy-command")
+            .unwrap_or_else(|_| DEFAULT_SSH_PROXY_COMMAND.to_owned()),
+        connect_timeout: RemoteTimeouts::from_settings(settings).connect,
+    });
+    let mut result = Ok(());
+    REGISTERED.call_once(|| {
+        tracing::info!(command, ?proxy, "using ssh command");
+        // Scp-like URLs are handled by the transport of the "ssh" scheme.
+        for scheme in ["ssh", "ssh+git", "git+ssh"] {
+            let command = command.clone();
+            let proxy = proxy.clone();
+            let factory = move |remote: &git2::Remote<'_>| {
+                let subtransport = SshCommandTransport {
+                    command: command.clone(),
+                    proxy: proxy.clone(),
+                    stream: Mutex::new(None),
+                };
+                git2::transport::Transport::smart(remote, false, subtransport)
+            };
+            // SAFETY: This is called before any remote is connected to, and
+            // only once, so no other thread can be using the transports.
+            if let Err(err) = unsafe { git2::transport::register(scheme, factory) } {
+                result = Err(err);
+                return;
+            }
+        }
+    });
+    result.map_err(|err| user_error_with_message("Failed to set up the SSH command", err))
+}
+
+/// Quotes `arg` for a POSIX shell as Git does.
+pub fn shell_quote(arg: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in arg.chars() {
+        match c {
+            '\'' | '!' => {
+                quoted.push_str("'\\");
+                quoted.push(c);
+                quoted.push('\'');
+            }
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Returns the arguments to pass to the SSH command to run `service`, e.g.
+/// `git-upload-pack`, for the repository at `url`.
+pub fn ssh_command_args(url: &RemoteUrl, service: &str) -> Option<Vec<String>> {
+    let host = url.host?;
+    let mut args = vec![];
+    if let Some(port) = url.port {
+        args.extend(["-p".to_owned(), port.to_string()]);
+    }
+    args.push(match url.user {
+        Some(user) => format!("{user}@{host}"),
+        None => host.to_owned(),
+    });
+    // `ssh://host/~/repo` refers to a path relative to the home directory.
+    let path = url
+        .path
+        .strip_prefix('/')
+        .filter(|path| path.starts_with('~'));
+    args.push(format!(
+        "{service} {}",
+        shell_quote(path.unwrap_or(url.path))
+    ));
+    Some(args)
+}
+
+/// Runs the SSH command to talk to the remote, like Git's `ssh` transport.
+pub struct SshCommandTransport {
+    pub command: String,
+    pub proxy: Option<SshProxy>,
+    pub stream: Mutex<Option<SshCommandStream>>,
+}
+
+impl git2::transport::SmartSubtransport for SshCommandTransport {
+    fn action(
+        &self,
+        url: &str,
+        action: git2::transport::Service,
+    ) -> Result<Box<dyn git2::transport::SmartSubtransportStream>, git2::Error> {
+        use git2::transport::Service;
+        let service = match action {
+            Service::UploadPackLs | Service::UploadPack => "git-upload-pack",
+            Service::ReceivePackLs | Service::ReceivePack => "git-receive-pack",
+        };
+        let mut stream = self.stream.lock().unwrap();
+        // The pack is transferred over the same connection as the refs.
+        if let (Service::UploadPack | Service::ReceivePack, Some(stream)) = (action, &*stream) {
+            return Ok(Box::new(stream.clone()));
+        }
+        let invalid_url = || git2::Error::from_str(&format!("Invalid SSH URL: {url}"));
+        let url = RemoteUrl::parse(url)
+            .filter(RemoteUrl::is_ssh)
+            .ok_or_else(invalid_url)?;
+        let mut args = ssh_command_args(&url, service).ok_or_else(invalid_url)?;
+        let env_var = |name: &str| std::env::var(name).ok();
+        if let Some(proxy) = ssh_proxy_for_url(self.proxy.as_ref(), &url, env_var) {
+            proxy
+                .check_command_exists(env_var)
+                .map_err(|message| git2::Error::from_str(&message))?;
+            proxy.check_reachable().map_err(|err| {
+                git2::Error::new(
+                    git2::ErrorCode::GenericError,
+                    git2::ErrorClass::Net,
+                    format!(
+                        "Failed to connect to the SOCKS5 proxy {}: {err}",
+                        proxy.address
+                    ),
+                )
+            })?;
+            args.splice(0..0, proxy.ssh_args());
+        }
+        let new_stream = SshCommandStream::spawn(&self.command, &args).map_err(|err| {
+            git2::Error::from_str(&format!(
+                "Failed to run the SSH command {}: {err}",
+                self.command
+            ))
+        })?;
+        *stream = Some(new_stream.clone());
+        Ok(Box::new(new_stream))
+    }
+
+    fn close(&self) -> Result<(), git2::Error> {
+        if let Some(stream) = self.stream.lock().unwrap().take() {
+            stream.close();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SshCommandTransport {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.get_mut().unwrap().take() {
+            stream.close();
+        }
+    }
+}
+
+/// The stdin and stdout of the SSH command, which are the connection to the
+/// remote. The stderr of the command is shown to the user.
+#[derive(Clone)]
+pub struct SshCommandStream {
+    pub command: String,
+    pub child: Arc<Mutex<std::process::Child>>,
+    pub stdin: Arc<Mutex<Option<std::process::ChildStdin>>>,
+    pub stdout: Arc<Mutex<std::process::ChildStdout>>,
+}
+
+impl SshCommandStream {
+    pub fn spawn(command: &str, args: &[String]) -> io::Result<Self> {
+        // Like Git, run the command by the shell, so it can contain arguments.
+        #[cfg(unix)]
+        let mut cmd = {
+            let mut cmd = std::process::Command::new("sh");
+            cmd.arg("-c").arg(format!("{command} \"$@\"")).arg(command);
+            cmd
+        };
+        #[cfg(not(unix))]
+        let mut cmd = {
+            let mut words = command.split_whitespace();
+            let mut cmd = std::process::Command::new(words.next().unwrap_or_default());
+            cmd.args(words);
+            cmd
+        };
+        tracing::info!(command, ?args, "running ssh command");
+        let mut child = cmd
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        Ok(SshCommandStream {
+            command: command.to_owned(),
+            child: Arc::new(Mutex::new(child)),
+            stdin: Arc::new(Mutex::new(Some(stdin))),
+            stdout: Arc::new(Mutex::new(stdout)),
+        })
+    }
+
+    /// Closes the connection and waits for the command to exit.
+    pub fn close(&self) {
+        self.stdin.lock().unwrap().take();
+        match self.child.lock().unwrap().wait() {
+            Ok(status) if !status.success() => tracing::info!(%status, "ssh command failed"),
+            Ok(_) => {}
+            Err(err) => tracing::info!(%err, "failed to wait for ssh command"),
+        }
+    }
+}
+
+impl Read for SshCommandStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.lock().unwrap().read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            // Explain why the connection was closed if the command failed.
+            // Its error message should have been printed to stderr.
+            let status = self.child.lock().unwrap().wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!(
+                    "The SSH command {} failed with {status}",
+                    self.command
+                )));
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Write for SshCommandStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut *self.stdin.lock().unwrap() {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *self.stdin.lock().unwrap() {
+            Some(stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_ssh_command() {
+        let temp_dir = testutils::new_temp_dir();
+        let config_path = temp_dir.path().join("config");
+        let mut git_config = git2::Config::open(&config_path).unwrap();
+        let no_env = |_: &str| None;
+        assert_eq!(ssh_command(None, no_env), None);
+        assert_eq!(ssh_command(Some(&git_config), no_env), None);
+
+        git_config
+            .set_str("core.sshCommand", "ssh -F config")
+            .unwrap();
+        assert_eq!(
+            ssh_command(Some(&git_config), no_env).as_deref(),
+            Some("ssh -F config")
+        );
+
+        // $GIT_SSH_COMMAND takes precedence over the Git config.
+        let env = HashMap::from([("GIT_SSH_COMMAND", "ssh -v")]);
+        let env_var = |name: &str| env.get(name).map(|value| value.to_string());
+        assert_eq!(
+            ssh_command(Some(&git_config), env_var).as_deref(),
+            Some("ssh -v")
+        );
+
+        // An empty command is treated as unset.
+        let env = HashMap::from([("GIT_SSH_COMMAND", " ")]);
+        let env_var = |name: &str| env.get(name).map(|value| value.to_string());
+        assert_eq!(ssh_command(None, env_var), None);
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("repo.git"), "'repo.git'");
+        assert_eq!(shell_quote("my repo"), "'my repo'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("wow!"), r"'wow'\!''");
+    }
+
+    #[test]
+    fn test_ssh_command_args() {
+        let args = |url: &str| {
+            let url = RemoteUrl::parse(url).unwrap();
+            ssh_command_args(&url, "git-upload-pack")
+        };
+        assert_eq!(
+            args("ssh://git@example.com/repo.git").unwrap(),
+            ["git@example.com", "git-upload-pack '/repo.git'"]
+        );
+        assert_eq!(
+            args("ssh://example.com:2222/repo.git").unwrap(),
+            ["-p", "2222", "example.com", "git-upload-pack '/repo.git'"]
+        );
+        assert_eq!(
+            args("ssh://example.com/~/repo.git").unwrap(),
+            ["example.com", "git-upload-pack '~/repo.git'"]
+        );
+        assert_eq!(
+            args("git@example.com:path/to/repo.git").unwrap(),
+            ["git@example.com", "git-upload-pack 'path/to/repo.git'"]
+        );
+        assert_eq!(args("file:///path/to/repo.git"), None);
+    }
+}
@@ -4,6 +4,7 @@ use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
 use crossterm::terminal::{Clear, ClearType};
+use itertools::Itertools as _;
 use jj_lib::fmt_util::binary_prefix;
 use jj_lib::git;
 use jj_lib::repo_path::RepoPath;
@@ -16,6 +17,8 @@ pub struct Progress {
     next_print: Instant,
     rate: RateEstimate,
     buffer: String,
+    /// Length of the escape sequences at the start of the buffer.
+    control_chars: usize,
     guard: Option<CleanupGuard>,
 }
 
@@ -25,14 +28,19 @@ impl Progress {
             next_print: now + INITIAL_DELAY,
             rate: RateEstimate::new(),
             buffer: String::new(),
+            control_chars: 0,
             guard: None,
         }
     }
 
+    /// Displays `progress` of the current transfer. If the transfer is part
+    /// of an operation on several remotes, `aggregate` adds the remote being
+    /// transferred and the bytes received from all of them so far.
     pub fn update(
         &mut self,
         now: Instant,
         progress: &git::Progress,
+        aggregate: Option<&AggregateProgress>,
         output: &mut ProgressOutput,
     ) -> io::Result<()> {
         use std::fmt::Write as _;
@@ -46,9 +54,72 @@ impl Progress {
         let rate = progress
             .bytes_downloaded
             .and_then(|x| self.rate.update(now, x));
-        if now < self.next_print {
+        if !self.start_frame(now, output) {
+            return Ok(());
+        }
+        if let Some(label) = aggregate.and_then(|aggregate| aggregate.label()) {
+            write!(self.buffer, "{label} ").unwrap();
+        }
+        write!(self.buffer, "{: >3.0}% ", 100.0 * progress.overall).unwrap();
+        if let Some(received) = progress.bytes_downloaded {
+            write!(self.buffer, "{:>9} ", format_bytes(received as f32)).unwrap();
+            if let Some(total) = estimate_total_bytes(progress) {
+                write!(self.buffer, "/ {:>9} ", format_bytes(total)).unwrap();
+            }
+            if let Some(aggregate) = aggregate.filter(|aggregate| aggregate.remote_count > 1) {
+                let total = format_bytes(aggregate.total_bytes() as f32);
+                write!(self.buffer, "({total} total) ").unwrap();
+            }
+        }
+        if let Some(estimate) = rate {
+            write!(self.buffer, "at {:>9}/s ", format_bytes(estimate)).unwrap();
+            if let Some(eta) = estimate_remaining_time(progress, estimate) {
+                write!(self.buffer, "ETA {} ", format_duration(eta)).unwrap();
+            }
+        }
+
+        self.finish_frame(progress.overall, output)
+    }
+
+    /// Displays the `progress` of a push.
+    pub fn update_push(
+        &mut self,
+        now: Instant,
+        progress: &git::PushProgress,
+        output: &mut ProgressOutput,
+    ) -> io::Result<()> {
+        let writing = progress.phase == git::PushPhase::WritingObjects;
+        if writing && progress.current == progress.total {
+            write!(output, "\r{}", Clear(ClearType::CurrentLine))?;
+            output.flush()?;
+            return Ok(());
+        }
+
+        let rate = if writing {
+            self.rate.update(now, progress.sent_bytes)
+        } else {
+            None
+        };
+        if !self.start_frame(now, output) {
             return Ok(());
         }
+        write_push_status(&mut self.buffer, progress, rate).unwrap();
+        let fraction = if progress.total == 0 {
+            0.0
+        } else {
+            progress.current as f32 / progress.total as f32
+        };
+        self.finish_frame(fraction, output)
+    }
+
+    /// Starts a new line in the buffer, or returns false if it isn't time to
+    /// repaint yet.
+    fn start_frame(&mut self, now: Instant, output: &mut ProgressOutput) -> bool {
+        use std::fmt::Write as _;
+
+        if now < self.next_print {
+            return false;
+        }
         if self.guard.is_none() {
             let guard = output.output_guard(crossterm::cursor::Show.to_string());
             let guard = CleanupGuard::new(move || {
@@ -57,28 +128,26 @@ impl Progress {
             _ = write!(output, "{}", crossterm::cursor::Hide);
             self.guard = Some(guard);
         }
-        self.next_print = now.min(self.next_print + Duration::from_secs(1) / UPDATE_HZ);
+        // Wait a full frame after each repaint, however late it was, so bursts
+        // of updates from libgit2 are coalesced.
+        self.next_print = now + Duration::from_secs(1) / UPDATE_HZ;
 
         self.buffer.clear();
         write!(self.buffer, "\r{}", Clear(ClearType::CurrentLine)).unwrap();
-        let control_chars = self.buffer.len();
-        write!(self.buffer, "{: >3.0}% ", 100.0 * progress.overall).unwrap();
-        if let Some(total) = progress.bytes_downloaded {
-            let (scaled, prefix) = binary_prefix(total as f32);
-            write!(self.buffer, "{scaled: >5.1} {prefix}B ").unwrap();
-        }
-        if let Some(estimate) = rate {
-            let (scaled, prefix) = binary_prefix(estimate);
-            write!(self.buffer, "at {scaled: >5.1} {prefix}B/s ").unwrap();
-        }
+        self.control_chars = self.buffer.len();
+        true
+    }
 
+    /// Fills the rest of the line with a bar showing `fraction`, and displays
+    /// the buffer.
+    fn finish_frame(&mut self, fraction: f32, output: &mut ProgressOutput) -> io::Result<()> {
         let bar_width = output
             .term_width()
             .map(usize::from)
             .unwrap_or(0)
-            .saturating_sub(self.buffer.len() - control_chars + 2);
+            .saturating_sub(self.buffer.len() - self.control_chars + 2);
         self.buffer.push('[');
-        draw_progress(progress.overall, &mut self.buffer, bar_width);
+        draw_progress(fraction, &mut self.buffer, bar_width);
         self.buffer.push(']');
 
         write!(output, "{}", self.buffer)?;
@@ -87,6 +156,278 @@ impl Progress {
     }
 }
 
+/// Writes e.g. "Writing objects:  50% (10/20) 12.3 MiB at 1.0 MiB/s " like
+/// Git does while pushing.
+fn write_push_status(
+    buffer: &mut String,
+    progress: &git::PushProgress,
+    rate: Option<f32>,
+) -> std::fmt::Result {
+    use std::fmt::Write as _;
+
+    let label = match progress.phase {
+        git::PushPhase::CountingObjects => "Counting objects",
+        git::PushPhase::CompressingObjects => "Compressing objects",
+        git::PushPhase::WritingObjects => "Writing objects",
+    };
+    write!(buffer, "{label}: ")?;
+    if progress.total > 0 {
+        let percent = 100.0 * progress.current as f32 / progress.total as f32;
+        write!(
+            buffer,
+            "{percent: >3.0}% ({}/{}) ",
+            progress.current, progress.total
+        )?;
+    } else {
+        write!(buffer, "{} ", progress.current)?;
+    }
+    if progress.phase == git::PushPhase::WritingObjects {
+        write!(buffer, "{} ", format_bytes(progress.sent_bytes as f32))?;
+        if let Some(rate) = rate {
+            write!(buffer, "at {}/s ", format_bytes(rate))?;
+        }
+    }
+    Ok(())
+}
+
+/// Progress of an operation which transfers from several remotes in a row,
+/// e.g. `jj git fetch --all-remotes`.
+#[derive(Debug)]
+pub struct AggregateProgress {
+    remote_count: usize,
+    /// The 1-based index and name of the remote being transferred.
+    current_remote: Option<(usize, String)>,
+    /// Bytes received from the remotes before the current one.
+    completed_bytes: u64,
+    /// Bytes received from the current remote so far.
+    current_bytes: u64,
+}
+
+impl AggregateProgress {
+    pub fn new(remote_count: usize) -> Self {
+        AggregateProgress {
+            remote_count,
+            current_remote: None,
+            completed_bytes: 0,
+            current_bytes: 0,
+        }
+    }
+
+    /// Moves on to the next remote. The bytes received from the previous one
+    /// are kept in the total.
+    pub fn start_remote(&mut self, name: &str) {
+        let index = self.current_remote.as_ref().map_or(0, |(index, _)| *index) + 1;
+        self.current_remote = Some((index, name.to_owned()));
+        self.completed_bytes += self.current_bytes;
+        self.current_bytes = 0;
+    }
+
+    /// Forgets the bytes received from the current remote, e.g. because the
+    /// transfer is retried.
+    pub fn restart_remote(&mut self) {
+        self.current_bytes = 0;
+    }
+
+    /// Records the progress of the current remote's transfer.
+    pub fn update(&mut self, progress: &git::Progress) {
+        self.current_bytes = progress.received_bytes;
+    }
+
+    /// Bytes received from all remotes so far.
+    pub fn total_bytes(&self) -> u64 {
+        self.completed_bytes + self.current_bytes
+    }
+
+    /// Returns e.g. "remote 2/4: origin", or `None` if there's only one
+    /// remote.
+    fn label(&self) -> Option<String> {
+        let (index, name) = self.current_remote.as_ref()?;
+        (self.remote_count > 1).then(|| format!("remote {index}/{}: {name}", self.remote_count))
+    }
+}
+
+/// Records when a fetch moves on to the next phase, to find out where the time
+/// went.
+#[derive(Clone, Debug)]
+pub struct FetchTimings {
+    start: Instant,
+    /// When the remote started sending objects
+    transfer_start: Option<Instant>,
+    /// When all objects were received and the deltas started being resolved
+    index_start: Option<Instant>,
+    /// When the last progress was reported
+    last_progress: Option<Instant>,
+}
+
+impl FetchTimings {
+    pub fn new(start: Instant) -> Self {
+        FetchTimings {
+            start,
+            transfer_start: None,
+            index_start: None,
+            last_progress: None,
+        }
+    }
+
+    /// Records a progress update of the transfer.
+    pub fn update(&mut self, now: Instant, progress: &git::Progress) {
+        if progress.total_objects > 0 {
+            self.transfer_start.get_or_insert(now);
+            if progress.bytes_downloaded.is_none() {
+                self.index_start.get_or_insert(now);
+            }
+        }
+        self.last_progress = Some(now);
+    }
+
+    /// Returns the duration of each phase, given the time the fetch finished.
+    /// A phase which didn't happen, e.g. because there was nothing to
+    /// transfer, takes no time.
+    pub fn phases(&self, end: Instant) -> [(&'static str, Duration); 4] {
+        let import_start = self.last_progress.unwrap_or(end);
+        let index_start = self.index_start.unwrap_or(import_start);
+        let transfer_start = self.transfer_start.unwrap_or(index_start);
+        [
+            ("negotiation", transfer_start - self.start),
+            ("transfer", index_start - transfer_start),
+            ("indexing", import_start - index_start),
+            ("ref import", end - import_start),
+        ]
+    }
+}
+
+/// Formats the durations of the fetch phases, e.g. "1.20s (negotiation 0.10s,
+/// transfer 1.00s, indexing 0.05s, ref import 0.05s)".
+pub fn format_fetch_timings(timings: &FetchTimings, end: Instant) -> String {
+    let phases = timings.phases(end);
+    format!(
+        "{:.2}s ({})",
+        (end - timings.start).as_secs_f32(),
+        phases
+            .iter()
+            .map(|(name, duration)| format!("{name} {:.2}s", duration.as_secs_f32()))
+            .join(", ")
+    )
+}
+
+/// Displays the messages sent by a Git server with a "remote: " prefix, as Git
+/// does.
+///
+/// Lines ending with "\r" are progress meters, which are updated in place on
+/// a terminal. Otherwise, they're dropped along with their final state since
+/// Git wouldn't have asked the server for them.
+pub struct SidebandWriter {
+    overwrite_lines: bool,
+    /// The incomplete line received so far.
+    pending: Vec<u8>,
+    /// Whether the last line was a progress meter.
+    in_progress: bool,
+}
+
+impl SidebandWriter {
+    pub fn new(overwrite_lines: bool) -> Self {
+        SidebandWriter {
+            overwrite_lines,
+            pending: vec![],
+            in_progress: false,
+        }
+    }
+
+    /// Writes the complete lines in `data`, keeping the rest until it's
+    /// completed by a later message.
+    pub fn write(&mut self, output: &mut dyn io::Write, data: &[u8]) -> io::Result<()> {
+        for &byte in data {
+            match byte {
+                b'\r' => self.write_progress_line(output)?,
+                b'\n' => self.write_line(output)?,
+                _ => self.pending.push(byte),
+            }
+        }
+        output.flush()
+    }
+
+    /// Writes the last line if it wasn't terminated.
+    pub fn finish(&mut self, output: &mut dyn io::Write) -> io::Result<()> {
+        if !self.pending.is_empty() || self.in_progress {
+            self.write_line(output)?;
+        }
+        output.flush()
+    }
+
+    fn write_progress_line(&mut self, output: &mut dyn io::Write) -> io::Result<()> {
+        if self.overwrite_lines {
+            let line = self.take_pending();
+            write!(
+                output,
+                "\r{}remote: {line}\r",
+                Clear(ClearType::CurrentLine)
+            )?;
+        }
+        self.pending.clear();
+        self.in_progress = true;
+        Ok(())
+    }
+
+    fn write_line(&mut self, output: &mut dyn io::Write) -> io::Result<()> {
+        let line = self.take_pending();
+        let was_in_progress = std::mem::replace(&mut self.in_progress, false);
+        if self.overwrite_lines {
+            if was_in_progress && line.is_empty() {
+                // Keep the final state of the progress meter ("\r\n")
+                writeln!(output)?;
+            } else {
+                writeln!(output, "\r{}remote: {line}", Clear(ClearType::CurrentLine))?;
+            }
+        } else if !was_in_progress {
+            writeln!(output, "remote: {line}")?;
+        }
+        Ok(())
+    }
+
+    fn take_pending(&mut self) -> String {
+        let line = String::from_utf8_lossy(&self.pending).trim_end().to_owned();
+        self.pending.clear();
+        line
+    }
+}
+
+/// Formats a size in bytes with a binary prefix, e.g. "12.3 MiB".
+fn format_bytes(bytes: f32) -> String {
+    let (scaled, prefix) = binary_prefix(bytes);
+    format!("{scaled:.1} {prefix}B")
+}
+
+/// Estimates the size of the whole transfer. It isn't known, so it's
+/// extrapolated from the average size of the objects received so far.
+fn estimate_total_bytes(progress: &git::Progress) -> Option<f32> {
+    if progress.received_objects == 0 || progress.total_objects == 0 {
+        return None;
+    }
+    let bytes_per_object = progress.received_bytes as f32 / progress.received_objects as f32;
+    Some(progress.total_objects as f32 * bytes_per_object)
+}
+
+/// Estimates the time left until the transfer is done.
+fn estimate_remaining_time(progress: &git::Progress, rate: f32) -> Option<Duration> {
+    if rate <= 0.0 {
+        return None;
+    }
+    let remaining_bytes = estimate_total_bytes(progress)? - progress.received_bytes as f32;
+    Duration::try_from_secs_f32(remaining_bytes.max(0.0) / rate).ok()
+}
+
+/// Formats a duration as e.g. "5s", "2m05s", or "1h02m".
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 60 * 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / (60 * 60), secs / 60 % 60)
+    }
+}
+
 fn draw_progress(progress: f32, buffer: &mut String, width: usize) {
     const CHARS: [char; 9] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
     const RESOLUTION: usize = CHARS.len() - 1;
@@ -104,7 +445,7 @@ fn draw_progress(progress: f32, buffer: &mut String, width: usize) {
     }
 }
 
-const UPDATE_HZ: u32 = 30;
+const UPDATE_HZ: u32 = 15;
 const INITIAL_DELAY: Duration = Duration::from_millis(250);
 
 struct RateEstimate {
@@ -228,4 +569,182 @@ mod tests {
         assert_eq!(buf, "█████▍    ");
         buf.clear();
     }
+
+    #[test]
+    fn test_estimate_remaining_time() {
+        let progress = |received_bytes, received_objects, total_objects| git::Progress {
+            bytes_downloaded: Some(received_bytes),
+            overall: 0.0,
+            received_bytes,
+            received_objects,
+            total_objects,
+        };
+        assert_eq!(
+            estimate_remaining_time(&progress(1000, 10, 30), 100.0),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            estimate_remaining_time(&progress(1000, 10, 10), 100.0),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(estimate_remaining_time(&progress(0, 0, 30), 100.0), None);
+        assert_eq!(estimate_remaining_time(&progress(1000, 10, 30), 0.0), None);
+    }
+
+    #[test]
+    fn test_fetch_timings() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let progress = |received_objects, total_objects| git::Progress {
+            bytes_downloaded: (received_objects < total_objects).then_some(0),
+            overall: 0.0,
+            received_bytes: 0,
+            received_objects,
+            total_objects,
+        };
+
+        let mut timings = FetchTimings::new(start);
+        timings.update(at(100), &progress(0, 10));
+        timings.update(at(1000), &progress(5, 10));
+        timings.update(at(1100), &progress(10, 10));
+        timings.update(at(1150), &progress(10, 10));
+        assert_eq!(
+            format_fetch_timings(&timings, at(1200)),
+            "1.20s (negotiation 0.10s, transfer 1.00s, indexing 0.05s, ref import 0.05s)"
+        );
+
+        // Nothing was transferred
+        let mut timings = FetchTimings::new(start);
+        timings.update(at(300), &progress(0, 0));
+        assert_eq!(
+            timings.phases(at(500)),
+            [
+                ("negotiation", Duration::from_millis(300)),
+                ("transfer", Duration::ZERO),
+                ("indexing", Duration::ZERO),
+                ("ref import", Duration::from_millis(200)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0.0), "0.0 B");
+        assert_eq!(format_bytes(999.0), "999.0 B");
+        assert_eq!(format_bytes(12.3 * 1024.0 * 1024.0), "12.3 MiB");
+        assert_eq!(format_bytes(45.6 * 1024.0 * 1024.0 * 1024.0), "45.6 GiB");
+    }
+
+    #[test]
+    fn test_estimate_total_bytes() {
+        let progress = |received_bytes, received_objects, total_objects| git::Progress {
+            bytes_downloaded: Some(received_bytes),
+            overall: 0.0,
+            received_bytes,
+            received_objects,
+            total_objects,
+        };
+        assert_eq!(estimate_total_bytes(&progress(1000, 10, 30)), Some(3000.0));
+        assert_eq!(estimate_total_bytes(&progress(0, 0, 30)), None);
+        assert_eq!(estimate_total_bytes(&progress(1000, 10, 0)), None);
+    }
+
+    #[test]
+    fn test_write_push_status() {
+        let status = |phase, current, total, sent_bytes, rate| {
+            let progress = git::PushProgress {
+                phase,
+                current,
+                total,
+                sent_bytes,
+            };
+            let mut buffer = String::new();
+            write_push_status(&mut buffer, &progress, rate).unwrap();
+            buffer
+        };
+        assert_eq!(
+            status(git::PushPhase::CountingObjects, 12, 0, 0, None),
+            "Counting objects: 12 "
+        );
+        assert_eq!(
+            status(git::PushPhase::CompressingObjects, 3, 12, 0, None),
+            "Compressing objects:  25% (3/12) "
+        );
+        assert_eq!(
+            status(
+                git::PushPhase::WritingObjects,
+                6,
+                12,
+                3 * 1024 * 1024,
+                Some(1024.0 * 1024.0)
+            ),
+            "Writing objects:  50% (6/12) 3.0 MiB at 1.0 MiB/s "
+        );
+    }
+
+    #[test]
+    fn test_aggregate_progress() {
+        let progress = |received_bytes| git::Progress {
+            bytes_downloaded: Some(received_bytes),
+            overall: 0.0,
+            received_bytes,
+            received_objects: 0,
+            total_objects: 0,
+        };
+        let mut aggregate = AggregateProgress::new(2);
+        assert_eq!(aggregate.label(), None);
+        aggregate.start_remote("origin");
+        aggregate.update(&progress(100));
+        aggregate.update(&progress(300));
+        assert_eq!(aggregate.label().as_deref(), Some("remote 1/2: origin"));
+        assert_eq!(aggregate.total_bytes(), 300);
+        aggregate.start_remote("upstream");
+        assert_eq!(aggregate.total_bytes(), 300);
+        aggregate.update(&progress(50));
+        assert_eq!(aggregate.label().as_deref(), Some("remote 2/2: upstream"));
+        assert_eq!(aggregate.total_bytes(), 350);
+
+        let mut aggregate = AggregateProgress::new(1);
+        aggregate.start_remote("origin");
+        assert_eq!(aggregate.label(), None);
+    }
+
+    #[test]
+    fn test_sideband_writer() {
+        let write = |overwrite_lines, messages: &[&str]| {
+            let mut writer = SidebandWriter::new(overwrite_lines);
+            let mut output = vec![];
+            for message in messages {
+                writer.write(&mut output, message.as_bytes()).unwrap();
+            }
+            writer.finish(&mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let messages = [
+            "Counting: 1\rCounting: 3\r",
+            "\nCompressing: 50%\rCompressing: 100%, done.\nCreate a pull ",
+            "request:\n  https://example.com/pull\n\nunterminated",
+        ];
+        assert_eq!(
+            write(false, &messages),
+            "remote: Create a pull request:\nremote:   https://example.com/pull\nremote: \n\
+             remote: unterminated\n"
+        );
+        let clear = format!("\r{}", Clear(ClearType::CurrentLine));
+        assert_eq!(
+            write(true, &messages[..2]),
+            format!(
+                "{clear}remote: Counting: 1\r{clear}remote: Counting: 3\r\n{clear}remote: \
+                 Compressing: 50%\r{clear}remote: Compressing: 100%, done.\n{clear}remote: \
+                 Create a pull\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(5)), "5s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m05s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h02m");
+    }
 }
@@ -12,9 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fs::{File, OpenOptions};
 use std::io::{IsTerminal as _, Stderr, StderrLock, Stdout, StdoutLock, Write};
 use std::process::{Child, ChildStdin, Stdio};
 use std::str::FromStr;
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
 use std::thread::JoinHandle;
 use std::{env, fmt, io, mem};
 
@@ -39,6 +43,12 @@ enum UiOutput {
     BuiltinPaged {
         pager: BuiltinPager,
     },
+    /// Output kept in memory, so tests can inspect it.
+    #[cfg(test)]
+    Captured {
+        stdout: CapturedOutput,
+        stderr: CapturedOutput,
+    },
 }
 
 /// A builtin pager
@@ -91,6 +101,23 @@ impl BuiltinPager {
     }
 }
 
+/// Output kept in memory by a `Ui` created by `Ui::captured()`.
+#[cfg(test)]
+#[derive(Default)]
+pub struct CapturedOutput(Mutex<Vec<u8>>);
+
+#[cfg(test)]
+impl std::io::Write for &CapturedOutput {
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
 impl UiOutput {
     fn new_builtin() -> UiOutput {
         UiOutput::BuiltinPaged {
@@ -115,12 +142,16 @@ pub enum UiStdout<'a> {
     Terminal(StdoutLock<'static>),
     Paged(&'a ChildStdin),
     Builtin(&'a BuiltinPager),
+    #[cfg(test)]
+    Captured(&'a CapturedOutput),
 }
 
 pub enum UiStderr<'a> {
     Terminal(StderrLock<'static>),
     Paged(&'a ChildStdin),
     Builtin(&'a BuiltinPager),
+    #[cfg(test)]
+    Captured(&'a CapturedOutput),
 }
 
 macro_rules! for_outputs {
@@ -129,6 +160,8 @@ macro_rules! for_outputs {
             $ty::Terminal($pat) => $expr,
             $ty::Paged($pat) => $expr,
             $ty::Builtin($pat) => $expr,
+            #[cfg(test)]
+            $ty::Captured($pat) => $expr,
         }
     };
 }
@@ -166,6 +199,7 @@ pub struct Ui {
     pager_cmd: CommandNameAndArgs,
     paginate: PaginationChoice,
     progress_indicator: bool,
+    progress_output: ProgressOutputChoice,
     formatter_factory: FormatterFactory,
     output: UiOutput,
 }
@@ -236,6 +270,25 @@ fn pagination_setting(config: &config::Config) -> Result<PaginationChoice, Comma
         .map_err(|err| CommandError::ConfigError(format!("Invalid `ui.paginate`: {err}")))
 }
 
+/// Where the progress display is written.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all(deserialize = "kebab-case"))]
+pub enum ProgressOutputChoice {
+    /// Stderr, if it's a terminal.
+    #[default]
+    Stderr,
+    /// The controlling terminal, even if stderr is redirected.
+    Tty,
+    /// Nowhere.
+    None,
+}
+
+fn progress_output_setting(config: &config::Config) -> Result<ProgressOutputChoice, CommandError> {
+    config
+        .get::<ProgressOutputChoice>("ui.progress-output")
+        .map_err(|err| CommandError::ConfigError(format!("Invalid `ui.progress-output`: {err}")))
+}
+
 fn pager_setting(config: &config::Config) -> Result<CommandNameAndArgs, CommandError> {
     config
         .get::<CommandNameAndArgs>("ui.pager")
@@ -256,15 +309,42 @@ impl Ui {
             pager_cmd: pager_setting(config)?,
             paginate: pagination_setting(config)?,
             progress_indicator,
+            progress_output: progress_output_setting(config)?,
             output: UiOutput::new_terminal(),
         })
     }
 
+    /// Creates a `Ui` which keeps the output in memory instead of writing it
+    /// to the terminal.
+    #[cfg(test)]
+    pub fn captured(config: &config::Config) -> Result<Ui, CommandError> {
+        let mut ui = Ui::with_config(config)?;
+        ui.color = false;
+        ui.formatter_factory = FormatterFactory::prepare(config, false, false)?;
+        ui.output = UiOutput::Captured {
+            stdout: Default::default(),
+            stderr: Default::default(),
+        };
+        Ok(ui)
+    }
+
+    /// Takes the stderr output of a `Ui` created by `Ui::captured()`.
+    #[cfg(test)]
+    pub fn take_captured_stderr(&self) -> String {
+        match &self.output {
+            UiOutput::Captured { stderr, .. } => {
+                String::from_utf8(mem::take(&mut *stderr.0.lock().unwrap())).unwrap()
+            }
+            _ => panic!("output isn't captured"),
+        }
+    }
+
     pub fn reset(&mut self, config: &config::Config) -> Result<(), CommandError> {
         self.color = use_color(color_setting(config));
         self.paginate = pagination_setting(config)?;
         self.pager_cmd = pager_setting(config)?;
         self.progress_indicator = progress_indicator_setting(config);
+        self.progress_output = progress_output_setting(config)?;
         let sanitize = io::stdout().is_terminal();
         self.formatter_factory = FormatterFactory::prepare(config, self.color, sanitize)?;
         Ok(())
@@ -302,6 +382,8 @@ impl Ui {
                 }
             }
             UiOutput::Terminal { .. } | UiOutput::BuiltinPaged { .. } | UiOutput::Paged { .. } => {}
+            #[cfg(test)]
+            UiOutput::Captured { .. } => {}
         }
     }
 
@@ -322,6 +404,8 @@ impl Ui {
             UiOutput::Terminal { stdout, .. } => UiStdout::Terminal(stdout.lock()),
             UiOutput::Paged { child_stdin, .. } => UiStdout::Paged(child_stdin),
             UiOutput::BuiltinPaged { pager } => UiStdout::Builtin(pager),
+            #[cfg(test)]
+            UiOutput::Captured { stdout, .. } => UiStdout::Captured(stdout),
         }
     }
 
@@ -339,6 +423,8 @@ impl Ui {
             UiOutput::Terminal { stderr, .. } => UiStderr::Terminal(stderr.lock()),
             UiOutput::Paged { child_stdin, .. } => UiStderr::Paged(child_stdin),
             UiOutput::BuiltinPaged { pager } => UiStderr::Builtin(pager),
+            #[cfg(test)]
+            UiOutput::Captured { stderr, .. } => UiStderr::Captured(stderr),
         }
     }
 
@@ -354,23 +440,41 @@ impl Ui {
             UiOutput::Paged { child_stdin, .. } => Ok(duplicate_child_stdin(child_stdin)?.into()),
             // Stderr does not get redirected through the built-in pager.
             UiOutput::BuiltinPaged { .. } => Ok(Stdio::inherit()),
+            #[cfg(test)]
+            UiOutput::Captured { .. } => Ok(Stdio::null()),
         }
     }
 
-    /// Whether continuous feedback should be displayed for long-running
-    /// operations
+    /// Whether continuous feedback for long-running operations should be
+    /// displayed on stderr
     pub fn use_progress_indicator(&self) -> bool {
         match &self.output {
-            UiOutput::Terminal { stderr, .. } => self.progress_indicator && stderr.is_terminal(),
+            UiOutput::Terminal { stderr, .. } => {
+                self.progress_indicator
+                    && self.progress_output != ProgressOutputChoice::None
+                    && stderr.is_terminal()
+            }
             UiOutput::Paged { .. } => false,
             UiOutput::BuiltinPaged { .. } => false,
+            #[cfg(test)]
+            UiOutput::Captured { .. } => false,
         }
     }
 
+    /// Stream to display continuous feedback for long-running operations on,
+    /// as configured by `ui.progress-output`.
     pub fn progress_output(&self) -> Option<ProgressOutput> {
-        self.use_progress_indicator().then(|| ProgressOutput {
-            output: io::stderr(),
-        })
+        if !self.progress_indicator || !matches!(self.output, UiOutput::Terminal { .. }) {
+            return None;
+        }
+        let output = match self.progress_output {
+            ProgressOutputChoice::Stderr => io::stderr()
+                .is_terminal()
+                .then(|| ProgressStream::Stderr(io::stderr()))?,
+            ProgressOutputChoice::Tty => ProgressStream::open_tty()?,
+            ProgressOutputChoice::None => return None,
+        };
+        Some(ProgressOutput { output })
     }
 
     pub fn hint(&self) -> LabeledWriter<Box<dyn Formatter + '_>, &'static str> {
@@ -501,9 +605,51 @@ impl Ui {
     }
 }
 
+#[cfg(unix)]
+const TTY_PATH: &str = "/dev/tty";
+#[cfg(windows)]
+const TTY_PATH: &str = "CONOUT$";
+
+#[derive(Debug)]
+enum ProgressStream {
+    Stderr(Stderr),
+    Tty(Arc<File>),
+}
+
+impl ProgressStream {
+    fn open_tty() -> Option<Self> {
+        let file = OpenOptions::new().write(true).open(TTY_PATH).ok()?;
+        file.is_terminal()
+            .then(|| ProgressStream::Tty(Arc::new(file)))
+    }
+
+    fn duplicate(&self) -> Self {
+        match self {
+            ProgressStream::Stderr(_) => ProgressStream::Stderr(io::stderr()),
+            ProgressStream::Tty(file) => ProgressStream::Tty(file.clone()),
+        }
+    }
+}
+
+impl Write for ProgressStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ProgressStream::Stderr(stderr) => stderr.write(buf),
+            ProgressStream::Tty(file) => file.as_ref().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ProgressStream::Stderr(stderr) => stderr.flush(),
+            ProgressStream::Tty(file) => file.as_ref().flush(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ProgressOutput {
-    output: Stderr,
+    output: ProgressStream,
 }
 
 impl ProgressOutput {
@@ -525,14 +671,14 @@ impl ProgressOutput {
     pub fn output_guard(&self, text: String) -> OutputGuard {
         OutputGuard {
             text,
-            output: io::stderr(),
+            output: self.output.duplicate(),
         }
     }
 }
 
 pub struct OutputGuard {
     text: String,
-    output: Stderr,
+    output: ProgressStream,
 }
 
 impl Drop for OutputGuard {
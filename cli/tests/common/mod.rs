@@ -360,6 +360,50 @@ pub fn escaped_fake_diff_editor_path() -> String {
     diff_editor_path.to_str().unwrap().replace('\\', r"\\")
 }
 
+/// Writes a fake `ssh` command to `dir` which runs the remote command locally,
+/// and appends its arguments to `ssh.log` in `dir`. Returns the path to the
+/// command.
+#[cfg(unix)]
+pub fn create_fake_ssh_command(dir: &Path) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let path = dir.join("fake-ssh");
+    std::fs::write(
+        &path,
+        "#!/bin/sh\necho \"$@\" >> \"$(dirname \"$0\")/ssh.log\"\nwhile [ $# -gt 1 ]; do shift; \
+         done\nexec sh -c \"$1\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// Writes a fake Git credential helper to `dir` which answers `get` with the
+/// given username and password, and appends the action and the password it's
+/// given to `credential.log` in `dir`. Returns the path to the helper.
+#[cfg(unix)]
+pub fn create_fake_credential_helper(dir: &Path, username: &str, password: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let path = dir.join("fake-credential-helper");
+    std::fs::write(
+        &path,
+        format!(
+            r#"#!/bin/sh
+log="$(dirname "$0")/credential.log"
+echo "$1" >> "$log"
+sed -n 's/^password=/  password=/p' >> "$log"
+if [ "$1" = get ]; then
+  printf 'username={username}\npassword={password}\n'
+fi
+"#
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
 /// Returns a string with the last line removed.
 ///
 /// Use this to remove the root error message containing platform-specific
@@ -537,7 +537,7 @@ fn test_branch_forget_fetched_branch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--remote=origin"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: feature1@origin [new] tracked
+    branch: feature1@origin [new] tracked (new) -> 9f01a0e04879
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     feature1: mzyxwzks 9f01a0e0 message
@@ -551,7 +551,7 @@ fn test_branch_forget_fetched_branch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--remote=origin"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: feature1@origin [new] tracked
+    branch: feature1@origin [new] tracked (new) -> 9f01a0e04879
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     feature1: mzyxwzks 9f01a0e0 message
@@ -579,7 +579,7 @@ fn test_branch_forget_fetched_branch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--remote=origin"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: feature1@origin [new] tracked
+    branch: feature1@origin [new] tracked (new) -> 38aefb173976
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     feature1: ooosovrs 38aefb17 (empty) another message
@@ -694,10 +694,9 @@ fn test_branch_track_untrack() {
     test_env.add_config("git.auto-local-branch = false");
     let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stderr, @r###"
-    branch: feature1@origin [new] untracked
-    branch: feature2@origin [new] untracked
-    branch: main@origin     [new] untracked
-
+    branch: feature1@origin [new] untracked (new) -> 7b33f6295eda
+    branch: feature2@origin [new] untracked (new) -> 7b33f6295eda
+    branch: main@origin     [new] untracked (new) -> 7b33f6295eda
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     feature1@origin: sptzoqmo 7b33f629 commit 1
@@ -771,10 +770,9 @@ fn test_branch_track_untrack() {
     );
     let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stderr, @r###"
-    branch: feature1@origin [updated] untracked
-    branch: feature2@origin [updated] untracked
-    branch: main@origin     [updated] tracked
-
+    branch: feature1@origin [updated] untracked 7b33f6295eda..40dabdaf4abe
+    branch: feature2@origin [updated] untracked 7b33f6295eda..40dabdaf4abe
+    branch: main@origin     [updated] tracked 7b33f6295eda..40dabdaf4abe
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     feature1: sptzoqmo 7b33f629 commit 1
@@ -807,10 +805,10 @@ fn test_branch_track_untrack() {
     test_env.add_config("git.auto-local-branch = true");
     let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stderr, @r###"
-    branch: feature1@origin [updated] untracked
-    branch: feature2@origin [updated] untracked
-    branch: feature3@origin [new] tracked
-    branch: main@origin     [updated] tracked
+    branch: feature1@origin [updated] untracked 40dabdaf4abe..3f0f86fa0e57
+    branch: feature2@origin [updated] untracked 40dabdaf4abe..3f0f86fa0e57
+    branch: feature3@origin [new] tracked (new) -> 3f0f86fa0e57
+    branch: main@origin     [updated] tracked 40dabdaf4abe..3f0f86fa0e57
     Abandoned 1 commits that are no longer reachable.
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
@@ -868,9 +866,8 @@ fn test_branch_track_untrack_patterns() {
     test_env.add_config("git.auto-local-branch = false");
     let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stderr, @r###"
-    branch: feature1@origin [new] untracked
-    branch: feature2@origin [new] untracked
-
+    branch: feature1@origin [new] untracked (new) -> 1336caed678d
+    branch: feature2@origin [new] untracked (new) -> 1336caed678d
     "###);
 
     // Track local branch
@@ -400,15 +400,13 @@ fn test_git_colocated_conflicting_git_refs() {
     test_env.jj_cmd_ok(&workspace_root, &["branch", "create", "main"]);
     let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["branch", "create", "main/sub"]);
     insta::assert_snapshot!(stdout, @"");
-    insta::with_settings!({filters => vec![(": The lock for resource.*", ": ...")]}, {
-        insta::assert_snapshot!(stderr, @r###"
-        Failed to export some branches:
-          main/sub: Failed to set: A lock could not be obtained for reference "refs/heads/main/sub": ...
-        Hint: Git doesn't allow a branch name that looks like a parent directory of
-        another (e.g. `foo` and `foo/bar`). Try to rename the branches that failed to
-        export or their "parent" branches.
-        "###);
-    });
+    insta::assert_snapshot!(stderr, @r###"
+    Failed to export some branches:
+      main/sub: Conflicts with the existing Git ref refs/heads/main
+    Hint: Git doesn't allow a branch name that looks like a parent directory of
+    another. To rename the conflicting branches, run:
+      jj branch rename main/sub main-sub
+    "###);
 }
 
 #[test]
@@ -507,8 +505,8 @@ fn test_git_colocated_fetch_deleted_or_moved_branch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&clone_path, &["git", "fetch"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: B_to_delete@origin [deleted] untracked
-    branch: C_to_move@origin   [updated] tracked
+    branch: B_to_delete@origin [deleted] untracked 929e298ae9ed -> (deleted)
+    branch: C_to_move@origin   [updated] tracked 8d4e006fd635..04fd29df0563
     Abandoned 2 commits that are no longer reachable.
     "###);
     // "original C" and "B_to_delete" are abandoned, as the corresponding branches
@@ -702,6 +700,47 @@ fn test_git_colocated_undo_head_move() {
     "###);
 }
 
+#[test]
+fn test_git_colocate() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let workspace_root = test_env.env_root().join("repo");
+    std::fs::write(workspace_root.join("file"), "contents").unwrap();
+    test_env.jj_cmd_ok(&workspace_root, &["commit", "-m", "initial"]);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "colocate", "--dry-run"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Would move the Git repo from "$TEST_ENV/repo/.jj/repo/store/git" to "$TEST_ENV/repo/.git"
+    Dry-run requested, not moving.
+    "###);
+    assert!(!workspace_root.join(".git").exists());
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "colocate"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Moved the Git repo to "$TEST_ENV/repo/.git"
+    "###);
+    let git_repo = git2::Repository::open(&workspace_root).unwrap();
+    assert!(!git_repo.is_bare());
+    assert!(workspace_root.join(".jj").join(".gitignore").exists());
+    insta::assert_snapshot!(get_log_output(&test_env, &workspace_root), @r###"
+    @  e0e6d5672858dc9a57ec5b772b7c4f3270ed0223
+    ◉  7d308bc9d934c53c6cc52935192e2d6ac5d78cfd HEAD@git initial
+    ◉  0000000000000000000000000000000000000000
+    "###);
+    // The Git index matches the parent of the working-copy commit
+    let statuses = git_repo
+        .statuses(Some(git2::StatusOptions::new().include_ignored(false)))
+        .unwrap();
+    assert!(statuses.is_empty());
+
+    let stderr = test_env.jj_cmd_failure(&workspace_root, &["git", "colocate"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The workspace is already colocated with its Git repo
+    "###);
+}
+
 fn get_log_output_divergence(test_env: &TestEnvironment, repo_path: &Path) -> String {
     let template = r#"
     separate(" ",
@@ -13,7 +13,9 @@
 // limitations under the License.
 use std::path::Path;
 
-use crate::common::TestEnvironment;
+use regex::Regex;
+
+use crate::common::{get_stderr_string, get_stdout_string, TestEnvironment};
 
 /// Creates a remote Git repo containing a branch with the same name
 fn init_git_remote(test_env: &TestEnvironment, remote: &str) {
@@ -111,7 +113,7 @@ fn test_git_fetch_single_remote() {
         .jj_cmd(&repo_path, &["git", "fetch"])
         .assert()
         .success()
-        .stderr("Fetching from the only existing remote: rem1\nbranch: rem1@rem1 [new] tracked\n");
+        .stderr("Fetching from the only existing remote: rem1\nbranch: rem1@rem1 [new] tracked (new) -> 6a21102783e8\n");
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     rem1: qxosxrvv 6a211027 message
       @rem1: qxosxrvv 6a211027 message
@@ -237,7 +239,7 @@ fn test_git_fetch_nonexistent_remote() {
         &["git", "fetch", "--remote", "rem1", "--remote", "rem2"],
     );
     insta::assert_snapshot!(stderr, @r###"
-    branch: rem1@rem1 [new] untracked
+    branch: rem1@rem1 [new] untracked (new) -> 6a21102783e8
     Error: No git remote named 'rem2'
     "###);
     // No remote should have been fetched as part of the failing transaction
@@ -254,13 +256,518 @@ fn test_git_fetch_nonexistent_remote_from_config() {
 
     let stderr = &test_env.jj_cmd_failure(&repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stderr, @r###"
-    branch: rem1@rem1 [new] untracked
+    branch: rem1@rem1 [new] untracked (new) -> 6a21102783e8
     Error: No git remote named 'rem2'
     "###);
     // No remote should have been fetched as part of the failing transaction
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @"");
 }
 
+#[test]
+fn test_git_fetch_verbose_timings() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_git_remote(&test_env, &repo_path, "rem1");
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--verbose"]);
+    let stderr = Regex::new(r"\d+\.\d\ds")
+        .unwrap()
+        .replace_all(&stderr, "<time>");
+    insta::assert_snapshot!(stderr, @r###"
+    Fetching from the only existing remote: rem1
+    branch: rem1@rem1 [new] untracked (new) -> 6a21102783e8
+    Fetched from rem1 in <time> (negotiation <time>, transfer <time>, indexing <time>, ref import <time>)
+    "###);
+}
+
+#[test]
+fn test_git_fetch_verbose_bypasses_summary_threshold() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("git.import-summary-threshold = 0");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_git_remote(&test_env, &repo_path, "rem1");
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Fetching from the only existing remote: rem1
+    Imported 1 branch (1 new)
+    "###);
+
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--verbose"]);
+    let stderr = Regex::new(r"\d+\.\d\ds")
+        .unwrap()
+        .replace_all(&stderr, "<time>");
+    insta::assert_snapshot!(stderr, @r###"
+    Fetching from the only existing remote: rem1
+    branch: rem1@rem1 [new] untracked (new) -> 6a21102783e8
+    Fetched from rem1 in <time> (negotiation <time>, transfer <time>, indexing <time>, ref import <time>)
+    "###);
+}
+
+#[test]
+fn test_git_fetch_refspec() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_git_remote(&test_env, &repo_path, "rem1");
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "git",
+            "fetch",
+            "--refspec",
+            "+refs/heads/*:refs/remotes/rem1/mirror/*",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Fetching from the only existing remote: rem1
+    branch: mirror/rem1@rem1 [new] untracked (new) -> 6a21102783e8
+    "###);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
+    mirror/rem1@rem1: qxosxrvv 6a211027 message
+    "###);
+
+    // The destination must be a remote-tracking ref of the fetched remote
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["git", "fetch", "--refspec", "+refs/heads/*:refs/heads/*"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Fetching from the only existing remote: rem1
+    Error: Invalid refspec '+refs/heads/*:refs/heads/*'. The destination must be a ref under refs/remotes/<remote>/
+    "###);
+
+    let stderr = test_env.jj_cmd_cli_error(
+        &repo_path,
+        &["git", "fetch", "--refspec", "refs/heads/a", "--branch", "a"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    error: the argument '--refspec <REFSPEC>' cannot be used with '--branch <BRANCH>'
+
+    Usage: jj git fetch --refspec <REFSPEC>
+
+    For more information, try '--help'.
+    "###);
+}
+
+#[test]
+fn test_git_fetch_ref() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_git_remote(&test_env, &repo_path, "rem1");
+    let git_repo = git2::Repository::open(test_env.env_root().join("rem1")).unwrap();
+    let commit = git_repo.revparse_single("refs/heads/rem1").unwrap();
+    git_repo
+        .reference("refs/pull/1/head", commit.id(), false, "")
+        .unwrap();
+
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--ref", "pull/1/head"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Fetching from the only existing remote: rem1
+    branch: fetched/pull/1/head@rem1 [new] untracked (new) -> 6a21102783e8
+    "###);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
+    fetched/pull/1/head@rem1: qxosxrvv 6a211027 message
+    "###);
+
+    // The namespace is configurable, and a commit can be fetched by its id
+    test_env.add_config(r#"git.fetched-ref-namespace = "pr""#);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "git",
+            "fetch",
+            "--ref",
+            "refs/pull/1/head",
+            "--ref",
+            &commit.id().to_string(),
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Fetching from the only existing remote: rem1
+    branch: pr/6a21102783e8ab43df22ee2386490e542214ed49@rem1 [new] untracked (new) -> 6a21102783e8
+    branch: pr/pull/1/head@rem1                              [new] untracked (new) -> 6a21102783e8
+    "###);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
+    fetched/pull/1/head@rem1: qxosxrvv 6a211027 message
+    pr/6a21102783e8ab43df22ee2386490e542214ed49@rem1: qxosxrvv 6a211027 message
+    pr/pull/1/head@rem1: qxosxrvv 6a211027 message
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["git", "fetch", "--ref", "a..b"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Fetching from the only existing remote: rem1
+    Error: Invalid ref to fetch: a..b
+    "###);
+}
+
+#[test]
+fn test_git_fetch_missing_ca_bundle() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_git_remote(&test_env, &repo_path, "origin");
+    test_env.add_config(r#"git.ssl-ca-info = "missing.pem""#);
+
+    let stderr = &test_env.jj_cmd_failure(&repo_path, &["git", "fetch"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The CA bundle missing.pem set by git.ssl-ca-info doesn't exist
+    "###);
+}
+
+#[test]
+fn test_git_fetch_rewritten_url() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("git.auto-local-branch = true");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    init_git_remote(&test_env, "origin");
+    // The longest matching prefix wins, regardless of the order
+    let git_config_path = repo_path.join(".jj/repo/store/git/config");
+    let mut git_config = std::fs::read_to_string(&git_config_path).unwrap();
+    git_config.push_str(
+        r#"
+[url "../nonexistent/"]
+    insteadOf = loc
+[url "../"]
+    insteadOf = local:
+[url "../nonexistent-too/"]
+    insteadOf = local
+"#,
+    );
+    std::fs::write(&git_config_path, git_config).unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["git", "remote", "add", "origin", "local:origin"],
+    );
+
+    test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
+    origin: oputwtnw ffecd2d6 message
+      @origin: oputwtnw ffecd2d6 message
+    "###);
+}
+
+#[test]
+fn test_git_fetch_timeout() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    // Accepts connections but never responds
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/repo.git", listener.local_addr().unwrap());
+    test_env.jj_cmd_ok(&repo_path, &["git", "remote", "add", "origin", &url]);
+
+    let stderr = &test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "git",
+            "fetch",
+            "--timeout",
+            "1",
+            "--config-toml=git.retry-attempts=1",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The remote didn't respond in time: could not read from socket: timed out
+    Hint: The timeouts can be changed with git.connect-timeout and git.timeout, or with --connect-timeout and --timeout.
+    "###);
+}
+
+/// Starts an HTTP server which serves an empty Git repo, but only to requests
+/// authenticated with `basic_auth`, the base64 of `username:password`. Returns
+/// the URL of the repo.
+fn start_http_server_with_auth(basic_auth: &'static str) -> String {
+    use std::io::{BufRead as _, BufReader, Write as _};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/repo.git", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            let mut authorized = false;
+            for line in BufReader::new(&stream).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(':').unwrap_or_default();
+                if name.eq_ignore_ascii_case("authorization")
+                    && value.trim() == format!("Basic {basic_auth}")
+                {
+                    authorized = true;
+                }
+            }
+            let response = if authorized {
+                let capabilities = "0000000000000000000000000000000000000000 capabilities^{}\0\n";
+                let body = format!(
+                    "001e# service=git-upload-pack\n0000{:04x}{capabilities}0000",
+                    capabilities.len() + 4
+                );
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: \
+                     application/x-git-upload-pack-advertisement\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"jj\"\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_owned()
+            };
+            _ = stream.write_all(response.as_bytes());
+        }
+    });
+    url
+}
+
+#[cfg(unix)]
+#[test]
+fn test_git_fetch_credential_helper_approve() {
+    let test_env = TestEnvironment::default();
+    let helper_path =
+        crate::common::create_fake_credential_helper(test_env.env_root(), "alice", "good");
+    std::fs::write(
+        test_env.home_dir().join(".gitconfig"),
+        format!("[credential]\n\thelper = {}\n", helper_path.display()),
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let url = start_http_server_with_auth("YWxpY2U6Z29vZA==");
+    test_env.jj_cmd_ok(&repo_path, &["git", "remote", "add", "origin", &url]);
+
+    // The credential from the helper works, so the helper is told to store it
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    "###);
+    let log = std::fs::read_to_string(test_env.env_root().join("credential.log")).unwrap();
+    insta::assert_snapshot!(log, @r###"
+    get
+    store
+      password=good
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_git_fetch_credential_helper_reject() {
+    let test_env = TestEnvironment::default();
+    let helper_path =
+        crate::common::create_fake_credential_helper(test_env.env_root(), "alice", "stale");
+    std::fs::write(
+        test_env.home_dir().join(".gitconfig"),
+        format!("[credential]\n\thelper = {}\n", helper_path.display()),
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let url = start_http_server_with_auth("YWxpY2U6Z29vZA==");
+    test_env.jj_cmd_ok(&repo_path, &["git", "remote", "add", "origin", &url]);
+
+    // The stale credential from the helper is rejected, so the helper is told
+    // to erase it, and the attempts are bounded
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["git", "fetch", "--config-toml=git.retry-attempts=1"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Authentication failed after 3 attempts
+    Hint: Check that the credentials are correct and allowed to access the remote.
+    "###);
+    let log = std::fs::read_to_string(test_env.env_root().join("credential.log")).unwrap();
+    insta::assert_snapshot!(log, @r###"
+    get
+    get
+    get
+    erase
+      password=stale
+    "###);
+}
+
+#[test]
+fn test_git_fetch_list_abandoned_commits() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("git.list-abandoned-commits = true");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_git_remote(&test_env, &repo_path, "rem1");
+    test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--remote", "rem1"]);
+
+    // Delete the branch on the remote, which abandons its commit
+    let git_repo = git2::Repository::open(test_env.env_root().join("rem1")).unwrap();
+    git_repo
+        .find_branch("rem1", git2::BranchType::Local)
+        .unwrap()
+        .delete()
+        .unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--remote", "rem1"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: rem1@rem1 [deleted] untracked 6a21102783e8 -> (deleted)
+    Abandoned 1 commits that are no longer reachable:
+      qxosxrvvxpsz 6a21102783e8
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_git_fetch_post_fetch_hook() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config(
+        r#"
+        git.post-fetch-hook = [
+            "sh", "-c",
+            "echo \"remotes: $JJ_FETCH_REMOTES\"; printf %s \"$JJ_CHANGED_REFS\"; echo oops >&2; exit 1",
+        ]
+        "#,
+    );
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_git_remote(&test_env, &repo_path, "rem1");
+    add_git_remote(&test_env, &repo_path, "rem2");
+
+    // The hook gets the changed refs, and a failure is only a warning
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--all-remotes"]);
+    insta::assert_snapshot!(stdout, @r###"
+    remotes: rem1 rem2
+    0000000000000000000000000000000000000000 6a21102783e8ab43df22ee2386490e542214ed49 refs/remotes/rem1/rem1
+    0000000000000000000000000000000000000000 2497a8a08f85a1aa1525bef19de0d2c8a1a9f0e8 refs/remotes/rem2/rem2
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: rem1@rem1 [new] untracked (new) -> 6a21102783e8
+    branch: rem2@rem2 [new] untracked (new) -> 2497a8a08f85
+    oops
+    The post-fetch hook failed with exit status: 1
+    "###);
+
+    // The hook runs even if nothing changed, and can be required to succeed
+    test_env.add_config("git.post-fetch-hook-required = true");
+    let assert = test_env
+        .jj_cmd(&repo_path, &["git", "fetch", "--all-remotes"])
+        .assert()
+        .code(1);
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    remotes: rem1 rem2
+    "###);
+    insta::assert_snapshot!(test_env.normalize_output(&get_stderr_string(&assert)), @r###"
+    Nothing changed.
+    oops
+    Error: The post-fetch hook failed with exit status: 1
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_git_fetch_ssh_command() {
+    let mut test_env = TestEnvironment::default();
+    let fake_ssh_path = crate::common::create_fake_ssh_command(test_env.env_root());
+    test_env.add_env_var(
+        "GIT_SSH_COMMAND",
+        &format!("{} -o SomeOption=yes", fake_ssh_path.display()),
+    );
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    init_git_remote(&test_env, "rem1");
+    let remote_path = test_env.env_root().join("rem1");
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "git",
+            "remote",
+            "add",
+            "rem1",
+            &format!("ssh://me@example.com:2222{}", remote_path.display()),
+        ],
+    );
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--remote", "rem1"]);
+    insta::assert_snapshot!(stderr, @r###"
+    remote: Enumerating objects: 3, done.
+    remote: Total 3 (delta 0), reused 0 (delta 0), pack-reused 0
+    branch: rem1@rem1 [new] untracked (new) -> 6a21102783e8
+    "###);
+    let log = std::fs::read_to_string(test_env.env_root().join("ssh.log")).unwrap();
+    insta::assert_snapshot!(test_env.normalize_output(&log), @r###"
+    -o SomeOption=yes -p 2222 me@example.com git-upload-pack '$TEST_ENV/rem1'
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_git_fetch_ssh_proxy() {
+    let mut test_env = TestEnvironment::default();
+    let fake_ssh_path = crate::common::create_fake_ssh_command(test_env.env_root());
+    test_env.add_env_var("GIT_SSH_COMMAND", fake_ssh_path.to_str().unwrap());
+    let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_address = proxy.local_addr().unwrap().to_string();
+    test_env.add_config(&format!(r#"git.ssh-proxy = "socks5://{proxy_address}""#));
+    // Any program that exists will do, since the fake SSH command doesn't run it
+    test_env.add_config(&format!(
+        r#"git.ssh-proxy-command = "{} -x $proxy %h %p""#,
+        fake_ssh_path.to_str().unwrap()
+    ));
+    test_env.add_config("git.retry-attempts = 1");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    init_git_remote(&test_env, "rem1");
+    let remote_path = test_env.env_root().join("rem1");
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "git",
+            "remote",
+            "add",
+            "rem1",
+            &format!("ssh://example.com{}", remote_path.display()),
+        ],
+    );
+
+    // The SSH command is told to connect through the proxy
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--remote", "rem1"]);
+    insta::assert_snapshot!(stderr, @r###"
+    remote: Enumerating objects: 3, done.
+    remote: Total 3 (delta 0), reused 0 (delta 0), pack-reused 0
+    branch: rem1@rem1 [new] untracked (new) -> 6a21102783e8
+    "###);
+    let log = std::fs::read_to_string(test_env.env_root().join("ssh.log")).unwrap();
+    insta::assert_snapshot!(test_env.normalize_output(&log).replace(&proxy_address, "$PROXY"), @r###"
+    -o ProxyCommand=$TEST_ENV/fake-ssh -x $PROXY %h %p example.com git-upload-pack '$TEST_ENV/rem1'
+    "###);
+
+    // A missing proxy command is reported as such
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "git",
+            "fetch",
+            "--remote=rem1",
+            "--config-toml=git.ssh-proxy-command='missing-proxy-tool $proxy %h %p'",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The command `missing-proxy-tool` of git.ssh-proxy-command isn't installed
+    "###);
+
+    // An unreachable proxy is reported as such
+    drop(proxy);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["git", "fetch", "--remote", "rem1"]);
+    let stderr = stderr.replace(&proxy_address, "$PROXY");
+    // The OS error code differs between platforms
+    let (message, _) = stderr.split_once(" (os error").unwrap();
+    insta::assert_snapshot!(message, @"Error: Failed to connect to the SOCKS5 proxy $PROXY: Connection refused");
+}
+
 #[test]
 fn test_git_fetch_from_remote_named_git() {
     let test_env = TestEnvironment::default();
@@ -464,10 +971,11 @@ fn test_git_fetch_all() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&target_jj_repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a1@origin     [new] tracked
-    branch: a2@origin     [new] tracked
-    branch: b@origin      [new] tracked
-    branch: trunk1@origin [new] tracked
+    branch: a1@origin     [new] tracked (new) -> 359a9a02457d
+    branch: a2@origin     [new] tracked (new) -> decaa3966c83
+    branch: b@origin      [new] tracked (new) -> c7d4bdcbc215
+    branch: trunk1@origin [new] tracked (new) -> ff36dc55760e
+    Hint: The default branch of remote origin is trunk1. Run `jj new trunk1@origin` to start working on it.
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &target_jj_repo_path), @r###"
     a1: nknoxmzm 359a9a02 descr_for_a1
@@ -536,10 +1044,10 @@ fn test_git_fetch_all() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&target_jj_repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a1@origin     [updated] tracked
-    branch: a2@origin     [updated] tracked
-    branch: b@origin      [updated] tracked
-    branch: trunk2@origin [new] tracked
+    branch: a1@origin     [updated] tracked 359a9a02457d..0424f6dfc1ff
+    branch: a2@origin     [updated] tracked decaa3966c83..91e46b4b2653
+    branch: b@origin      [updated] tracked c7d4bdcbc215..babc49226c14
+    branch: trunk2@origin [new] tracked (new) -> 8f1f14fbbf42
     Abandoned 2 commits that are no longer reachable.
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &target_jj_repo_path), @r###"
@@ -628,7 +1136,7 @@ fn test_git_fetch_some_of_many_branches() {
         test_env.jj_cmd_ok(&target_jj_repo_path, &["git", "fetch", "--branch", "b"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: b@origin [new] tracked
+    branch: b@origin [new] tracked (new) -> c7d4bdcbc215
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
     ◉  c7d4bdcbc215 descr_for_b b
@@ -649,8 +1157,8 @@ fn test_git_fetch_some_of_many_branches() {
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a1@origin [new] tracked
-    branch: a2@origin [new] tracked
+    branch: a1@origin [new] tracked (new) -> 359a9a02457d
+    branch: a2@origin [new] tracked (new) -> decaa3966c83
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
     ◉  decaa3966c83 descr_for_a2 a2
@@ -720,8 +1228,8 @@ fn test_git_fetch_some_of_many_branches() {
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a1@origin [updated] tracked
-    branch: b@origin  [updated] tracked
+    branch: a1@origin [updated] tracked 359a9a02457d..6df2d34cf0da
+    branch: b@origin  [updated] tracked c7d4bdcbc215..01d115196c39
     Abandoned 1 commits that are no longer reachable.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
@@ -759,7 +1267,7 @@ fn test_git_fetch_some_of_many_branches() {
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a2@origin [updated] tracked
+    branch: a2@origin [updated] tracked decaa3966c83..31c7d94b1f29
     Abandoned 1 commits that are no longer reachable.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
@@ -828,8 +1336,8 @@ fn test_git_fetch_undo() {
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a1@origin [new] tracked
-    branch: b@origin  [new] tracked
+    branch: a1@origin [new] tracked (new) -> 359a9a02457d
+    branch: b@origin  [new] tracked (new) -> c7d4bdcbc215
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
     ◉  c7d4bdcbc215 descr_for_b b
@@ -853,7 +1361,7 @@ fn test_git_fetch_undo() {
         test_env.jj_cmd_ok(&target_jj_repo_path, &["git", "fetch", "--branch", "b"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: b@origin [new] tracked
+    branch: b@origin [new] tracked (new) -> c7d4bdcbc215
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
     ◉  c7d4bdcbc215 descr_for_b b
@@ -905,7 +1413,7 @@ fn test_fetch_undo_what() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--branch", "b"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: b@origin [new] tracked
+    branch: b@origin [new] tracked (new) -> c7d4bdcbc215
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     ◉  c7d4bdcbc215 descr_for_b b
@@ -994,7 +1502,7 @@ fn test_git_fetch_remove_fetch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: origin@origin [new] tracked
+    branch: origin@origin [new] tracked (new) -> ffecd2d67827
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     origin (conflicted):
@@ -1079,10 +1587,11 @@ fn test_git_fetch_removed_branch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&target_jj_repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a1@origin     [new] tracked
-    branch: a2@origin     [new] tracked
-    branch: b@origin      [new] tracked
-    branch: trunk1@origin [new] tracked
+    branch: a1@origin     [new] tracked (new) -> 359a9a02457d
+    branch: a2@origin     [new] tracked (new) -> decaa3966c83
+    branch: b@origin      [new] tracked (new) -> c7d4bdcbc215
+    branch: trunk1@origin [new] tracked (new) -> ff36dc55760e
+    Hint: The default branch of remote origin is trunk1. Run `jj new trunk1@origin` to start working on it.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
     ◉  c7d4bdcbc215 descr_for_b b
@@ -1123,7 +1632,7 @@ fn test_git_fetch_removed_branch() {
         test_env.jj_cmd_ok(&target_jj_repo_path, &["git", "fetch", "--branch", "a2"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a2@origin [deleted] untracked
+    branch: a2@origin [deleted] untracked decaa3966c83 -> (deleted)
     Abandoned 1 commits that are no longer reachable.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
@@ -1171,10 +1680,11 @@ fn test_git_fetch_removed_parent_branch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&target_jj_repo_path, &["git", "fetch"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a1@origin     [new] tracked
-    branch: a2@origin     [new] tracked
-    branch: b@origin      [new] tracked
-    branch: trunk1@origin [new] tracked
+    branch: a1@origin     [new] tracked (new) -> 359a9a02457d
+    branch: a2@origin     [new] tracked (new) -> decaa3966c83
+    branch: b@origin      [new] tracked (new) -> c7d4bdcbc215
+    branch: trunk1@origin [new] tracked (new) -> ff36dc55760e
+    Hint: The default branch of remote origin is trunk1. Run `jj new trunk1@origin` to start working on it.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
     ◉  c7d4bdcbc215 descr_for_b b
@@ -1202,8 +1712,8 @@ fn test_git_fetch_removed_parent_branch() {
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a1@origin     [deleted] untracked
-    branch: trunk1@origin [deleted] untracked
+    branch: a1@origin     [deleted] untracked 359a9a02457d -> (deleted)
+    branch: trunk1@origin [deleted] untracked ff36dc55760e -> (deleted)
     Abandoned 1 commits that are no longer reachable.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &target_jj_repo_path), @r###"
@@ -1285,3 +1795,90 @@ fn test_git_fetch_remote_only_branch() {
     feature2@origin: mzyxwzks 9f01a0e0 message
     "###);
 }
+
+#[test]
+fn test_git_fetch_shallow_and_deepen() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_git_remote(&test_env, &repo_path, "origin");
+    let origin_repo = git2::Repository::open(test_env.env_root().join("origin")).unwrap();
+    let parent = origin_repo
+        .find_reference("refs/heads/origin")
+        .unwrap()
+        .peel_to_commit()
+        .unwrap();
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let tip_oid = origin_repo
+        .commit(
+            Some("refs/heads/origin"),
+            &signature,
+            &signature,
+            "tip",
+            &parent.tree().unwrap(),
+            &[&parent],
+        )
+        .unwrap();
+
+    // The local transport doesn't support shallow fetches. Simulate one by
+    // downloading the objects beforehand, and marking the tip as shallow.
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+    git_repo
+        .remote_anonymous(test_env.env_root().join("origin").to_str().unwrap())
+        .unwrap()
+        .fetch(&["refs/heads/origin:refs/prefetched"], None, None)
+        .unwrap();
+    git_repo
+        .find_reference("refs/prefetched")
+        .unwrap()
+        .delete()
+        .unwrap();
+    let shallow_path = git_repo.path().join("shallow");
+    std::fs::write(&shallow_path, format!("{tip_oid}\n")).unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--shallow"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: origin@origin [new] untracked (new) -> 8bca9e6ed4e3
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  8bca9e6ed4e3 tip origin@origin
+    │ @  230dd059e1b0
+    ├─╯
+    ◉  000000000000
+    "###);
+
+    // Fetching a new commit over the local transport unmarks the tip, as if
+    // the history was deepened, and then the parent of the tip is indexed.
+    let tip = origin_repo.find_commit(tip_oid).unwrap();
+    origin_repo
+        .commit(
+            Some("refs/heads/origin"),
+            &signature,
+            &signature,
+            "new tip",
+            &tip.tree().unwrap(),
+            &[&tip],
+        )
+        .unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--depth", "10"]);
+    assert!(!shallow_path.exists());
+    insta::assert_snapshot!(stderr, @r###"
+    branch: origin@origin [updated] untracked 8bca9e6ed4e3..b1e334238afc
+    Reindexing the deepened history
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  230dd059e1b0
+    │ ◉  b1e334238afc new tip origin@origin
+    │ ◉  8bca9e6ed4e3 tip
+    │ ◉  ffecd2d67827 message
+    ├─╯
+    ◉  000000000000
+    "###);
+
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["git", "fetch", "--depth", "0"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: invalid value '0' for '--depth <N>': 0 is not in 1..=4294967295
+
+    For more information, try '--help'.
+    "###);
+}
@@ -63,17 +63,108 @@ fn test_git_export_conflicting_git_refs() {
 
     test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main"]);
     test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main/sub"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "export", "--dry-run"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Failed to export some branches:
+      main/sub: Conflicts with the existing Git ref refs/heads/main
+    Hint: Git doesn't allow a branch name that looks like a parent directory of
+    another. To rename the conflicting branches, run:
+      jj branch rename main/sub main-sub
+    Dry-run requested, not exporting.
+    "###);
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "export"]);
     insta::assert_snapshot!(stdout, @"");
-    insta::with_settings!({filters => vec![(": The lock for resource.*", ": ...")]}, {
-        insta::assert_snapshot!(stderr, @r###"
-        Failed to export some branches:
-          main/sub: Failed to set: A lock could not be obtained for reference "refs/heads/main/sub": ...
-        Hint: Git doesn't allow a branch name that looks like a parent directory of
-        another (e.g. `foo` and `foo/bar`). Try to rename the branches that failed to
-        export or their "parent" branches.
-        "###);
-    });
+    insta::assert_snapshot!(stderr, @r###"
+    Failed to export some branches:
+      main/sub: Conflicts with the existing Git ref refs/heads/main
+    Hint: Git doesn't allow a branch name that looks like a parent directory of
+    another. To rename the conflicting branches, run:
+      jj branch rename main/sub main-sub
+    "###);
+
+    // The deeper branch is suggested to be renamed even if it was exported
+    test_env.jj_cmd_ok(&repo_path, &["branch", "delete", "main", "main/sub"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "feature/a"]);
+    test_env.jj_cmd_ok(&repo_path, &["git", "export"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "feature"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "export"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    Failed to export some branches:
+      feature: Conflicts with the existing Git ref refs/heads/feature/a
+    Hint: Git doesn't allow a branch name that looks like a parent directory of
+    another. To rename the conflicting branches, run:
+      jj branch rename feature/a feature-a
+    "###);
+
+    // No rename is suggested if the flattened name is taken, so the generic
+    // hint is shown too
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "feature-a"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "feature/a/b"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "export"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Failed to export some branches:
+      feature: Conflicts with the existing Git ref refs/heads/feature/a
+      feature/a/b: Conflicts with the existing Git ref refs/heads/feature/a
+    Hint: Git doesn't allow a branch name that looks like a parent directory of
+    another (e.g. `foo` and `foo/bar`). Try to rename the branches that failed to
+    export or their "parent" branches.
+    Hint: Git doesn't allow a branch name that looks like a parent directory of
+    another. To rename the conflicting branches, run:
+      jj branch rename feature/a/b feature-a-b
+    "###);
+}
+
+#[test]
+fn test_git_export_selected_branches() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "a", "b", "c", "c/d"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["git", "export", "a", "c/d", "unknown", "--dry-run"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    No such branch: unknown
+    Dry-run requested, not exporting.
+    "###);
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["git", "export", "a", "c", "c/d", "unknown"]);
+    insta::assert_snapshot!(stderr, @r###"
+    No such branch: unknown
+    Failed to export some branches:
+      c/d: Conflicts with the existing Git ref refs/heads/c
+    Hint: Git doesn't allow a branch name that looks like a parent directory of
+    another. To rename the conflicting branches, run:
+      jj branch rename c/d c-d
+    "###);
+    let git_branches = || {
+        git_repo
+            .branches(None)
+            .unwrap()
+            .map(|branch| branch.unwrap().0.name().unwrap().unwrap().to_owned())
+            .collect_vec()
+    };
+    insta::assert_debug_snapshot!(git_branches(), @r###"
+    [
+        "a",
+        "c",
+    ]
+    "###);
+
+    // Deleting a branch is exported too
+    test_env.jj_cmd_ok(&repo_path, &["branch", "delete", "a", "b"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "export", "a"]);
+    insta::assert_snapshot!(stderr, @"");
+    insta::assert_debug_snapshot!(git_branches(), @r###"
+    [
+        "c",
+    ]
+    "###);
 }
 
 #[test]
@@ -149,7 +240,7 @@ fn test_git_import_undo() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a [new] tracked
+    branch: a [new] tracked (new) -> 230dd059e1b0
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     a: qpvuntsm 230dd059 (empty) (no description set)
@@ -165,7 +256,7 @@ fn test_git_import_undo() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a [new] tracked
+    branch: a [new] tracked (new) -> 230dd059e1b0
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     a: qpvuntsm 230dd059 (empty) (no description set)
@@ -173,6 +264,275 @@ fn test_git_import_undo() {
     "###);
 }
 
+#[test]
+fn test_git_import_branches_and_tags() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+
+    let commit_id =
+        test_env.jj_cmd_success(&repo_path, &["log", "-Tcommit_id", "--no-graph", "-r@"]);
+    let commit = git_repo
+        .find_commit(git2::Oid::from_str(&commit_id).unwrap())
+        .unwrap();
+
+    // A single kind of refs is listed without headings
+    git_repo
+        .tag_lightweight("v1.0", commit.as_object(), false)
+        .unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
+    insta::assert_snapshot!(stderr, @r###"
+    tag: v1.0 [new] (new) -> 230dd059e1b0
+    "###);
+
+    // Branches and tags are listed under separate headings
+    git_repo.branch("a", &commit, true).unwrap();
+    git_repo.branch("long-branch", &commit, true).unwrap();
+    git_repo
+        .tag_lightweight("v2.0", commit.as_object(), false)
+        .unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Branches:
+      a           [new] tracked (new) -> 230dd059e1b0
+      long-branch [new] tracked (new) -> 230dd059e1b0
+    Tags:
+      v2.0 [new] (new) -> 230dd059e1b0
+    "###);
+}
+
+#[test]
+fn test_git_import_notes() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+
+    let commit_id =
+        test_env.jj_cmd_success(&repo_path, &["log", "-Tcommit_id", "--no-graph", "-r@"]);
+    let signature =
+        git2::Signature::new("Someone", "someone@example.com", &git2::Time::new(0, 0)).unwrap();
+    git_repo
+        .note(
+            &signature,
+            &signature,
+            None,
+            git2::Oid::from_str(&commit_id).unwrap(),
+            "LGTM",
+            false,
+        )
+        .unwrap();
+
+    // Notes aren't imported unless configured
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    "###);
+
+    test_env.add_config(r#"git.import-notes = ["commits"]"#);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
+    insta::assert_snapshot!(stderr, @r###"
+    notes: commits [new] (new) -> 40fc65530f6b
+    "###);
+
+    // The notes commit can be referred to by the name of the ref
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-r",
+            "notes/commits",
+            "-T",
+            "description",
+            "--no-graph",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Notes added by 'git_note_create' from libgit2
+    "###);
+}
+
+#[test]
+fn test_git_import_colors() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+
+    let commit_id =
+        test_env.jj_cmd_success(&repo_path, &["log", "-Tcommit_id", "--no-graph", "-r@"]);
+    let commit = git_repo
+        .find_commit(git2::Oid::from_str(&commit_id).unwrap())
+        .unwrap();
+
+    // Only the ref name is colored by default
+    git_repo.branch("a", &commit, true).unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import", "--color=always"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: [38;5;5ma[39m [new] tracked (new) -> 230dd059e1b0
+    "###);
+
+    // The import and tracking statuses can be colored too
+    test_env.add_config(
+        r#"
+        colors."import new" = "green"
+        colors."tracking tracked" = "blue"
+        "#,
+    );
+    git_repo.branch("b", &commit, true).unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import", "--color=always"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: [38;5;5mb[39m [[38;5;2mnew[39m] [38;5;4mtracked[39m (new) -> 230dd059e1b0
+    "###);
+}
+
+#[test]
+fn test_git_import_branch_filter() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+
+    let commit_id =
+        test_env.jj_cmd_success(&repo_path, &["log", "-Tcommit_id", "--no-graph", "-r@"]);
+    let oid = git2::Oid::from_str(&commit_id).unwrap();
+    for name in ["feature", "release/1", "release/2"] {
+        git_repo
+            .reference(&format!("refs/remotes/origin/{name}"), oid, false, "")
+            .unwrap();
+    }
+
+    // Only the matching remote branches are imported
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["git", "import", "--branch", "glob:release/*"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: release/1@origin [new] untracked (new) -> 230dd059e1b0
+    branch: release/2@origin [new] untracked (new) -> 230dd059e1b0
+    "###);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: feature@origin [new] untracked (new) -> 230dd059e1b0
+    "###);
+
+    // Remote branches which don't match are left as they are, even if their
+    // Git refs are gone
+    git_repo
+        .find_reference("refs/remotes/origin/feature")
+        .unwrap()
+        .delete()
+        .unwrap();
+    git_repo
+        .find_reference("refs/remotes/origin/release/1")
+        .unwrap()
+        .delete()
+        .unwrap();
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["git", "import", "--branch", "glob:release/*"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: release/1@origin [deleted] untracked 230dd059e1b0 -> (deleted)
+    "###);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
+    feature@origin: qpvuntsm 230dd059 (empty) (no description set)
+    release/2@origin: qpvuntsm 230dd059 (empty) (no description set)
+    "###);
+}
+
+#[test]
+fn test_git_import_tags_or_branches_only() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+
+    let commit_id =
+        test_env.jj_cmd_success(&repo_path, &["log", "-Tcommit_id", "--no-graph", "-r@"]);
+    let oid = git2::Oid::from_str(&commit_id).unwrap();
+    git_repo
+        .reference("refs/heads/main", oid, false, "")
+        .unwrap();
+    git_repo
+        .reference("refs/remotes/origin/main", oid, false, "")
+        .unwrap();
+    git_repo
+        .reference("refs/tags/v1.0", oid, false, "")
+        .unwrap();
+
+    // Only the tags are imported
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import", "--tags-only"]);
+    insta::assert_snapshot!(stderr, @r###"
+    tag: v1.0 [new] (new) -> 230dd059e1b0
+    "###);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @"");
+
+    // The branches are imported later, and the deleted tag is left as it is
+    git_repo
+        .find_reference("refs/tags/v1.0")
+        .unwrap()
+        .delete()
+        .unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import", "--branches-only"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: main        [new] tracked (new) -> 230dd059e1b0
+    branch: main@origin [new] untracked (new) -> 230dd059e1b0
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["tag", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    v1.0
+    "###);
+
+    let stderr = test_env.jj_cmd_cli_error(
+        &repo_path,
+        &["git", "import", "--tags-only", "--branches-only"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    error: the argument '--tags-only' cannot be used with '--branches-only'
+
+    Usage: jj git import --tags-only
+
+    For more information, try '--help'.
+    "###);
+}
+
+#[test]
+fn test_git_import_summary_threshold() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("git.import-summary-threshold = 2");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+
+    let commit_id =
+        test_env.jj_cmd_success(&repo_path, &["log", "-Tcommit_id", "--no-graph", "-r@"]);
+    let commit = git_repo
+        .find_commit(git2::Oid::from_str(&commit_id).unwrap())
+        .unwrap();
+
+    // Refs are listed up to the threshold
+    git_repo.branch("a", &commit, true).unwrap();
+    git_repo.branch("b", &commit, true).unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
+    insta::assert_snapshot!(stderr, @r###"
+    branch: a [new] tracked (new) -> 230dd059e1b0
+    branch: b [new] tracked (new) -> 230dd059e1b0
+    "###);
+
+    // More refs than that are summarized
+    git_repo
+        .find_branch("a", git2::BranchType::Local)
+        .unwrap()
+        .delete()
+        .unwrap();
+    git_repo.branch("c", &commit, true).unwrap();
+    git_repo
+        .tag_lightweight("v1.0", commit.as_object(), false)
+        .unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Imported 2 branches (1 new, 1 deleted) and 1 tag (1 new)
+    "###);
+}
+
 #[test]
 fn test_git_import_move_export_with_default_undo() {
     let test_env = TestEnvironment::default();
@@ -196,7 +556,7 @@ fn test_git_import_move_export_with_default_undo() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a [new] tracked
+    branch: a [new] tracked (new) -> 230dd059e1b0
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     a: qpvuntsm 230dd059 (empty) (no description set)
@@ -244,7 +604,7 @@ fn test_git_import_move_export_with_default_undo() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "import"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    branch: a [new] tracked
+    branch: a [new] tracked (new) -> 096dc80da670
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     a: yqosqzyt 096dc80d (empty) (no description set)
@@ -271,6 +271,7 @@ fn test_git_init_colocated_via_git_repo_path_symlink_directory() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
+    Hint: The .git symlink points to $TEST_ENV/git-repo/.git, outside of the workspace.
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
@@ -310,6 +311,7 @@ fn test_git_init_colocated_via_git_repo_path_symlink_directory_without_bare_conf
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
+    Hint: The .git symlink points to $TEST_ENV/git-repo.git, outside of the workspace.
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
@@ -351,6 +353,7 @@ fn test_git_init_colocated_via_git_repo_path_symlink_gitlink() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
+    Hint: The .git symlink points to $TEST_ENV/git-workdir/.git, outside of the workspace.
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
@@ -46,6 +46,75 @@ fn set_up() -> (TestEnvironment, PathBuf) {
     (test_env, workspace_root)
 }
 
+#[cfg(unix)]
+#[test]
+fn test_git_push_ssh_command() {
+    let (mut test_env, workspace_root) = set_up();
+    let fake_ssh_path = crate::common::create_fake_ssh_command(test_env.env_root());
+    let origin_git_repo_path = test_env
+        .env_root()
+        .join("origin")
+        .join(".jj")
+        .join("repo")
+        .join("store")
+        .join("git");
+    let git_repo = git2::Repository::open(
+        workspace_root
+            .join(".jj")
+            .join("repo")
+            .join("store")
+            .join("git"),
+    )
+    .unwrap();
+    git_repo
+        .remote_set_url(
+            "origin",
+            &format!("example.com:{}", origin_git_repo_path.display()),
+        )
+        .unwrap();
+    // core.sshCommand is used if $GIT_SSH_COMMAND isn't set
+    git_repo
+        .config()
+        .unwrap()
+        .set_str("core.sshCommand", fake_ssh_path.to_str().unwrap())
+        .unwrap();
+
+    test_env.jj_cmd_ok(&workspace_root, &["describe", "-m=foo"]);
+    test_env.jj_cmd_ok(&workspace_root, &["branch", "create", "branch3"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push", "-b=branch3"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Branch changes to push to origin:
+      Add branch branch3 to 254b17d9b357
+    "###);
+
+    // $GIT_SSH_COMMAND takes precedence
+    test_env.add_env_var("GIT_SSH_COMMAND", "false");
+    test_env.jj_cmd_ok(&workspace_root, &["new", "-m=bar"]);
+    test_env.jj_cmd_ok(&workspace_root, &["branch", "create", "branch4"]);
+    // Network errors are retried
+    let stderr = test_env.jj_cmd_failure(
+        &workspace_root,
+        &[
+            "git",
+            "push",
+            "-b=branch4",
+            "--config-toml=git.retry-delay=0",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Branch changes to push to origin:
+      Add branch branch4 to 191b33e99061
+    The SSH command false failed with exit status: 1. Retrying in 0s (1/2)...
+    The SSH command false failed with exit status: 1. Retrying in 0s (2/2)...
+    Error: The SSH command false failed with exit status: 1; class=Net (12)
+    Hint: Check your network connection, and that the URL of the remote is correct.
+    "###);
+    let log = std::fs::read_to_string(test_env.env_root().join("ssh.log")).unwrap();
+    insta::assert_snapshot!(test_env.normalize_output(&log), @r###"
+    example.com git-receive-pack '$TEST_ENV/origin/.jj/repo/store/git'
+    "###);
+}
+
 #[test]
 fn test_git_push_nothing() {
     let (test_env, workspace_root) = set_up();
@@ -238,6 +307,53 @@ fn test_git_push_not_fast_forward() {
     "###);
 }
 
+#[test]
+fn test_git_push_force_with_lease() {
+    let (test_env, workspace_root) = set_up();
+
+    // Move branch1 forward on the remote
+    let origin_path = test_env.env_root().join("origin");
+    test_env.jj_cmd_ok(&origin_path, &["new", "branch1", "-m=remote"]);
+    test_env.jj_cmd_ok(&origin_path, &["branch", "set", "branch1"]);
+    test_env.jj_cmd_ok(&origin_path, &["git", "export"]);
+
+    // Rewrite branch1 locally, so it would be force-pushed
+    test_env.jj_cmd_ok(&workspace_root, &["describe", "branch1", "-m=rewritten"]);
+
+    // Pushing with a lease should fail since the remote branch moved
+    let stderr = test_env.jj_cmd_failure(
+        &workspace_root,
+        &["git", "push", "--force-with-lease", "-b=branch1"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Branch changes to push to origin:
+      Force branch branch1 from 45a3aa29e907 to ced10b839382
+    Error: Refusing to push branches which changed on the remote since they were last fetched: branch1
+    Hint: Try fetching from the remote, then make the branch point to where you want it to be, and push again.
+    "###);
+
+    // Once fetched, the branch can be pushed
+    test_env.jj_cmd_ok(&workspace_root, &["git", "fetch"]);
+    test_env.jj_cmd_ok(
+        &workspace_root,
+        &[
+            "branch",
+            "set",
+            "--allow-backwards",
+            "branch1",
+            "-r=description(rewritten)",
+        ],
+    );
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &workspace_root,
+        &["git", "push", "--force-with-lease", "-b=branch1"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Branch changes to push to origin:
+      Force branch branch1 from 6319062252b8 to ced10b839382
+    "###);
+}
+
 #[test]
 fn test_git_push_locally_created_and_rewritten() {
     let (test_env, workspace_root) = set_up();
@@ -685,6 +801,185 @@ fn test_git_push_deleted() {
     "###);
 }
 
+#[test]
+fn test_git_push_mirror() {
+    let (test_env, workspace_root) = set_up();
+    test_env.jj_cmd_ok(&workspace_root, &["new", "branch1", "-m=description 3"]);
+    test_env.jj_cmd_ok(&workspace_root, &["branch", "set", "branch1"]);
+    test_env.jj_cmd_ok(&workspace_root, &["branch", "create", "branch3"]);
+    test_env.jj_cmd_ok(&workspace_root, &["branch", "delete", "branch2"]);
+
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--mirror", "--dry-run"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Ref changes to mirror to origin:
+      Move branch branch1 from 45a3aa29e907 to 8bff13b68148
+      Add branch branch3 to 8bff13b68148
+    Dry-run requested, not pushing.
+    "###);
+    // The branches weren't exported by the dry run
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["branch", "list", "--all"]);
+    insta::assert_snapshot!(stdout, @r###"
+    branch1: vruxwmqv 8bff13b6 (empty) description 3
+      @origin (behind by 1 commits): lzmmnrxq 45a3aa29 (empty) description 1
+    branch2 (deleted)
+      @origin: rlzusymt 8476341e (empty) description 2
+      (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
+    branch3: vruxwmqv 8bff13b6 (empty) description 3
+    "###);
+
+    // The deleted branch is left on the remote unless pruning
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--mirror"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Ref changes to mirror to origin:
+      Move branch branch1 from 45a3aa29e907 to 8bff13b68148
+      Add branch branch3 to 8bff13b68148
+    "###);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--mirror"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    "###);
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--mirror", "--prune"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Ref changes to mirror to origin:
+      Delete branch branch2 from 8476341eb395
+    "###);
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["branch", "list", "--all"]);
+    insta::assert_snapshot!(stdout, @r###"
+    branch1: vruxwmqv 8bff13b6 (empty) description 3
+      @git: vruxwmqv 8bff13b6 (empty) description 3
+      @origin: vruxwmqv 8bff13b6 (empty) description 3
+    branch3: vruxwmqv 8bff13b6 (empty) description 3
+      @git: vruxwmqv 8bff13b6 (empty) description 3
+      @origin: vruxwmqv 8bff13b6 (empty) description 3
+    "###);
+
+    // Branches which can't be exported aren't pushed
+    test_env.jj_cmd_ok(&workspace_root, &["branch", "create", "branch3/sub"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--mirror"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Failed to export some branches:
+      branch3/sub: Conflicts with the existing Git ref refs/heads/branch3
+    Hint: Git doesn't allow a branch name that looks like a parent directory of
+    another. To rename the conflicting branches, run:
+      jj branch rename branch3/sub branch3-sub
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_git_push_mirror_unimported_commit() {
+    let (test_env, workspace_root) = set_up();
+    let git_repo = {
+        let mut git_repo_path = workspace_root.clone();
+        git_repo_path.extend([".jj", "repo", "store", "git"]);
+        git2::Repository::open(&git_repo_path).unwrap()
+    };
+    // A tag which points to a commit which isn't imported is pushed as it is
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let tree_id = git_repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = git_repo.find_tree(tree_id).unwrap();
+    let commit_id = git_repo
+        .commit(None, &signature, &signature, "", &tree, &[])
+        .unwrap();
+    git_repo
+        .reference("refs/tags/v1", commit_id, false, "")
+        .unwrap();
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--mirror"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Ref changes to mirror to origin:
+      Push refs/tags/v1 to ce9014768705
+    "###);
+}
+
+#[test]
+fn test_git_push_mirror_unimported_source() {
+    let (test_env, workspace_root) = set_up();
+    let git_repo = {
+        let mut git_repo_path = workspace_root.clone();
+        git_repo_path.extend([".jj", "repo", "store", "git"]);
+        git2::Repository::open(&git_repo_path).unwrap()
+    };
+    // A ref which isn't imported is mapped to the existing remote branches
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let tree_id = git_repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = git_repo.find_tree(tree_id).unwrap();
+    let commit_id = git_repo
+        .commit(None, &signature, &signature, "", &tree, &[])
+        .unwrap();
+    git_repo
+        .reference("refs/stash", commit_id, false, "")
+        .unwrap();
+    test_env.add_config(
+        r#"
+        [git.push-refspecs]
+        origin = ["+refs/stash:refs/heads/branch1", "refs/stash:refs/heads/branch2"]
+        "#,
+    );
+
+    // Whether the updates are fast-forward isn't known
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--mirror", "--dry-run"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Ref changes to mirror to origin:
+      Force branch branch1 from 45a3aa29e907 to ce9014768705
+      Push branch branch2 from 8476341eb395 to ce9014768705
+    Dry-run requested, not pushing.
+    "###);
+}
+
+#[test]
+fn test_git_push_mirror_refspecs() {
+    let (test_env, workspace_root) = set_up();
+    test_env.add_config(
+        r#"
+        [git.push-refspecs]
+        origin = ["refs/heads/*:refs/heads/backup/*"]
+        "#,
+    );
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--mirror"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Ref changes to mirror to origin:
+      Add branch backup/branch1 to 45a3aa29e907
+      Add branch backup/branch2 to 8476341eb395
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(
+        &workspace_root,
+        &[
+            "git",
+            "push",
+            "--mirror",
+            "--config-toml=git.push-refspecs.origin=['refs/heads/*:refs/heads/main']",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Invalid push refspec 'refs/heads/*:refs/heads/main'. It must map a ref to a ref under refs/, with a wildcard on both sides or neither
+    "###);
+
+    let stderr = test_env.jj_cmd_cli_error(&workspace_root, &["git", "push", "--prune"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: the following required arguments were not provided:
+      --mirror
+
+    Usage: jj git push --mirror --prune
+
+    For more information, try '--help'.
+    "###);
+}
+
 #[test]
 fn test_git_push_conflicting_branches() {
     let (test_env, workspace_root) = set_up();
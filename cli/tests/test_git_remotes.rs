@@ -54,6 +54,40 @@ fn test_git_remotes() {
     "###);
 }
 
+#[test]
+fn test_git_remote_list_rewritten_urls() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "--git", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_config_path = repo_path.join(".jj/repo/store/git/config");
+    let mut git_config = fs::read_to_string(&git_config_path).unwrap();
+    git_config.push_str(
+        r#"
+[url "https://example.com/"]
+    insteadOf = ex:
+[url "ssh://git@example.com/"]
+    pushInsteadOf = ex:
+[remote "origin"]
+    url = ex:repo/origin
+[remote "mirror"]
+    url = ex:repo/mirror
+    pushurl = ex:repo/mirror-push
+[remote "plain"]
+    url = http://example.org/repo
+"#,
+    );
+    fs::write(&git_config_path, git_config).unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["git", "remote", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    mirror https://example.com/repo/mirror
+    mirror https://example.com/repo/mirror-push (push)
+    origin https://example.com/repo/origin
+    origin ssh://git@example.com/repo/origin (push)
+    plain http://example.org/repo
+    "###);
+}
+
 #[test]
 fn test_git_remote_add() {
     let test_env = TestEnvironment::default();
@@ -127,6 +161,67 @@ fn test_git_remote_rename() {
     "###);
 }
 
+#[test]
+fn test_git_remote_check() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "--git", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let origin_path = test_env.env_root().join("origin");
+    let origin_repo = git2::Repository::init_bare(&origin_path).unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "git",
+            "remote",
+            "add",
+            "origin",
+            origin_path.to_str().unwrap(),
+        ],
+    );
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["git", "remote", "add", "missing", "file:///nonexistent"],
+    );
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "remote", "check", "origin"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Connected to remote origin, which advertises 0 refs
+    "###);
+
+    let signature = git2::Signature::now("Some One", "some.one@example.com").unwrap();
+    let tree_id = origin_repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = origin_repo.find_tree(tree_id).unwrap();
+    origin_repo
+        .commit(
+            Some("refs/heads/main"),
+            &signature,
+            &signature,
+            "message",
+            &tree,
+            &[],
+        )
+        .unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "remote", "check", "origin"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Connected to remote origin, which advertises 1 ref
+    "###);
+    // Nothing was fetched
+    let stdout = test_env.jj_cmd_success(&repo_path, &["branch", "list", "--all"]);
+    insta::assert_snapshot!(stdout, @"");
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["git", "remote", "check", "missing"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The repository wasn't found on the remote: failed to resolve path '/nonexistent': No such file or directory
+    Hint: Check the URL of the remote with `jj git remote list`.
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["git", "remote", "check", "nonexistent"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No git remote named 'nonexistent'
+    "###);
+}
+
 #[test]
 fn test_git_remote_named_git() {
     let test_env = TestEnvironment::default();
@@ -402,12 +402,44 @@ fn test_early_args() {
     // bug that causes defaults to be unpopulated. Test that the early args are
     // tolerant of this bug and don't cause a crash.
     test_env.jj_cmd_success(test_env.env_root(), &["--no-pager", "help"]);
+    test_env.jj_cmd_success(test_env.env_root(), &["--no-progress", "help"]);
     test_env.jj_cmd_success(
         test_env.env_root(),
         &["--config-toml", "ui.color = 'always'", "help"],
     );
 }
 
+#[test]
+fn test_no_progress() {
+    let test_env = TestEnvironment::default();
+    let stdout = test_env.jj_cmd_success(
+        test_env.env_root(),
+        &["config", "get", "ui.progress-indicator", "--no-progress"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    false
+    "###);
+}
+
+#[test]
+fn test_invalid_progress_output() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["--config-toml", "ui.progress-output = 'none'", "status"],
+    );
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["--config-toml", "ui.progress-output = 'stdout'", "status"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Config error: Invalid `ui.progress-output`: enum ProgressOutputChoice does not have variant constructor stdout
+    For help, see https://github.com/martinvonz/jj/blob/main/docs/config.md.
+    "###);
+}
+
 #[test]
 fn test_invalid_config() {
     // Test that we get a reasonable error if the config is invalid (#55)
@@ -480,6 +512,7 @@ fn test_help() {
           --debug                        Enable debug logging
           --color <WHEN>                 When to colorize output (always, never, auto)
           --no-pager                     Disable the pager
+          --no-progress                  Don't display progress, e.g. while fetching or snapshotting
           --config-toml <TOML>           Additional configuration options (can be repeated)
     "###);
 }
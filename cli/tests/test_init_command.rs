@@ -275,6 +275,7 @@ fn test_init_git_colocated_symlink_directory() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
+    Hint: The .git symlink points to $TEST_ENV/git-repo/.git, outside of the workspace.
     warning: `--git` and `--git-repo` are deprecated.
     Use `jj git init` instead
     Initialized repo in "."
@@ -316,6 +317,7 @@ fn test_init_git_colocated_symlink_directory_without_bare_config() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
+    Hint: The .git symlink points to $TEST_ENV/git-repo.git, outside of the workspace.
     warning: `--git` and `--git-repo` are deprecated.
     Use `jj git init` instead
     Initialized repo in "."
@@ -359,6 +361,7 @@ fn test_init_git_colocated_symlink_gitlink() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
+    Hint: The .git symlink points to $TEST_ENV/git-workdir/.git, outside of the workspace.
     warning: `--git` and `--git-repo` are deprecated.
     Use `jj git init` instead
     Initialized repo in "."
@@ -15,10 +15,13 @@
 #![allow(missing_docs)]
 
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::default::Default;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
 use std::{fmt, iter, str};
 
 use git2::Oid;
@@ -32,11 +35,13 @@ use crate::git_backend::GitBackend;
 use crate::object_id::ObjectId;
 use crate::op_store::{RefTarget, RefTargetOptionExt, RemoteRef, RemoteRefState};
 use crate::refs::BranchPushUpdate;
-use crate::repo::{MutableRepo, Repo};
+use crate::repo::{MutableRepo, ReadonlyRepo, Repo};
 use crate::revset::RevsetExpression;
-use crate::settings::GitSettings;
+use crate::settings::{GitSettings, UserSettings};
 use crate::store::Store;
 use crate::str_util::StringPattern;
+use crate::transaction::Transaction;
+use crate::tree::TreeMergeError;
 use crate::view::View;
 
 /// Reserved remote name for the backing Git repo.
@@ -47,8 +52,13 @@ const UNBORN_ROOT_REF_NAME: &str = "refs/jj/root";
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Debug)]
 pub enum RefName {
     LocalBranch(String),
-    RemoteBranch { branch: String, remote: String },
+    RemoteBranch {
+        branch: String,
+        remote: String,
+    },
     Tag(String),
+    /// Notes ref, named relative to `refs/notes/`
+    Note(String),
 }
 
 impl fmt::Display for RefName {
@@ -57,6 +67,7 @@ impl fmt::Display for RefName {
             RefName::LocalBranch(name) => write!(f, "{name}"),
             RefName::RemoteBranch { branch, remote } => write!(f, "{branch}@{remote}"),
             RefName::Tag(name) => write!(f, "{name}"),
+            RefName::Note(name) => write!(f, "{name}"),
         }
     }
 }
@@ -74,14 +85,18 @@ fn parse_git_ref(ref_name: &str) -> Option<RefName> {
                 remote: remote.to_string(),
                 branch: branch.to_string(),
             })
+    } else if let Some(tag_name) = ref_name.strip_prefix("refs/tags/") {
+        Some(RefName::Tag(tag_name.to_string()))
     } else {
         ref_name
-            .strip_prefix("refs/tags/")
-            .map(|tag_name| RefName::Tag(tag_name.to_string()))
+            .strip_prefix("refs/notes/")
+            .map(|notes_name| RefName::Note(notes_name.to_string()))
     }
 }
 
-fn to_git_ref_name(parsed_ref: &RefName) -> Option<String> {
+/// Returns the full Git ref name, e.g. `refs/remotes/origin/main`, or `None`
+/// if the ref can't be represented in Git.
+pub fn to_git_ref_name(parsed_ref: &RefName) -> Option<String> {
     match parsed_ref {
         RefName::LocalBranch(branch) => {
             (!branch.is_empty() && branch != "HEAD").then(|| format!("refs/heads/{branch}"))
@@ -89,13 +104,32 @@ fn to_git_ref_name(parsed_ref: &RefName) -> Option<String> {
         RefName::RemoteBranch { branch, remote } => (!branch.is_empty() && branch != "HEAD")
             .then(|| format!("refs/remotes/{remote}/{branch}")),
         RefName::Tag(tag) => Some(format!("refs/tags/{tag}")),
+        RefName::Note(name) => Some(format!("refs/notes/{name}")),
     }
 }
 
+/// Finds the Git ref which prevents `name` from being exported because one of
+/// them would be a directory of the other, e.g. `refs/heads/foo` and
+/// `refs/heads/foo/bar`.
+pub fn find_conflicting_git_ref<'a>(view: &'a View, name: &RefName) -> Option<&'a str> {
+    let git_ref_name = to_git_ref_name(name)?;
+    view.git_refs()
+        .iter()
+        .filter(|(_, target)| target.is_present())
+        .map(|(other_name, _)| other_name.as_str())
+        .find(|other_name| {
+            let is_dir_of = |dir: &str, name: &str| {
+                name.strip_prefix(dir)
+                    .is_some_and(|rest| rest.starts_with('/'))
+            };
+            is_dir_of(other_name, &git_ref_name) || is_dir_of(&git_ref_name, other_name)
+        })
+}
+
 fn to_remote_branch<'a>(parsed_ref: &'a RefName, remote_name: &str) -> Option<&'a str> {
     match parsed_ref {
         RefName::RemoteBranch { branch, remote } => (remote == remote_name).then_some(branch),
-        RefName::LocalBranch(..) | RefName::Tag(..) => None,
+        RefName::LocalBranch(..) | RefName::Tag(..) | RefName::Note(..) => None,
     }
 }
 
@@ -186,6 +220,12 @@ pub enum GitImportError {
         name = REMOTE_NAME_FOR_LOCAL_GIT_REPO
     )]
     RemoteReservedForLocalGitRepo,
+    #[error("Git refs {first} and {second} would both be imported as {ref_name}")]
+    DuplicateRefName {
+        ref_name: RefName,
+        first: String,
+        second: String,
+    },
     #[error("Unexpected backend error when importing refs")]
     InternalBackend(#[source] BackendError),
     #[error("Unexpected git error when importing refs")]
@@ -238,15 +278,61 @@ pub fn import_some_refs(
     mut_repo: &mut MutableRepo,
     git_settings: &GitSettings,
     git_ref_filter: impl Fn(&RefName) -> bool,
+) -> Result<GitImportStats, GitImportError> {
+    import_classified_refs(mut_repo, git_settings, |ref_name| {
+        if git_ref_filter(ref_name) {
+            RefImportDecision::Import
+        } else {
+            RefImportDecision::Skip
+        }
+    })
+}
+
+/// What to do with a ref when importing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RefImportDecision {
+    /// Import the ref under its own name.
+    Import,
+    /// Leave the ref as it is in the Jujutsu repo.
+    Skip,
+    /// Import the Git ref under another name, e.g. a remote branch
+    /// `pull/1/head@origin` as `pr/1@origin`.
+    RenameTo(RefName),
+}
+
+/// Reflect changes made in the underlying Git repo in the Jujutsu repo,
+/// deciding what to do with each ref by calling `classify_ref`.
+///
+/// `classify_ref` is called with the name of each Git ref, and with the name
+/// of each ref in the Jujutsu repo to tell whether it's managed by the
+/// import. A ref which isn't skipped but which no Git ref is imported as is
+/// deleted, so a ref that is renamed on import should be imported as is under
+/// its new name. The keys of `changed_remote_refs` in the returned stats are
+/// the names the refs were imported as. It's an error for several Git refs to
+/// be imported under the same name.
+///
+/// `classify_ref` is called once or twice for each ref, while the refs are
+/// read, so it should be cheap. Repos can have tens of thousands of refs, so
+/// it shouldn't do any I/O, and patterns should be compiled beforehand.
+pub fn import_classified_refs(
+    mut_repo: &mut MutableRepo,
+    git_settings: &GitSettings,
+    classify_ref: impl Fn(&RefName) -> RefImportDecision,
 ) -> Result<GitImportStats, GitImportError> {
     let store = mut_repo.store();
     let git_backend = get_git_backend(store).ok_or(GitImportError::UnexpectedBackend)?;
     let git_repo = git_backend.git_repo();
+    // Notes are only imported if they're configured.
+    let classify_ref = |ref_name: &RefName| match ref_name {
+        RefName::Note(name) if !git_settings.import_notes.contains(name) => RefImportDecision::Skip,
+        _ => classify_ref(ref_name),
+    };
+    let git_ref_filter = |ref_name: &RefName| classify_ref(ref_name) != RefImportDecision::Skip;
 
     let RefsToImport {
         changed_git_refs,
         changed_remote_refs,
-    } = diff_refs_to_import(mut_repo.view(), &git_repo, git_ref_filter)?;
+    } = diff_refs_to_import(mut_repo.view(), &git_repo, classify_ref)?;
 
     // Bulk-import all reachable Git commits to the backend to reduce overhead
     // of table merging and ref updates.
@@ -321,11 +407,14 @@ pub fn import_some_refs(
                 }
                 // TODO: If we add Git-tracking tag, it will be updated here.
             }
+            RefName::Note(_) => {
+                // Notes are only recorded as Git refs, which were updated above.
+            }
         }
     }
 
     let abandoned_commits = if git_settings.abandon_unreachable_commits {
-        abandon_unreachable_commits(mut_repo, &changed_remote_refs)
+        abandon_unreachable_commits(mut_repo, &changed_remote_refs, git_ref_filter)
     } else {
         vec![]
     };
@@ -338,9 +427,11 @@ pub fn import_some_refs(
 
 /// Finds commits that used to be reachable in git that no longer are reachable.
 /// Those commits will be recorded as abandoned in the `MutableRepo`.
+/// Remote branches which were excluded by `git_ref_filter` keep their commits.
 fn abandon_unreachable_commits(
     mut_repo: &mut MutableRepo,
     changed_remote_refs: &BTreeMap<RefName, (RemoteRef, RefTarget)>,
+    git_ref_filter: impl Fn(&RefName) -> bool,
 ) -> Vec<CommitId> {
     let hidable_git_heads = changed_remote_refs
         .values()
@@ -355,6 +446,17 @@ fn abandon_unreachable_commits(
             .values()
             .flat_map(|(_, new_target)| new_target.added_ids()),
         pinned_commit_ids(mut_repo.view()),
+        mut_repo
+            .view()
+            .all_remote_branches()
+            .filter(|((branch, remote), _)| {
+                *remote != REMOTE_NAME_FOR_LOCAL_GIT_REPO
+                    && !git_ref_filter(&RefName::RemoteBranch {
+                        branch: (*branch).to_owned(),
+                        remote: (*remote).to_owned(),
+                    })
+            })
+            .flat_map(|(_, remote_ref)| remote_ref.target.added_ids()),
         iter::once(mut_repo.store().root_commit_id()),
     )
     .cloned()
@@ -377,8 +479,9 @@ fn abandon_unreachable_commits(
 fn diff_refs_to_import(
     view: &View,
     git_repo: &gix::Repository,
-    git_ref_filter: impl Fn(&RefName) -> bool,
+    classify_ref: impl Fn(&RefName) -> RefImportDecision,
 ) -> Result<RefsToImport, GitImportError> {
+    let git_ref_filter = |ref_name: &RefName| classify_ref(ref_name) != RefImportDecision::Skip;
     let mut known_git_refs: HashMap<&str, &RefTarget> = view
         .git_refs()
         .iter()
@@ -389,7 +492,7 @@ fn diff_refs_to_import(
         })
         .collect();
     // TODO: migrate tags to the remote view, and don't destructure &RemoteRef
-    let mut known_remote_refs: HashMap<RefName, (&RefTarget, RemoteRefState)> = itertools::chain(
+    let mut known_remote_refs: HashMap<RefName, (&RefTarget, RemoteRefState)> = itertools::chain!(
         view.all_remote_branches()
             .map(|((branch, remote), remote_ref)| {
                 // TODO: want to abstract local ref as "git" tracking remote, but
@@ -411,12 +514,19 @@ fn diff_refs_to_import(
             let ref_name = RefName::Tag(name.to_owned());
             (ref_name, (target, RemoteRefState::Tracking))
         }),
+        // Notes are only stored as Git refs.
+        view.git_refs().iter().filter_map(|(full_name, target)| {
+            let ref_name = RefName::Note(full_name.strip_prefix("refs/notes/")?.to_owned());
+            Some((ref_name, (target, RemoteRefState::Tracking)))
+        }),
     )
     .filter(|(ref_name, _)| git_ref_filter(ref_name))
     .collect();
 
     let mut changed_git_refs = Vec::new();
     let mut changed_remote_refs = BTreeMap::new();
+    // Git ref names by the names they're imported as, to detect duplicates
+    let mut imported_ref_names: HashMap<RefName, String> = HashMap::new();
     let git_references = git_repo.references().map_err(GitImportError::from_git)?;
     let chain_git_refs_iters = || -> Result<_, gix::reference::iter::init::Error> {
         // Exclude uninteresting directories such as refs/jj/keep.
@@ -424,6 +534,7 @@ fn diff_refs_to_import(
             git_references.local_branches()?,
             git_references.remote_branches()?,
             git_references.tags()?,
+            git_references.prefixed("refs/notes/")?,
         ))
     };
     for git_ref in chain_git_refs_iters().map_err(GitImportError::from_git)? {
@@ -433,15 +544,24 @@ fn diff_refs_to_import(
             continue;
         };
         let Some(ref_name) = parse_git_ref(full_name) else {
-            // Skip other refs (such as notes) and symbolic refs.
+            // Skip other refs and symbolic refs.
             continue;
         };
-        if !git_ref_filter(&ref_name) {
-            continue;
-        }
+        let ref_name = match classify_ref(&ref_name) {
+            RefImportDecision::Import => ref_name,
+            RefImportDecision::Skip => continue,
+            RefImportDecision::RenameTo(new_name) => new_name,
+        };
         if is_reserved_git_remote_ref(&ref_name) {
             return Err(GitImportError::RemoteReservedForLocalGitRepo);
         }
+        if let Some(first) = imported_ref_names.insert(ref_name.clone(), full_name.to_owned()) {
+            return Err(GitImportError::DuplicateRefName {
+                ref_name,
+                first,
+                second: full_name.to_owned(),
+            });
+        }
         let old_git_target = known_git_refs.get(full_name).copied().flatten();
         let Some(id) = resolve_git_ref_to_commit_id(&git_ref, old_git_target) else {
             // Skip (or remove existing) invalid refs.
@@ -484,7 +604,7 @@ fn diff_refs_to_import(
 fn default_remote_ref_state_for(ref_name: &RefName, git_settings: &GitSettings) -> RemoteRefState {
     match ref_name {
         // LocalBranch means Git-tracking branch
-        RefName::LocalBranch(_) | RefName::Tag(_) => RemoteRefState::Tracking,
+        RefName::LocalBranch(_) | RefName::Tag(_) | RefName::Note(_) => RemoteRefState::Tracking,
         RefName::RemoteBranch { .. } => {
             if git_settings.auto_local_branch {
                 RemoteRefState::Tracking
@@ -504,11 +624,73 @@ fn pinned_commit_ids(view: &View) -> impl Iterator<Item = &CommitId> {
     itertools::chain!(
         view.local_branches().map(|(_, target)| target),
         view.tags().values(),
+        view.git_refs()
+            .iter()
+            .filter(|(full_name, _)| full_name.starts_with("refs/notes/"))
+            .map(|(_, target)| target),
         iter::once(view.git_head()),
     )
     .flat_map(|target| target.added_ids())
 }
 
+/// Git refs imported into a transaction which hasn't been committed yet.
+///
+/// The caller can inspect the stats and the resulting view, and then either
+/// `commit()` the import or `abort()` it. Dropping it also discards the
+/// import. Git commits which were newly imported stay in the backend either
+/// way, but they aren't visible unless the import is committed.
+pub struct PendingGitImport {
+    tx: Transaction,
+    stats: GitImportStats,
+    settings: UserSettings,
+}
+
+impl PendingGitImport {
+    /// Changes made by the import.
+    pub fn stats(&self) -> &GitImportStats {
+        &self.stats
+    }
+
+    /// The repo with the import applied.
+    pub fn repo(&self) -> &MutableRepo {
+        self.tx.repo()
+    }
+
+    /// Rebases the descendants of the abandoned commits and commits the
+    /// transaction.
+    pub fn commit(
+        mut self,
+        description: impl Into<String>,
+    ) -> Result<Arc<ReadonlyRepo>, TreeMergeError> {
+        self.tx.mut_repo().rebase_descendants(&self.settings)?;
+        Ok(self.tx.commit(description))
+    }
+
+    /// Discards the import.
+    pub fn abort(self) {}
+
+    /// Returns the transaction, e.g. to make more changes before committing
+    /// it.
+    pub fn into_transaction(self) -> Transaction {
+        self.tx
+    }
+}
+
+/// Like `import_refs()`, but imports into a new transaction which the caller
+/// can commit or abort after inspecting the changes.
+pub fn import_refs_pending(
+    repo: &Arc<ReadonlyRepo>,
+    settings: &UserSettings,
+) -> Result<PendingGitImport, GitImportError> {
+    let mut tx = repo.start_transaction(settings);
+    let stats = import_refs(tx.mut_repo(), &settings.git_settings())?;
+    Ok(PendingGitImport {
+        tx,
+        stats,
+        settings: settings.clone(),
+    })
+}
+
 /// Imports `HEAD@git` from the underlying Git repo.
 ///
 /// Unlike `import_refs()`, the old HEAD branch is not abandoned because HEAD
@@ -597,6 +779,13 @@ pub enum FailedRefExportReason {
     /// We wanted to modify it, but Git had deleted it
     #[error("Modified ref had been deleted in Git")]
     ModifiedInJjDeletedInGit,
+    /// We wanted to modify it, but Git had moved it to a different target
+    #[error("Modified ref had been modified differently in Git")]
+    ModifiedInJjModifiedInGit,
+    /// We wanted to add it, but an existing Git ref would be its directory or
+    /// a file inside it
+    #[error("Conflicts with the existing Git ref {0}")]
+    ConflictsWithGitRef(String),
     /// Failed to delete the ref from the Git repo
     #[error("Failed to delete")]
     FailedToDelete(#[source] Box<gix::reference::edit::Error>),
@@ -627,6 +816,25 @@ pub fn export_refs(mut_repo: &mut MutableRepo) -> Result<Vec<FailedRefExport>, G
     export_some_refs(mut_repo, |_| true)
 }
 
+/// Like `export_refs()`, but only exports the local branches named in
+/// `branch_names` and the remote branches of the same names. The other refs in
+/// the Git repo are left untouched.
+pub fn export_branches(
+    mut_repo: &mut MutableRepo,
+    branch_names: &[String],
+) -> Result<Vec<FailedRefExport>, GitExportError> {
+    export_some_refs(mut_repo, |ref_name| is_named_branch(ref_name, branch_names))
+}
+
+fn is_named_branch(ref_name: &RefName, branch_names: &[String]) -> bool {
+    match ref_name {
+        RefName::LocalBranch(branch) | RefName::RemoteBranch { branch, .. } => {
+            branch_names.contains(branch)
+        }
+        RefName::Tag(_) | RefName::Note(_) => false,
+    }
+}
+
 pub fn export_some_refs(
     mut_repo: &mut MutableRepo,
     git_ref_filter: impl Fn(&RefName) -> bool,
@@ -672,6 +880,12 @@ pub fn export_some_refs(
             }
         }
     }
+    // Listing all refs is only needed to check if new refs can be created.
+    let mut existing_refs = if branches_to_update.values().any(|(old, _)| old.is_none()) {
+        git_ref_names(&git_repo)?
+    } else {
+        BTreeSet::new()
+    };
     for (parsed_ref_name, old_oid) in branches_to_delete {
         let Some(git_ref_name) = to_git_ref_name(&parsed_ref_name) else {
             failed_branches.insert(parsed_ref_name, FailedRefExportReason::InvalidGitName);
@@ -680,6 +894,7 @@ pub fn export_some_refs(
         if let Err(reason) = delete_git_ref(&git_repo, &git_ref_name, &old_oid) {
             failed_branches.insert(parsed_ref_name, reason);
         } else {
+            existing_refs.remove(&git_ref_name);
             let new_target = RefTarget::absent();
             mut_repo.set_git_ref_target(&git_ref_name, new_target);
         }
@@ -689,9 +904,12 @@ pub fn export_some_refs(
             failed_branches.insert(parsed_ref_name, FailedRefExportReason::InvalidGitName);
             continue;
         };
-        if let Err(reason) = update_git_ref(&git_repo, &git_ref_name, old_oid, new_oid) {
+        if let Err(reason) =
+            update_git_ref(&git_repo, &existing_refs, &git_ref_name, old_oid, new_oid)
+        {
             failed_branches.insert(parsed_ref_name, reason);
         } else {
+            existing_refs.insert(git_ref_name.clone());
             let new_target = RefTarget::normal(CommitId::from_bytes(new_oid.as_bytes()));
             mut_repo.set_git_ref_target(&git_ref_name, new_target);
         }
@@ -711,6 +929,109 @@ pub fn export_some_refs(
     Ok(failed_branches)
 }
 
+/// Computes the refs that `export_refs()` would fail to export, without
+/// modifying the Git repo or the Jujutsu view.
+///
+/// The refs are checked against the current state of the Git repo in the same
+/// way as the actual export, so the result is the same unless the Git repo is
+/// changed concurrently.
+pub fn preview_export_refs(repo: &dyn Repo) -> Result<Vec<FailedRefExport>, GitExportError> {
+    preview_export_some_refs(repo, |_| true)
+}
+
+/// Like `preview_export_refs()`, but only for the branches `export_branches()`
+/// would export.
+pub fn preview_export_branches(
+    repo: &dyn Repo,
+    branch_names: &[String],
+) -> Result<Vec<FailedRefExport>, GitExportError> {
+    preview_export_some_refs(repo, |ref_name| is_named_branch(ref_name, branch_names))
+}
+
+fn preview_export_some_refs(
+    repo: &dyn Repo,
+    git_ref_filter: impl Fn(&RefName) -> bool,
+) -> Result<Vec<FailedRefExport>, GitExportError> {
+    let checked = check_refs_to_export(repo, git_ref_filter)?;
+    Ok(checked.failed_branches)
+}
+
+/// Returns the Git refs `export_refs()` would update, mapped to their new
+/// targets, or `None` for the refs it would delete. Nothing is modified.
+pub fn preview_exported_refs(
+    repo: &dyn Repo,
+) -> Result<BTreeMap<String, Option<CommitId>>, GitExportError> {
+    let checked = check_refs_to_export(repo, |_| true)?;
+    Ok(checked.exported_refs)
+}
+
+struct CheckedRefsToExport {
+    /// New targets of the Git refs to update, or `None` for the ones to delete.
+    exported_refs: BTreeMap<String, Option<CommitId>>,
+    failed_branches: Vec<FailedRefExport>,
+}
+
+/// Checks the refs to export against the Git repo without modifying it.
+fn check_refs_to_export(
+    repo: &dyn Repo,
+    git_ref_filter: impl Fn(&RefName) -> bool,
+) -> Result<CheckedRefsToExport, GitExportError> {
+    let git_repo = get_git_repo(repo.store()).ok_or(GitExportError::UnexpectedBackend)?;
+
+    let RefsToExport {
+        branches_to_update,
+        branches_to_delete,
+        mut failed_branches,
+    } = diff_refs_to_export(repo.view(), repo.store().root_commit_id(), git_ref_filter);
+
+    let mut exported_refs = BTreeMap::new();
+    let mut existing_refs = if branches_to_update.values().any(|(old, _)| old.is_none()) {
+        git_ref_names(&git_repo)?
+    } else {
+        BTreeSet::new()
+    };
+    for (parsed_ref_name, old_oid) in branches_to_delete {
+        let Some(git_ref_name) = to_git_ref_name(&parsed_ref_name) else {
+            failed_branches.insert(parsed_ref_name, FailedRefExportReason::InvalidGitName);
+            continue;
+        };
+        match check_git_ref_deletion(&git_repo, &git_ref_name, &old_oid) {
+            Ok(_) => {
+                existing_refs.remove(&git_ref_name);
+                exported_refs.insert(git_ref_name, None);
+            }
+            Err(reason) => {
+                failed_branches.insert(parsed_ref_name, reason);
+            }
+        }
+    }
+    for (parsed_ref_name, (old_oid, new_oid)) in branches_to_update {
+        let Some(git_ref_name) = to_git_ref_name(&parsed_ref_name) else {
+            failed_branches.insert(parsed_ref_name, FailedRefExportReason::InvalidGitName);
+            continue;
+        };
+        match check_git_ref_update(&git_repo, &existing_refs, &git_ref_name, old_oid, new_oid) {
+            Ok(_) => {
+                existing_refs.insert(git_ref_name.clone());
+                exported_refs.insert(git_ref_name, Some(CommitId::from_bytes(new_oid.as_bytes())));
+            }
+            Err(reason) => {
+                failed_branches.insert(parsed_ref_name, reason);
+            }
+        }
+    }
+
+    let failed_branches = failed_branches
+        .into_iter()
+        .map(|(name, reason)| FailedRefExport { name, reason })
+        .sorted_unstable_by(|a, b| a.name.cmp(&b.name))
+        .collect();
+    Ok(CheckedRefsToExport {
+        exported_refs,
+        failed_branches,
+    })
+}
+
 fn copy_exportable_local_branches_to_remote_view(
     mut_repo: &mut MutableRepo,
     remote_name: &str,
@@ -829,77 +1150,126 @@ fn diff_refs_to_export(
     }
 }
 
-fn delete_git_ref(
+/// Lists the names of all refs in the Git repo, used to detect refs which
+/// can't be created because of a directory/file conflict.
+fn git_ref_names(git_repo: &gix::Repository) -> Result<BTreeSet<String>, GitExportError> {
+    let mut names = BTreeSet::new();
+    let platform = git_repo.references().map_err(GitExportError::from_git)?;
+    for git_ref in platform.all().map_err(GitExportError::from_git)? {
+        let git_ref = git_ref.map_err(GitExportError::from_git)?;
+        if let Ok(name) = str::from_utf8(git_ref.name().as_bstr()) {
+            names.insert(name.to_owned());
+        }
+    }
+    Ok(names)
+}
+
+/// Finds an existing ref which `git_ref_name` would be a directory or a file
+/// of, e.g. `refs/heads/foo` for `refs/heads/foo/bar`.
+fn find_dir_conflicting_ref<'a>(
+    existing_refs: &'a BTreeSet<String>,
+    git_ref_name: &str,
+) -> Option<&'a str> {
+    let parent = git_ref_name
+        .match_indices('/')
+        .map(|(pos, _)| &git_ref_name[..pos])
+        .find_map(|dir| existing_refs.get(dir));
+    let child = || {
+        let dir = format!("{git_ref_name}/");
+        existing_refs
+            .range(dir.clone()..)
+            .next()
+            .filter(|name| name.starts_with(&dir))
+    };
+    parent.or_else(child).map(String::as_str)
+}
+
+/// Checks whether the Git ref can be deleted. Returns true if it still exists
+/// and needs to be deleted.
+fn check_git_ref_deletion(
     git_repo: &gix::Repository,
     git_ref_name: &str,
     old_oid: &gix::oid,
-) -> Result<(), FailedRefExportReason> {
+) -> Result<bool, FailedRefExportReason> {
     if let Ok(git_ref) = git_repo.find_reference(git_ref_name) {
         if git_ref.inner.target.try_id() == Some(old_oid) {
             // The branch has not been updated by git, so go ahead and delete it
-            git_ref
-                .delete()
-                .map_err(|err| FailedRefExportReason::FailedToDelete(err.into()))?;
+            Ok(true)
         } else {
             // The branch was updated by git
-            return Err(FailedRefExportReason::DeletedInJjModifiedInGit);
+            Err(FailedRefExportReason::DeletedInJjModifiedInGit)
         }
     } else {
         // The branch is already deleted
+        Ok(false)
+    }
+}
+
+fn delete_git_ref(
+    git_repo: &gix::Repository,
+    git_ref_name: &str,
+    old_oid: &gix::oid,
+) -> Result<(), FailedRefExportReason> {
+    if check_git_ref_deletion(git_repo, git_ref_name, old_oid)? {
+        if let Ok(git_ref) = git_repo.find_reference(git_ref_name) {
+            git_ref
+                .delete()
+                .map_err(|err| FailedRefExportReason::FailedToDelete(err.into()))?;
+        }
     }
     Ok(())
 }
 
+/// Checks whether the Git ref can be updated from `old_oid` to `new_oid`.
+/// Returns true if it needs to be written.
+fn check_git_ref_update(
+    git_repo: &gix::Repository,
+    existing_refs: &BTreeSet<String>,
+    git_ref_name: &str,
+    old_oid: Option<gix::ObjectId>,
+    new_oid: gix::ObjectId,
+) -> Result<bool, FailedRefExportReason> {
+    let current_target = git_repo
+        .find_reference(git_ref_name)
+        .ok()
+        .map(|git_ref| git_ref.inner.target.try_id().map(ToOwned::to_owned));
+    match (old_oid, current_target) {
+        // Git already points the ref to our desired target
+        (_, Some(Some(current_oid))) if current_oid == new_oid => Ok(false),
+        // The branch was added in jj and in git with different targets
+        (None, Some(_)) => Err(FailedRefExportReason::AddedInJjAddedInGit),
+        // The branch was added in jj but still doesn't exist in git
+        (None, None) => match find_dir_conflicting_ref(existing_refs, git_ref_name) {
+            Some(other_name) => Err(FailedRefExportReason::ConflictsWithGitRef(
+                other_name.to_owned(),
+            )),
+            None => Ok(true),
+        },
+        // The branch was modified in jj and is unchanged in git
+        (Some(old_oid), Some(Some(current_oid))) if current_oid == old_oid => Ok(true),
+        (Some(_), Some(_)) => Err(FailedRefExportReason::ModifiedInJjModifiedInGit),
+        (Some(_), None) => Err(FailedRefExportReason::ModifiedInJjDeletedInGit),
+    }
+}
+
 fn update_git_ref(
     git_repo: &gix::Repository,
+    existing_refs: &BTreeSet<String>,
     git_ref_name: &str,
     old_oid: Option<gix::ObjectId>,
     new_oid: gix::ObjectId,
 ) -> Result<(), FailedRefExportReason> {
-    match old_oid {
-        None => {
-            if let Ok(git_repo_ref) = git_repo.find_reference(git_ref_name) {
-                // The branch was added in jj and in git. We're good if and only if git
-                // pointed it to our desired target.
-                if git_repo_ref.inner.target.try_id() != Some(&new_oid) {
-                    return Err(FailedRefExportReason::AddedInJjAddedInGit);
-                }
-            } else {
-                // The branch was added in jj but still doesn't exist in git, so add it
-                git_repo
-                    .reference(
-                        git_ref_name,
-                        new_oid,
-                        gix::refs::transaction::PreviousValue::MustNotExist,
-                        "export from jj",
-                    )
-                    .map_err(|err| FailedRefExportReason::FailedToSet(err.into()))?;
-            }
-        }
-        Some(old_oid) => {
-            // The branch was modified in jj. We can use gix API for updating under a lock.
-            if let Err(err) = git_repo.reference(
-                git_ref_name,
-                new_oid,
-                gix::refs::transaction::PreviousValue::MustExistAndMatch(old_oid.into()),
-                "export from jj",
-            ) {
-                // The reference was probably updated in git
-                if let Ok(git_repo_ref) = git_repo.find_reference(git_ref_name) {
-                    // We still consider this a success if it was updated to our desired target
-                    if git_repo_ref.inner.target.try_id() != Some(&new_oid) {
-                        return Err(FailedRefExportReason::FailedToSet(err.into()));
-                    }
-                } else {
-                    // The reference was deleted in git and moved in jj
-                    return Err(FailedRefExportReason::ModifiedInJjDeletedInGit);
-                }
-            } else {
-                // Successfully updated from old_oid to new_oid (unchanged in
-                // git)
-            }
-        }
+    if !check_git_ref_update(git_repo, existing_refs, git_ref_name, old_oid, new_oid)? {
+        return Ok(());
     }
+    // Write under a lock in case git updated the ref since we checked it.
+    let previous_value = match old_oid {
+        None => gix::refs::transaction::PreviousValue::MustNotExist,
+        Some(old_oid) => gix::refs::transaction::PreviousValue::MustExistAndMatch(old_oid.into()),
+    };
+    git_repo
+        .reference(git_ref_name, new_oid, previous_value, "export from jj")
+        .map_err(|err| FailedRefExportReason::FailedToSet(err.into()))?;
     Ok(())
 }
 
@@ -1078,6 +1448,9 @@ fn rename_remote_refs(mut_repo: &mut MutableRepo, old_remote_name: &str, new_rem
 
 const INVALID_REFSPEC_CHARS: [char; 5] = [':', '^', '?', '[', ']'];
 
+/// Number of times the user is asked for a password before the operation fails.
+const MAX_PASSWORD_ATTEMPTS: usize = 3;
+
 #[derive(Error, Debug)]
 pub enum GitFetchError {
     #[error("No git remote named '{0}'")]
@@ -1087,6 +1460,8 @@ pub enum GitFetchError {
         chars = INVALID_REFSPEC_CHARS.iter().join("`, `")
     )]
     InvalidBranchPattern,
+    #[error("Invalid refspec '{0}'. The destination must be a ref under refs/remotes/<remote>/")]
+    InvalidRefspec(String),
     #[error("Failed to import Git refs")]
     GitImportError(#[from] GitImportError),
     // TODO: I'm sure there are other errors possible, such as transport-level errors.
@@ -1112,21 +1487,6 @@ pub fn fetch(
     callbacks: RemoteCallbacks<'_>,
     git_settings: &GitSettings,
 ) -> Result<GitFetchStats, GitFetchError> {
-    // Perform a `git fetch` on the local git repo, updating the remote-tracking
-    // branches in the git repo.
-    let mut remote = git_repo.find_remote(remote_name).map_err(|err| {
-        if is_remote_not_found_err(&err) {
-            GitFetchError::NoSuchRemote(remote_name.to_string())
-        } else {
-            GitFetchError::InternalGitError(err)
-        }
-    })?;
-    let mut fetch_options = git2::FetchOptions::new();
-    let mut proxy_options = git2::ProxyOptions::new();
-    proxy_options.auto();
-    fetch_options.proxy_options(proxy_options);
-    let callbacks = callbacks.into_git();
-    fetch_options.remote_callbacks(callbacks);
     // At this point, we are only updating Git's remote tracking branches, not the
     // local branches.
     let refspecs: Vec<_> = branch_names
@@ -1139,13 +1499,150 @@ pub fn fetch(
         })
         .collect::<Option<_>>()
         .ok_or(GitFetchError::InvalidBranchPattern)?;
+    fetch_impl(
+        mut_repo,
+        git_repo,
+        remote_name,
+        &refspecs,
+        branch_names,
+        callbacks,
+        git_settings,
+    )
+}
+
+/// Fetches with the given refspecs instead of the default branch refspecs.
+///
+/// The destination of each refspec must be under `refs/remotes/<remote>/`, so
+/// the fetched refs are imported as remote branches named by the part after
+/// that prefix. For example, `+refs/pull/*/head:refs/remotes/origin/pr/*`
+/// imports `pr/123@origin`. Tags are imported as usual.
+#[tracing::instrument(skip(mut_repo, git_repo, callbacks))]
+pub fn fetch_refspecs(
+    mut_repo: &mut MutableRepo,
+    git_repo: &git2::Repository,
+    remote_name: &str,
+    refspecs: &[String],
+    callbacks: RemoteCallbacks<'_>,
+    git_settings: &GitSettings,
+) -> Result<GitFetchStats, GitFetchError> {
+    let branch_patterns: Vec<_> = refspecs
+        .iter()
+        .map(|refspec| {
+            parse_refspec_destination(refspec, remote_name)
+                .ok_or_else(|| GitFetchError::InvalidRefspec(refspec.clone()))
+        })
+        .try_collect()?;
+    fetch_impl(
+        mut_repo,
+        git_repo,
+        remote_name,
+        refspecs,
+        &branch_patterns,
+        callbacks,
+        git_settings,
+    )
+}
+
+/// Returns the refspec for `fetch_refspecs()` which fetches `source`, a ref
+/// which isn't a branch or the full id of a commit, into a remote-tracking ref
+/// named after it under `namespace`. Without a destination, Git would only
+/// record the fetched commit in `FETCH_HEAD`.
+///
+/// A leading `refs/` is stripped from the name, and added to the source if
+/// missing. For example, `pull/1/head` is fetched as the remote branch
+/// `<namespace>/pull/1/head@<remote>`, and a commit as
+/// `<namespace>/<commit id>@<remote>`. Returns `None` if the resulting ref name
+/// isn't valid.
+pub fn fetched_ref_refspec(remote_name: &str, namespace: &str, source: &str) -> Option<String> {
+    let is_commit_id = source.len() == 40 && source.bytes().all(|b| b.is_ascii_hexdigit());
+    let (src, name) = if is_commit_id {
+        let id = source.to_ascii_lowercase();
+        (id.clone(), id)
+    } else if let Some(name) = source.strip_prefix("refs/") {
+        (source.to_owned(), name.to_owned())
+    } else if source == "HEAD" {
+        (source.to_owned(), source.to_owned())
+    } else {
+        (format!("refs/{source}"), source.to_owned())
+    };
+    let namespace = namespace.trim_matches('/');
+    let dst = if namespace.is_empty() {
+        format!("refs/remotes/{remote_name}/{name}")
+    } else {
+        format!("refs/remotes/{remote_name}/{namespace}/{name}")
+    };
+    (!src.contains(INVALID_REFSPEC_CHARS) && git2::Reference::is_valid_name(&dst))
+        .then(|| format!("+{src}:{dst}"))
+}
+
+/// Returns the pattern of remote branch names the refspec maps refs to, or
+/// `None` if the destination isn't a remote-tracking ref of the remote.
+fn parse_refspec_destination(refspec: &str, remote_name: &str) -> Option<StringPattern> {
+    let (src, dst) = refspec
+        .strip_prefix('+')
+        .unwrap_or(refspec)
+        .split_once(':')?;
+    let name = dst.strip_prefix(&format!("refs/remotes/{remote_name}/"))?;
+    if src.is_empty() || name.is_empty() || name == "HEAD" {
+        return None;
+    }
+    // Both sides must have a wildcard, or neither.
+    match (src.contains('*'), name.contains('*')) {
+        (false, false) => Some(StringPattern::exact(name)),
+        (true, true) => StringPattern::glob(name).ok(),
+        _ => None,
+    }
+}
+
+fn fetch_impl(
+    mut_repo: &mut MutableRepo,
+    git_repo: &git2::Repository,
+    remote_name: &str,
+    refspecs: &[String],
+    branch_names: &[StringPattern],
+    mut callbacks: RemoteCallbacks<'_>,
+    git_settings: &GitSettings,
+) -> Result<GitFetchStats, GitFetchError> {
+    // Perform a `git fetch` on the local git repo, updating the remote-tracking
+    // branches in the git repo.
+    let mut remote = git_repo.find_remote(remote_name).map_err(|err| {
+        if is_remote_not_found_err(&err) {
+            GitFetchError::NoSuchRemote(remote_name.to_string())
+        } else {
+            GitFetchError::InternalGitError(err)
+        }
+    })?;
+    let mut fetch_options = git2::FetchOptions::new();
+    let proxy_options = callbacks.proxy_options(git_repo, &remote, remote.url());
+    fetch_options.proxy_options(proxy_options);
+    let http_headers = callbacks.http_headers(git_repo, remote_name, remote.url());
+    fetch_options.custom_headers(&http_headers.iter().map(String::as_str).collect_vec());
+    // The refspecs are passed explicitly, so an anonymous remote updates the
+    // same remote-tracking refs.
+    if let Some(rewritten_remote) = callbacks.rewritten_remote(git_repo, remote.url())? {
+        remote = rewritten_remote;
+    }
+    if let Some(depth) = git_settings.fetch_depth {
+        // The largest depth fetches the full history, including the parents
+        // of the commits which were shallow before.
+        fetch_options.depth(depth.try_into().unwrap_or(i32::MAX));
+    }
+    let is_cancelled = callbacks.is_cancelled;
+    let callbacks = callbacks.into_git(remote_name);
+    fetch_options.remote_callbacks(callbacks);
     if refspecs.is_empty() {
         // Don't fall back to the base refspecs.
         let stats = GitFetchStats::default();
         return Ok(stats);
     }
     tracing::debug!("remote.download");
-    remote.download(&refspecs, Some(&mut fetch_options))?;
+    let download_result = remote.download(refspecs, Some(&mut fetch_options));
+    // The error of an aborted download depends on the transport, and some
+    // transports don't abort at all, so check before any ref is updated.
+    if is_cancelled.is_some_and(|cb| cb()) {
+        return Err(RemoteCallbacks::cancelled_error().into());
+    }
+    download_result?;
     tracing::debug!("remote.prune");
     remote.prune(None)?;
     tracing::debug!("remote.update_tips");
@@ -1163,6 +1660,9 @@ pub fn fetch(
             }
         }
     }
+    if let Some(branch_name) = &default_branch {
+        set_remote_default_branch(git_repo, remote_name, branch_name);
+    }
     tracing::debug!("remote.disconnect");
     remote.disconnect()?;
 
@@ -1182,6 +1682,103 @@ pub fn fetch(
     Ok(stats)
 }
 
+/// Records the default branch of the remote as the `refs/remotes/<remote>/HEAD`
+/// symbolic ref, like `git remote set-head --auto` does. Nothing is recorded if
+/// the remote-tracking ref of the branch wasn't fetched.
+fn set_remote_default_branch(git_repo: &git2::Repository, remote_name: &str, branch_name: &str) {
+    let target = format!("refs/remotes/{remote_name}/{branch_name}");
+    if git_repo.find_reference(&target).is_err() {
+        return;
+    }
+    let head_name = format!("refs/remotes/{remote_name}/HEAD");
+    if let Err(err) = git_repo.reference_symbolic(&head_name, &target, true, "set by jj fetch") {
+        tracing::warn!(?err, head_name, "failed to record the default branch");
+    }
+}
+
+/// Returns the default branch of the remote, as recorded by the last fetch
+/// from it (or by Git). Returns `None` if the remote didn't advertise one.
+pub fn get_remote_default_branch(git_repo: &git2::Repository, remote_name: &str) -> Option<String> {
+    let head_ref = git_repo
+        .find_reference(&format!("refs/remotes/{remote_name}/HEAD"))
+        .ok()?;
+    let target = head_ref.symbolic_target()?;
+    match parse_git_ref(target)? {
+        RefName::RemoteBranch { branch, remote } if remote == remote_name => Some(branch),
+        _ => None,
+    }
+}
+
+/// Connects to the remote and returns the refs it advertises, like `git
+/// ls-remote`, without downloading anything.
+#[tracing::instrument(skip(git_repo, callbacks))]
+pub fn list_remote_refs(
+    git_repo: &git2::Repository,
+    remote_name: &str,
+    mut callbacks: RemoteCallbacks<'_>,
+) -> Result<Vec<(String, CommitId)>, GitFetchError> {
+    let mut remote = git_repo.find_remote(remote_name).map_err(|err| {
+        if is_remote_not_found_err(&err) {
+            GitFetchError::NoSuchRemote(remote_name.to_string())
+        } else {
+            GitFetchError::InternalGitError(err)
+        }
+    })?;
+    let mut url = remote.url().map(str::to_owned);
+    // git2 can't pass the extra HTTP headers when only connecting, so they
+    // aren't sent here.
+    let proxy_options = callbacks.proxy_options(git_repo, &remote, url.as_deref());
+    if let Some(rewritten_remote) = callbacks.rewritten_remote(git_repo, url.as_deref())? {
+        url = rewritten_remote.url().map(str::to_owned);
+        remote = rewritten_remote;
+    }
+    let is_cancelled = callbacks.is_cancelled;
+    let callbacks = callbacks.into_git(remote_name);
+    tracing::debug!("remote.connect");
+    let connect_result =
+        remote.connect_auth(git2::Direction::Fetch, Some(callbacks), Some(proxy_options));
+    if is_cancelled.is_some_and(|cb| cb()) {
+        return Err(RemoteCallbacks::cancelled_error().into());
+    }
+    let connection = connect_result?;
+    // git2 makes a slice from the pointer to the advertised refs, which the
+    // local transport leaves null if there are none, so handle that first.
+    if url.as_deref().is_some_and(is_empty_local_repo) {
+        return Ok(vec![]);
+    }
+    let refs = connection
+        .list()?
+        .iter()
+        .map(|head| {
+            let target = CommitId::from_bytes(head.oid().as_bytes());
+            (head.name().to_owned(), target)
+        })
+        .collect();
+    Ok(refs)
+}
+
+/// Returns the commits whose parents weren't fetched into the shallow Git
+/// repo. The list is empty if the repo isn't shallow.
+pub fn shallow_root_ids(git_repo: &git2::Repository) -> Vec<CommitId> {
+    let Ok(content) = std::fs::read_to_string(git_repo.path().join("shallow")) else {
+        return vec![];
+    };
+    content
+        .lines()
+        .filter_map(|line| CommitId::try_from_hex(line.trim()).ok())
+        .collect()
+}
+
+/// Returns true if `url` is the path of a local repository without any refs.
+fn is_empty_local_repo(url: &str) -> bool {
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    let Ok(repo) = git2::Repository::open(path) else {
+        return false;
+    };
+    repo.references()
+        .is_ok_and(|mut references| references.next().is_none())
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum GitPushError {
     #[error("No git remote named '{0}'")]
@@ -1195,6 +1792,19 @@ pub enum GitPushError {
     NotFastForward,
     #[error("Remote rejected the update of some refs (do you have permission to push to {0:?}?)")]
     RefUpdateRejected(Vec<String>),
+    /// The refs on the remote don't point to the expected commits, so they
+    /// were changed since they were last fetched.
+    #[error("Some refs changed on the remote since they were last fetched: {0:?}")]
+    RefChangedOnRemote(Vec<String>),
+    #[error(
+        "Invalid push refspec '{0}'. It must map a ref to a ref under refs/, with a wildcard on \
+         both sides or neither"
+    )]
+    InvalidRefspec(String),
+    /// The push failed after the remote was sent the ref updates, so they may
+    /// or may not have been applied.
+    #[error("The push failed after the ref updates were sent to the remote")]
+    Interrupted(#[source] git2::Error),
     // TODO: I'm sure there are other errors possible, such as transport-level errors,
     // and errors caused by the remote rejecting the push.
     #[error("Unexpected git error when pushing")]
@@ -1205,14 +1815,20 @@ pub enum GitPushError {
 pub struct GitBranchPushTargets {
     pub branch_updates: Vec<(String, BranchPushUpdate)>,
     pub force_pushed_branches: HashSet<String>,
+    /// Whether to refuse to update the branches on the remote unless they
+    /// still point to the `old_target`s, like `git push --force-with-lease`.
+    pub force_with_lease: bool,
 }
 
 pub struct GitRefUpdate {
     pub qualified_name: String,
-    // TODO: We want this to be a `current_target: Option<CommitId>` for the expected current
-    // commit on the remote. It's a blunt "force" option instead until git2-rs supports the
-    // "push negotiation" callback (https://github.com/rust-lang/git2-rs/issues/733).
+    /// Whether the ref on the remote may be moved backwards or sideways. This
+    /// doesn't check where it currently points; set `expected_current_target`
+    /// for that, which is checked through the "push negotiation" callback.
     pub force: bool,
+    /// If set, the update is refused unless the ref on the remote currently
+    /// points to this commit, or is absent if it's `Some(None)`.
+    pub expected_current_target: Option<Option<CommitId>>,
     pub new_target: Option<CommitId>,
 }
 
@@ -1230,6 +1846,7 @@ pub fn push_branches(
         .map(|(branch_name, update)| GitRefUpdate {
             qualified_name: format!("refs/heads/{branch_name}"),
             force: targets.force_pushed_branches.contains(branch_name),
+            expected_current_target: targets.force_with_lease.then(|| update.old_target.clone()),
             new_target: update.new_target.clone(),
         })
         .collect_vec();
@@ -1260,9 +1877,13 @@ pub fn push_updates(
 ) -> Result<(), GitPushError> {
     let mut temp_refs = vec![];
     let mut qualified_remote_refs = vec![];
+    let mut expected_remote_targets = HashMap::new();
     let mut refspecs = vec![];
     for update in updates {
         qualified_remote_refs.push(update.qualified_name.as_str());
+        if let Some(expected_target) = &update.expected_current_target {
+            expected_remote_targets.insert(update.qualified_name.as_str(), expected_target);
+        }
         if let Some(new_target) = &update.new_target {
             // Create a temporary ref to work around https://github.com/libgit2/libgit2/issues/3178
             let temp_ref_name = format!("refs/jj/git-push/{}", new_target.hex());
@@ -1286,6 +1907,7 @@ pub fn push_updates(
         git_repo,
         remote_name,
         &qualified_remote_refs,
+        &expected_remote_targets,
         &refspecs,
         callbacks,
     );
@@ -1304,12 +1926,266 @@ pub fn push_updates(
     result
 }
 
+/// The refspecs `mirror_push_updates()` is usually given, which push all
+/// branches and tags of the Git repo under the same names, overwriting them.
+pub const DEFAULT_MIRROR_REFSPECS: [&str; 2] =
+    ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
+
+/// An update of a ref on the remote made by a mirror push.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GitMirrorUpdate {
+    /// The name of the ref on the remote.
+    pub qualified_name: String,
+    /// The ref in the Git repo which is pushed, or `None` if the ref on the
+    /// remote is deleted.
+    pub source: Option<String>,
+    /// Whether the ref on the remote is overwritten even if the update isn't
+    /// fast-forward.
+    pub force: bool,
+    /// The name of the remote branch if the ref is a branch.
+    pub branch: Option<String>,
+    /// Where the remote branch pointed when it was last fetched or pushed.
+    /// Always `None` if the ref isn't a branch.
+    pub old_target: Option<CommitId>,
+    /// The commit the pushed ref points to.
+    pub new_target: Option<CommitId>,
+}
+
+/// Returns the updates which make the refs on the remote mirror the refs of
+/// the Git repo according to `refspecs`. Each refspec is `[+]<src>:<dst>`,
+/// where both sides may contain a `*`, like `+refs/heads/*:refs/heads/*`.
+///
+/// If `prune`, the remote branches recorded in the repo view which match the
+/// destination of a refspec but which no ref maps to are deleted. Refs on the
+/// remote which were never fetched are left as they are. Branches which
+/// already point to the pushed commit are omitted.
+pub fn mirror_push_updates(
+    repo: &dyn Repo,
+    git_repo: &git2::Repository,
+    remote_name: &str,
+    refspecs: &[String],
+    prune: bool,
+) -> Result<Vec<GitMirrorUpdate>, GitPushError> {
+    let git_refs = git_commit_refs(git_repo)?;
+    mirror_push_updates_for(repo.view(), git_refs, remote_name, refspecs, prune)
+}
+
+/// Like `mirror_push_updates()`, but as if the refs returned by
+/// `preview_exported_refs()` had been exported to the Git repo, so that the
+/// updates can be shown without exporting.
+pub fn preview_mirror_push_updates(
+    repo: &dyn Repo,
+    git_repo: &git2::Repository,
+    remote_name: &str,
+    refspecs: &[String],
+    prune: bool,
+    exported_refs: &BTreeMap<String, Option<CommitId>>,
+) -> Result<Vec<GitMirrorUpdate>, GitPushError> {
+    let mut git_refs = git_commit_refs(git_repo)?;
+    for (git_ref_name, new_target) in exported_refs {
+        match new_target {
+            Some(id) => git_refs.insert(git_ref_name.clone(), id.clone()),
+            None => git_refs.remove(git_ref_name),
+        };
+    }
+    mirror_push_updates_for(repo.view(), git_refs, remote_name, refspecs, prune)
+}
+
+/// Returns the refs of the Git repo which point to commits, and the commits
+/// they point to.
+fn git_commit_refs(git_repo: &git2::Repository) -> Result<BTreeMap<String, CommitId>, git2::Error> {
+    let mut git_refs = BTreeMap::new();
+    for reference in git_repo.references()? {
+        let reference = reference?;
+        let Some(name) = reference.name() else {
+            continue;
+        };
+        let Ok(commit) = reference.peel_to_commit() else {
+            continue;
+        };
+        git_refs.insert(
+            name.to_owned(),
+            CommitId::from_bytes(commit.id().as_bytes()),
+        );
+    }
+    Ok(git_refs)
+}
+
+fn mirror_push_updates_for(
+    view: &View,
+    git_refs: BTreeMap<String, CommitId>,
+    remote_name: &str,
+    refspecs: &[String],
+    prune: bool,
+) -> Result<Vec<GitMirrorUpdate>, GitPushError> {
+    let parsed_refspecs: Vec<_> = refspecs
+        .iter()
+        .map(|refspec| {
+            parse_push_refspec(refspec).ok_or_else(|| GitPushError::InvalidRefspec(refspec.clone()))
+        })
+        .try_collect()?;
+    let remote_branch_name = |qualified_name: &str| {
+        qualified_name
+            .strip_prefix("refs/heads/")
+            .map(ToOwned::to_owned)
+    };
+    // If several refs map to the same destination, the last one wins.
+    let mut updates = BTreeMap::new();
+    for (source, commit_id) in &git_refs {
+        for (force, src, dst) in &parsed_refspecs {
+            let Some(matched) = match_refspec_side(src, source) else {
+                continue;
+            };
+            let qualified_name = dst.replacen('*', matched, 1);
+            let branch = remote_branch_name(&qualified_name);
+            let old_target = branch.as_ref().and_then(|branch| {
+                let remote_ref = view.get_remote_branch(branch, remote_name);
+                remote_ref.target.as_normal().cloned()
+            });
+            updates.insert(
+                qualified_name.clone(),
+                GitMirrorUpdate {
+                    qualified_name,
+                    source: Some(source.clone()),
+                    force: *force,
+                    branch,
+                    old_target,
+                    new_target: Some(commit_id.clone()),
+                },
+            );
+        }
+    }
+    if prune {
+        for (branch, remote_ref) in view.remote_branches(remote_name) {
+            let qualified_name = format!("refs/heads/{branch}");
+            let Some(old_target) = remote_ref.target.as_normal() else {
+                continue;
+            };
+            let matches_refspec = parsed_refspecs
+                .iter()
+                .any(|(_, _, dst)| match_refspec_side(dst, &qualified_name).is_some());
+            if matches_refspec && !updates.contains_key(&qualified_name) {
+                updates.insert(
+                    qualified_name.clone(),
+                    GitMirrorUpdate {
+                        qualified_name,
+                        source: None,
+                        force: true,
+                        branch: Some(branch.to_owned()),
+                        old_target: Some(old_target.clone()),
+                        new_target: None,
+                    },
+                );
+            }
+        }
+    }
+    Ok(updates
+        .into_values()
+        .filter(|update| update.branch.is_none() || update.old_target != update.new_target)
+        .collect())
+}
+
+/// Parses `[+]<src>:<dst>` into whether it's forced, the source, and the
+/// destination.
+fn parse_push_refspec(refspec: &str) -> Option<(bool, &str, &str)> {
+    let (force, refspec) = match refspec.strip_prefix('+') {
+        Some(refspec) => (true, refspec),
+        None => (false, refspec),
+    };
+    let (src, dst) = refspec.split_once(':')?;
+    if !src.starts_with("refs/") || !dst.starts_with("refs/") {
+        return None;
+    }
+    // Both sides must have a single wildcard, or neither.
+    match (src.matches('*').count(), dst.matches('*').count()) {
+        (0, 0) | (1, 1) => Some((force, src, dst)),
+        _ => None,
+    }
+}
+
+/// Returns the part of `name` the wildcard of `pattern` matches, or the empty
+/// string if `pattern` has no wildcard and is equal to `name`.
+fn match_refspec_side<'a>(pattern: &str, name: &'a str) -> Option<&'a str> {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => name
+            .strip_prefix(prefix)?
+            .strip_suffix(suffix)
+            .filter(|matched| !matched.is_empty()),
+        None => (pattern == name).then_some(""),
+    }
+}
+
+/// Pushes the mirror updates and updates the remote branches in the repo view
+/// accordingly.
+///
+/// If `force_with_lease`, the branches on the remote are only updated if they
+/// still point to their `old_target`s. Other refs are updated regardless.
+pub fn push_mirror(
+    mut_repo: &mut MutableRepo,
+    git_repo: &git2::Repository,
+    remote_name: &str,
+    updates: &[GitMirrorUpdate],
+    force_with_lease: bool,
+    callbacks: RemoteCallbacks<'_>,
+) -> Result<(), GitPushError> {
+    let mut qualified_remote_refs = vec![];
+    let mut expected_remote_targets = HashMap::new();
+    let mut refspecs = vec![];
+    for update in updates {
+        qualified_remote_refs.push(update.qualified_name.as_str());
+        if force_with_lease && update.branch.is_some() {
+            expected_remote_targets.insert(update.qualified_name.as_str(), &update.old_target);
+        }
+        refspecs.push(match &update.source {
+            Some(source) => format!(
+                "{}{source}:{}",
+                (if update.force { "+" } else { "" }),
+                update.qualified_name
+            ),
+            None => format!(":{}", update.qualified_name),
+        });
+    }
+    push_refs(
+        git_repo,
+        remote_name,
+        &qualified_remote_refs,
+        &expected_remote_targets,
+        &refspecs,
+        callbacks,
+    )?;
+
+    for update in updates {
+        let Some(branch) = &update.branch else {
+            continue;
+        };
+        let old_remote_ref = mut_repo.get_remote_branch(branch, remote_name);
+        // A branch created by the push is tracked if there's a local branch
+        // of the same name, as if it had been pushed by name.
+        let state = if old_remote_ref.is_present() {
+            old_remote_ref.state
+        } else if mut_repo.get_local_branch(branch).is_present() {
+            RemoteRefState::Tracking
+        } else {
+            RemoteRefState::New
+        };
+        let new_remote_ref = RemoteRef {
+            target: RefTarget::resolved(update.new_target.clone()),
+            state,
+        };
+        let git_ref_name = format!("refs/remotes/{remote_name}/{branch}");
+        mut_repo.set_git_ref_target(&git_ref_name, new_remote_ref.target.clone());
+        mut_repo.set_remote_branch(branch, remote_name, new_remote_ref);
+    }
+    Ok(())
+}
+
 fn push_refs(
     git_repo: &git2::Repository,
     remote_name: &str,
     qualified_remote_refs: &[&str],
+    expected_remote_targets: &HashMap<&str, &Option<CommitId>>,
     refspecs: &[String],
-    callbacks: RemoteCallbacks<'_>,
+    mut callbacks: RemoteCallbacks<'_>,
 ) -> Result<(), GitPushError> {
     if remote_name == REMOTE_NAME_FOR_LOCAL_GIT_REPO {
         return Err(GitPushError::RemoteReservedForLocalGitRepo);
@@ -1322,11 +2198,19 @@ fn push_refs(
         }
     })?;
     let mut remaining_remote_refs: HashSet<_> = qualified_remote_refs.iter().copied().collect();
+    let mut changed_remote_refs = vec![];
+    let mut sending_updates = false;
     let mut push_options = git2::PushOptions::new();
-    let mut proxy_options = git2::ProxyOptions::new();
-    proxy_options.auto();
+    // libgit2 has already applied url.<base>.pushInsteadOf and insteadOf to
+    // the URLs of the remote, so the callbacks see the rewritten URL.
+    let push_url = remote.pushurl().or(remote.url());
+    let proxy_options = callbacks.proxy_options(git_repo, &remote, push_url);
     push_options.proxy_options(proxy_options);
-    let mut callbacks = callbacks.into_git();
+    let http_headers = callbacks.http_headers(git_repo, remote_name, push_url);
+    push_options.custom_headers(&http_headers.iter().map(String::as_str).collect_vec());
+    let mut rewritten_remote = callbacks.rewritten_remote(git_repo, push_url)?;
+    let is_cancelled = callbacks.is_cancelled;
+    let mut callbacks = callbacks.into_git(remote_name);
     callbacks.push_update_reference(|refname, status| {
         // The status is Some if the ref update was rejected
         if status.is_none() {
@@ -1334,17 +2218,54 @@ fn push_refs(
         }
         Ok(())
     });
-    push_options.remote_callbacks(callbacks);
-    remote
-        .push(refspecs, Some(&mut push_options))
-        .map_err(|err| match (err.class(), err.code()) {
-            (git2::ErrorClass::Reference, git2::ErrorCode::NotFastForward) => {
-                GitPushError::NotFastForward
+    // Called with the current targets of the refs on the remote before
+    // anything is sent.
+    callbacks.push_negotiation(|updates| {
+        for update in updates {
+            let Some(refname) = update.dst_refname() else {
+                continue;
+            };
+            let Some(expected_target) = expected_remote_targets.get(refname) else {
+                continue;
+            };
+            let current_target =
+                (!update.src().is_zero()).then(|| CommitId::from_bytes(update.src().as_bytes()));
+            if current_target != **expected_target {
+                changed_remote_refs.push(refname.to_owned());
             }
-            _ => GitPushError::InternalGitError(err),
-        })?;
+        }
+        if changed_remote_refs.is_empty() {
+            sending_updates = true;
+            Ok(())
+        } else {
+            Err(git2::Error::from_str("Refs changed on the remote"))
+        }
+    });
+    push_options.remote_callbacks(callbacks);
+    let push_result = rewritten_remote
+        .as_mut()
+        .unwrap_or(&mut remote)
+        .push(refspecs, Some(&mut push_options));
     drop(push_options);
+    if !changed_remote_refs.is_empty() {
+        changed_remote_refs.sort();
+        return Err(GitPushError::RefChangedOnRemote(changed_remote_refs));
+    }
+    push_result.map_err(|err| match (err.class(), err.code()) {
+        (git2::ErrorClass::Reference, git2::ErrorCode::NotFastForward) => {
+            GitPushError::NotFastForward
+        }
+        // The error of an aborted push depends on the transport.
+        _ if is_cancelled.is_some_and(|cb| cb()) => {
+            GitPushError::InternalGitError(RemoteCallbacks::cancelled_error())
+        }
+        _ if sending_updates => GitPushError::Interrupted(err),
+        _ => GitPushError::InternalGitError(err),
+    })?;
     if remaining_remote_refs.is_empty() {
+        if rewritten_remote.is_some() {
+            update_pushed_remote_tracking_refs(git_repo, &remote, refspecs)?;
+        }
         Ok(())
     } else {
         Err(GitPushError::RefUpdateRejected(
@@ -1357,19 +2278,221 @@ fn push_refs(
     }
 }
 
+/// Updates the remote-tracking refs of the refs pushed by `refspecs` to the
+/// remote, as libgit2 does after pushing to a named remote. It doesn't when
+/// pushing to the anonymous remote of a rewritten URL, which has no fetch
+/// refspecs to map the pushed refs to remote-tracking refs.
+fn update_pushed_remote_tracking_refs(
+    git_repo: &git2::Repository,
+    remote: &git2::Remote,
+    refspecs: &[String],
+) -> Result<(), git2::Error> {
+    for refspec in refspecs {
+        let Some((src, dst)) = refspec.trim_start_matches('+').split_once(':') else {
+            continue;
+        };
+        for fetch_refspec in remote.refspecs() {
+            if fetch_refspec.direction() != git2::Direction::Fetch
+                || !fetch_refspec.src_matches(dst)
+            {
+                continue;
+            }
+            let tracking_ref = fetch_refspec.transform(dst)?;
+            let Some(tracking_ref) = tracking_ref.as_str() else {
+                continue;
+            };
+            if src.is_empty() {
+                if let Ok(mut reference) = git_repo.find_reference(tracking_ref) {
+                    reference.delete()?;
+                }
+            } else {
+                let oid = git_repo.refname_to_id(src)?;
+                git_repo.reference(tracking_ref, oid, true, "update by push")?;
+            }
+        }
+    }
+    Ok(())
+}
+
 #[non_exhaustive]
 #[derive(Default)]
 #[allow(clippy::type_complexity)]
 pub struct RemoteCallbacks<'a> {
     pub progress: Option<&'a mut dyn FnMut(&Progress)>,
-    pub get_ssh_keys: Option<&'a mut dyn FnMut(&str) -> Vec<PathBuf>>,
+    /// Receives the messages the server sends to be displayed to the user,
+    /// e.g. "Resolving deltas" or a link to create a pull request. Messages
+    /// may be split at arbitrary points, and lines end with either "\n" or
+    /// "\r" (if the line is to be overwritten by the next one).
+    pub sideband_progress: Option<&'a mut dyn FnMut(&[u8])>,
+    /// Receives the progress of packing and sending objects to the remote
+    /// while pushing.
+    pub push_progress: Option<&'a mut dyn FnMut(&PushProgress)>,
+    pub get_ssh_keys: Option<&'a mut dyn FnMut(&str, &str) -> Vec<PathBuf>>,
+    /// Returns the SSH key configured for the remote, given its name and URL.
+    /// If there is one, it's the only key tried: neither ssh-agent nor the
+    /// keys returned by `get_ssh_keys` are used for the remote.
+    pub get_configured_ssh_key: Option<&'a mut dyn FnMut(&str, &str) -> Option<PathBuf>>,
+    /// Tells whether ssh-agent can be reached. If unset, the agent is always
+    /// tried once before the keys returned by `get_ssh_keys`.
+    pub ssh_agent_available: Option<&'a mut dyn FnMut() -> bool>,
+    /// Returns the passphrase of an SSH key file, or `None` if the key isn't
+    /// encrypted.
+    pub get_ssh_key_passphrase: Option<&'a mut dyn FnMut(&Path) -> Option<String>>,
+    /// Called right before authenticating with an SSH key file, e.g. to ask
+    /// the user to touch a security key.
+    pub ssh_key_selected: Option<&'a mut dyn FnMut(&Path)>,
+    /// Returns the username to connect to an SSH remote whose URL doesn't
+    /// include one.
+    pub get_username: Option<&'a mut dyn FnMut(&str) -> Option<String>>,
     pub get_password: Option<&'a mut dyn FnMut(&str, &str) -> Option<String>>,
     pub get_username_password: Option<&'a mut dyn FnMut(&str) -> Option<(String, String)>>,
+    /// Verifies the host key of an SSH server, returning an error message if
+    /// the connection must be refused. If unset, libgit2 checks the key against
+    /// `~/.ssh/known_hosts`.
+    pub check_ssh_host_key: Option<&'a mut dyn FnMut(&SshHostKey<'_>) -> Result<(), String>>,
+    /// Checks the DER-encoded TLS certificate of an HTTPS server in addition
+    /// to (or instead of) libgit2's validation. Returns true to trust the
+    /// certificate regardless of the validation, false to leave the decision
+    /// to the validation, or an error message to refuse the connection.
+    pub check_tls_certificate: Option<&'a mut dyn FnMut(&str, &[u8]) -> Result<bool, String>>,
+    /// Tells whether the operation should be aborted. It's polled while data is
+    /// transferred, and the operation fails with `git2::ErrorCode::User` once
+    /// it returns true.
+    pub is_cancelled: Option<&'a dyn Fn() -> bool>,
+    /// Chooses the proxy to connect to the remote URL through, given the
+    /// proxy configured in Git (`remote.<name>.proxy` or `http.proxy`).
+    /// Returns `None` to connect directly. If unset, libgit2 detects the proxy
+    /// from the Git config and the environment.
+    pub get_proxy: Option<&'a mut dyn FnMut(&str, Option<&str>) -> Option<String>>,
+    /// Returns the extra HTTP headers to send to a remote, given its name, its
+    /// URL, and the headers configured in Git (`http.extraHeader`). The
+    /// headers are sent in order. If unset, the configured headers are sent.
+    pub get_http_headers: Option<&'a mut dyn FnMut(&str, &str, Vec<String>) -> Vec<String>>,
+    /// Returns the URL to connect to instead of the given URL of the remote,
+    /// e.g. to add the port configured for an SSH host. Returns `None` to
+    /// connect to the URL as is.
+    pub rewrite_url: Option<&'a mut dyn FnMut(&str) -> Option<String>>,
+}
+
+/// Reads the values of `http.extraHeader` in order. Like in Git, an empty value
+/// discards the values before it.
+fn configured_http_headers(config: &git2::Config) -> Vec<String> {
+    let mut headers = vec![];
+    let Ok(entries) = config.multivar("http.extraheader", None) else {
+        return headers;
+    };
+    let result = entries.for_each(|entry| match entry.value() {
+        Some("") => headers.clear(),
+        Some(value) => headers.push(value.to_owned()),
+        None => {}
+    });
+    if let Err(err) = result {
+        tracing::warn!(?err, "failed to read http.extraHeader");
+    }
+    headers
+}
+
+/// Overwrites the passphrase with zeros, so that it doesn't linger in freed
+/// memory after libgit2 has read the key.
+fn zeroize_passphrase(passphrase: Option<String>) {
+    if let Some(passphrase) = passphrase {
+        let mut bytes = passphrase.into_bytes();
+        bytes.fill(0);
+        std::hint::black_box(&bytes);
+    }
+}
+
+/// Asks the credential helpers configured in Git for the credentials of `url`.
+/// This is only done if there's no callback for passwords, which would ask
+/// the helpers itself and tell them whether the credentials worked.
+fn credential_helper(url: &str, username: Option<&str>) -> Option<git2::Cred> {
+    let config = git2::Config::open_default().ok()?;
+    let creds = git2::Cred::credential_helper(&config, url, username).ok()?;
+    tracing::info!("using credential_helper");
+    Some(creds)
+}
+
+fn auth_error(message: &str) -> git2::Error {
+    git2::Error::new(git2::ErrorCode::Auth, git2::ErrorClass::Callback, message)
 }
 
 impl<'a> RemoteCallbacks<'a> {
-    fn into_git(mut self) -> git2::RemoteCallbacks<'a> {
+    fn cancelled_error() -> git2::Error {
+        git2::Error::new(
+            git2::ErrorCode::User,
+            git2::ErrorClass::Callback,
+            "Operation cancelled",
+        )
+    }
+
+    fn proxy_options(
+        &mut self,
+        git_repo: &git2::Repository,
+        remote: &git2::Remote,
+        url: Option<&str>,
+    ) -> git2::ProxyOptions<'static> {
+        let mut proxy_options = git2::ProxyOptions::new();
+        match (&mut self.get_proxy, url) {
+            (Some(get_proxy), Some(url)) => {
+                let configured_proxy = git_repo.config().ok().and_then(|config| {
+                    remote
+                        .name()
+                        .and_then(|name| config.get_string(&format!("remote.{name}.proxy")).ok())
+                        .or_else(|| config.get_string("http.proxy").ok())
+                });
+                if let Some(proxy) = get_proxy(url, configured_proxy.as_deref()) {
+                    // The proxy URL may contain credentials, so it isn't logged.
+                    tracing::debug!(url, "connecting through proxy");
+                    proxy_options.url(&proxy);
+                }
+            }
+            _ => {
+                proxy_options.auto();
+            }
+        }
+        proxy_options
+    }
+
+    fn http_headers(
+        &mut self,
+        git_repo: &git2::Repository,
+        remote_name: &str,
+        url: Option<&str>,
+    ) -> Vec<String> {
+        let configured_headers = git_repo
+            .config()
+            .ok()
+            .map(|config| configured_http_headers(&config))
+            .unwrap_or_default();
+        match (&mut self.get_http_headers, url) {
+            (Some(get_http_headers), Some(url)) => {
+                get_http_headers(remote_name, url, configured_headers)
+            }
+            _ => configured_headers,
+        }
+    }
+
+    /// Returns an anonymous remote for the URL `rewrite_url` returns for
+    /// `url`, if any, to connect to instead of the remote itself.
+    fn rewritten_remote<'r>(
+        &mut self,
+        git_repo: &'r git2::Repository,
+        url: Option<&str>,
+    ) -> Result<Option<git2::Remote<'r>>, git2::Error> {
+        let (Some(rewrite_url), Some(url)) = (&mut self.rewrite_url, url) else {
+            return Ok(None);
+        };
+        let Some(new_url) = rewrite_url(url) else {
+            return Ok(None);
+        };
+        tracing::debug!(url, new_url, "connecting to rewritten url");
+        git_repo.remote_anonymous(&new_url).map(Some)
+    }
+
+    fn into_git(mut self, remote_name: &str) -> git2::RemoteCallbacks<'a> {
         let mut callbacks = git2::RemoteCallbacks::new();
+        let is_cancelled = self.is_cancelled;
+        let check_cancelled = move || is_cancelled.is_some_and(|cb| cb());
         if let Some(progress_cb) = self.progress {
             callbacks.transfer_progress(move |progress| {
                 progress_cb(&Progress {
@@ -1377,40 +2500,185 @@ impl<'a> RemoteCallbacks<'a> {
                         .then(|| progress.received_bytes() as u64),
                     overall: (progress.indexed_objects() + progress.indexed_deltas()) as f32
                         / (progress.total_objects() + progress.total_deltas()) as f32,
+                    received_bytes: progress.received_bytes() as u64,
+                    received_objects: progress.received_objects(),
+                    total_objects: progress.total_objects(),
                 });
-                true
+                !check_cancelled()
+            });
+        } else if is_cancelled.is_some() {
+            callbacks.transfer_progress(move |_| !check_cancelled());
+        }
+        if let Some(sideband_progress_cb) = self.sideband_progress {
+            callbacks.sideband_progress(move |data| {
+                sideband_progress_cb(data);
+                !check_cancelled()
+            });
+        } else if is_cancelled.is_some() {
+            callbacks.sideband_progress(move |_| !check_cancelled());
+        }
+        if let Some(push_progress_cb) = self.push_progress {
+            // libgit2 reports packing and sending separately, so the callback
+            // is shared.
+            let push_progress_cb = Rc::new(RefCell::new(push_progress_cb));
+            let pack_progress_cb = push_progress_cb.clone();
+            callbacks.pack_progress(move |stage, current, total| {
+                let phase = match stage {
+                    git2::PackBuilderStage::AddingObjects => PushPhase::CountingObjects,
+                    git2::PackBuilderStage::Deltafication => PushPhase::CompressingObjects,
+                };
+                (pack_progress_cb.borrow_mut())(&PushProgress {
+                    phase,
+                    current,
+                    total,
+                    sent_bytes: 0,
+                });
+            });
+            callbacks.push_transfer_progress(move |current, total, bytes| {
+                (push_progress_cb.borrow_mut())(&PushProgress {
+                    phase: PushPhase::WritingObjects,
+                    current,
+                    total,
+                    sent_bytes: bytes as u64,
+                });
+            });
+        }
+        if self.check_ssh_host_key.is_some() || self.check_tls_certificate.is_some() {
+            let mut check_ssh_host_key = self.check_ssh_host_key;
+            let mut check_tls_certificate = self.check_tls_certificate;
+            callbacks.certificate_check(move |cert, host| {
+                let rejected = |message| {
+                    git2::Error::new(
+                        git2::ErrorCode::Certificate,
+                        git2::ErrorClass::Callback,
+                        message,
+                    )
+                };
+                if let (Some(hostkey), Some(check_cb)) =
+                    (cert.as_hostkey(), check_ssh_host_key.as_mut())
+                {
+                    let (Some(key), Some(key_type)) = (hostkey.hostkey(), hostkey.hostkey_type())
+                    else {
+                        return Ok(git2::CertificateCheckStatus::CertificatePassthrough);
+                    };
+                    let host_key = SshHostKey {
+                        host,
+                        key_type: key_type.name(),
+                        key,
+                        sha256: hostkey.hash_sha256(),
+                    };
+                    check_cb(&host_key).map_err(rejected)?;
+                    Ok(git2::CertificateCheckStatus::CertificateOk)
+                } else if let (Some(x509), Some(check_cb)) =
+                    (cert.as_x509(), check_tls_certificate.as_mut())
+                {
+                    if check_cb(host, x509.data()).map_err(rejected)? {
+                        Ok(git2::CertificateCheckStatus::CertificateOk)
+                    } else {
+                        Ok(git2::CertificateCheckStatus::CertificatePassthrough)
+                    }
+                } else {
+                    Ok(git2::CertificateCheckStatus::CertificatePassthrough)
+                }
             });
         }
         // TODO: We should expose the callbacks to the caller instead -- the library
         // crate shouldn't read environment variables.
         let mut tried_ssh_agent = false;
         let mut ssh_key_paths_to_try: Option<Vec<PathBuf>> = None;
+        let remote_name = remote_name.to_owned();
+        let mut configured_ssh_key: Option<Option<PathBuf>> = None;
+        let mut tried_configured_ssh_key = false;
+        // libgit2 asks for credentials again whenever the server rejects them.
+        // Passwords are asked for every time, whether from the callbacks or the
+        // credential helpers, so the attempts are bounded.
+        let mut password_attempts = 0;
+        let mut next_password_attempt = move || {
+            password_attempts += 1;
+            if password_attempts > MAX_PASSWORD_ATTEMPTS {
+                Err(auth_error("Too many failed authentication attempts"))
+            } else {
+                Ok(())
+            }
+        };
         callbacks.credentials(move |url, username_from_url, allowed_types| {
             let span = tracing::debug_span!("RemoteCallbacks.credentials");
             let _ = span.enter();
 
-            let git_config = git2::Config::open_default();
-            let credential_helper = git_config
-                .and_then(|conf| git2::Cred::credential_helper(&conf, url, username_from_url));
-            if let Ok(creds) = credential_helper {
-                tracing::info!("using credential_helper");
-                return Ok(creds);
-            } else if let Some(username) = username_from_url {
+            if check_cancelled() {
+                return Err(Self::cancelled_error());
+            }
+
+            if allowed_types.contains(git2::CredentialType::USERNAME) {
+                let username = username_from_url
+                    .map(str::to_owned)
+                    .or_else(|| self.get_username.as_mut().and_then(|cb| cb(url)));
+                if let Some(username) = username {
+                    tracing::info!(username, "using username");
+                    return git2::Cred::username(&username);
+                }
+            }
+
+            // The credential helpers configured in Git are asked by the password
+            // callbacks if there are any, since they tell the helpers whether
+            // the credentials worked.
+            if let Some(username) = username_from_url {
                 if allowed_types.contains(git2::CredentialType::SSH_KEY) {
-                    // Try to get the SSH key from the agent once. We don't even check if
-                    // $SSH_AUTH_SOCK is set because Windows uses another mechanism.
-                    if !tried_ssh_agent {
-                        tracing::info!(username, "trying ssh_key_from_agent");
-                        tried_ssh_agent = true;
-                        return git2::Cred::ssh_key_from_agent(username).map_err(|err| {
+                    let configured_ssh_key = configured_ssh_key.get_or_insert_with(|| {
+                        self.get_configured_ssh_key
+                            .as_mut()
+                            .and_then(|cb| cb(&remote_name, url))
+                    });
+                    if let Some(path) = configured_ssh_key {
+                        if tried_configured_ssh_key {
+                            return Err(auth_error(&format!(
+                                "The server didn't accept the SSH key {} configured for {}",
+                                path.display(),
+                                remote_name
+                            )));
+                        }
+                        tried_configured_ssh_key = true;
+                        if !path.is_file() {
+                            return Err(auth_error(&format!(
+                                "The SSH key {} configured for {} doesn't exist",
+                                path.display(),
+                                remote_name
+                            )));
+                        }
+                        let passphrase =
+                            self.get_ssh_key_passphrase.as_mut().and_then(|cb| cb(path));
+                        if let Some(cb) = &mut self.ssh_key_selected {
+                            cb(path);
+                        }
+                        tracing::info!(username, path = ?path, "trying configured ssh_key");
+                        let cred = git2::Cred::ssh_key(username, None, path, passphrase.as_deref());
+                        zeroize_passphrase(passphrase);
+                        return cred.map_err(|err| {
                             tracing::error!(err = %err);
                             err
                         });
                     }
 
+                    // Try to get the SSH key from the agent once. libssh2 iterates over
+                    // all identities the agent offers. How to locate the agent is
+                    // platform specific, so that's left to the caller.
+                    if !tried_ssh_agent {
+                        tried_ssh_agent = true;
+                        let agent_available =
+                            self.ssh_agent_available.as_mut().map_or(true, |cb| cb());
+                        if agent_available {
+                            tracing::info!(username, "trying ssh_key_from_agent");
+                            return git2::Cred::ssh_key_from_agent(username).map_err(|err| {
+                                tracing::error!(err = %err);
+                                err
+                            });
+                        }
+                        tracing::info!("ssh-agent not available");
+                    }
+
                     let paths = ssh_key_paths_to_try.get_or_insert_with(|| {
                         if let Some(ref mut cb) = self.get_ssh_keys {
-                            let mut paths = cb(username);
+                            let mut paths = cb(url, username);
                             paths.reverse();
                             paths
                         } else {
@@ -1419,14 +2687,25 @@ impl<'a> RemoteCallbacks<'a> {
                     });
 
                     if let Some(path) = paths.pop() {
+                        let passphrase = self
+                            .get_ssh_key_passphrase
+                            .as_mut()
+                            .and_then(|cb| cb(&path));
+                        if let Some(cb) = &mut self.ssh_key_selected {
+                            cb(&path);
+                        }
                         tracing::info!(username, path = ?path, "trying ssh_key");
-                        return git2::Cred::ssh_key(username, None, &path, None).map_err(|err| {
+                        let cred =
+                            git2::Cred::ssh_key(username, None, &path, passphrase.as_deref());
+                        zeroize_passphrase(passphrase);
+                        return cred.map_err(|err| {
                             tracing::error!(err = %err);
                             err
                         });
                     }
                 }
                 if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+                    next_password_attempt()?;
                     if let Some(ref mut cb) = self.get_password {
                         if let Some(pw) = cb(url, username) {
                             tracing::info!(
@@ -1438,9 +2717,19 @@ impl<'a> RemoteCallbacks<'a> {
                                 err
                             });
                         }
+                    } else if let Some(creds) = credential_helper(url, Some(username)) {
+                        return Ok(creds);
                     }
                 }
+                if allowed_types.contains(git2::CredentialType::SSH_KEY) {
+                    // Falling back to the default credentials would fail with a
+                    // less helpful error.
+                    return Err(auth_error(&format!(
+                        "The server didn't accept any SSH key for {username}"
+                    )));
+                }
             } else if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+                next_password_attempt()?;
                 if let Some(ref mut cb) = self.get_username_password {
                     if let Some((username, pw)) = cb(url) {
                         tracing::info!(username, "using userpass_plaintext");
@@ -1449,6 +2738,8 @@ impl<'a> RemoteCallbacks<'a> {
                             err
                         });
                     }
+                } else if let Some(creds) = credential_helper(url, None) {
+                    return Ok(creds);
                 }
             }
             tracing::info!("using default");
@@ -1458,10 +2749,47 @@ impl<'a> RemoteCallbacks<'a> {
     }
 }
 
+/// Host key presented by an SSH server.
+pub struct SshHostKey<'a> {
+    pub host: &'a str,
+    /// Key type as written in `known_hosts`, e.g. `ssh-ed25519`
+    pub key_type: &'a str,
+    pub key: &'a [u8],
+    pub sha256: Option<&'a [u8; 32]>,
+}
+
 pub struct Progress {
     /// `Some` iff data transfer is currently in progress
     pub bytes_downloaded: Option<u64>,
     pub overall: f32,
+    /// Number of bytes received so far, including after the transfer is done
+    pub received_bytes: u64,
+    /// Number of objects received so far
+    pub received_objects: usize,
+    /// Number of objects the remote is sending
+    pub total_objects: usize,
+}
+
+/// The phases of a push, in the order they happen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PushPhase {
+    /// Finding the objects to send
+    CountingObjects,
+    /// Compressing the objects into a pack
+    CompressingObjects,
+    /// Sending the pack to the remote
+    WritingObjects,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PushProgress {
+    pub phase: PushPhase,
+    /// Number of objects processed so far in this phase
+    pub current: usize,
+    /// Number of objects to process in this phase, or 0 if it isn't known
+    pub total: usize,
+    /// Number of bytes sent so far
+    pub sent_bytes: u64,
 }
 
 #[derive(Default)]
@@ -15,9 +15,11 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
+use std::cell::Cell;
 use std::collections::HashSet;
 use std::fmt::{Debug, Error, Formatter};
 use std::io::{Cursor, Read};
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus};
 use std::sync::{Arc, Mutex, MutexGuard};
@@ -122,6 +124,9 @@ pub struct GitBackend {
     empty_tree_id: TreeId,
     extra_metadata_store: TableStore,
     cached_extra_metadata: Mutex<Option<Arc<ReadonlyTable>>>,
+    /// git2 repository instances handed out by `pooled_git_repo()` which are no
+    /// longer in use.
+    idle_git2_repos: Arc<Mutex<GitRepoPool>>,
     /// Whether tree of imported commit should be promoted to non-legacy format.
     imported_commit_uses_tree_conflict_format: bool,
 }
@@ -148,6 +153,7 @@ impl GitBackend {
             empty_tree_id,
             extra_metadata_store,
             cached_extra_metadata: Mutex::new(None),
+            idle_git2_repos: Default::default(),
             imported_commit_uses_tree_conflict_format,
         }
     }
@@ -290,6 +296,32 @@ impl GitBackend {
         git2::Repository::open(self.git_repo_path())
     }
 
+    /// Returns git repository instance which may have been opened earlier.
+    /// Opening one reads the Git config, which can be slow in large repos, so
+    /// instances are reused once they are dropped.
+    pub fn pooled_git_repo(&self) -> Result<PooledGitRepo, git2::Error> {
+        let (idle_repo, generation) = {
+            let mut pool = self.idle_git2_repos.lock().unwrap();
+            (pool.idle.pop(), pool.generation)
+        };
+        let repo = match idle_repo {
+            Some(repo) => repo,
+            None => self.open_git_repo()?,
+        };
+        Ok(PooledGitRepo {
+            repo: Some(repo),
+            generation: Cell::new(generation),
+            pool: self.idle_git2_repos.clone(),
+        })
+    }
+
+    /// Drops the instances of `pooled_git_repo()`, whose cached state might be
+    /// stale after the Git repo was modified behind their back. Instances in
+    /// use are dropped once they're no longer needed.
+    fn clear_git_repo_pool(&self) {
+        self.idle_git2_repos.lock().unwrap().clear();
+    }
+
     /// Path to the `.git` directory or the repository itself if it's bare.
     pub fn git_repo_path(&self) -> &Path {
         self.base_repo.path()
@@ -448,6 +480,7 @@ fn commit_from_git_without_root_parent(
     id: &CommitId,
     git_object: &gix::Object,
     uses_tree_conflict_format: bool,
+    is_shallow: bool,
 ) -> Result<Commit, BackendError> {
     let commit = git_object
         .try_to_commit_ref()
@@ -466,10 +499,16 @@ fn commit_from_git_without_root_parent(
             .map(|b| b.reverse_bits())
             .collect(),
     );
-    let parents = commit
-        .parents()
-        .map(|oid| CommitId::from_bytes(oid.as_bytes()))
-        .collect_vec();
+    // The parents of shallow commits weren't fetched, so they're omitted, and
+    // the commits become children of the root commit.
+    let parents = if is_shallow {
+        vec![]
+    } else {
+        commit
+            .parents()
+            .map(|oid| CommitId::from_bytes(oid.as_bytes()))
+            .collect_vec()
+    };
     let tree_id = TreeId::from_bytes(commit.tree().as_bytes());
     // If this commit is a conflict, we'll update the root tree later, when we read
     // the extra metadata.
@@ -793,15 +832,26 @@ fn import_extra_metadata_entries_from_heads(
         .filter(|&id| mut_table.get_value(id.as_bytes()).is_none())
         .map(|&id| id.clone())
         .collect_vec();
+    let shallow_commits = git_repo
+        .shallow_commits()
+        .map_err(|err| BackendError::Other(err.into()))?;
     while let Some(id) = work_ids.pop() {
+        let git_commit_id = validate_git_object_id(&id)?;
         let git_object = git_repo
-            .find_object(validate_git_object_id(&id)?)
+            .find_object(git_commit_id)
             .map_err(|err| map_not_found_err(err, &id))?;
+        let is_shallow = shallow_commits
+            .as_ref()
+            .is_some_and(|shallow| shallow.contains(&git_commit_id));
         // TODO(#1624): Should we read the root tree here and check if it has a
         // `.jjconflict-...` entries? That could happen if the user used `git` to e.g.
         // change the description of a commit with tree-level conflicts.
-        let commit =
-            commit_from_git_without_root_parent(&id, &git_object, uses_tree_conflict_format)?;
+        let commit = commit_from_git_without_root_parent(
+            &id,
+            &git_object,
+            uses_tree_conflict_format,
+            is_shallow,
+        )?;
         mut_table.add_entry(id.to_bytes(), serialize_extras(&commit));
         work_ids.extend(
             commit
@@ -813,6 +863,58 @@ fn import_extra_metadata_entries_from_heads(
     Ok(())
 }
 
+#[derive(Default)]
+struct GitRepoPool {
+    idle: Vec<git2::Repository>,
+    /// Incremented whenever the pool is cleared, so that instances handed out
+    /// before that aren't returned to it.
+    generation: u64,
+}
+
+impl GitRepoPool {
+    fn clear(&mut self) {
+        self.idle.clear();
+        self.generation += 1;
+    }
+}
+
+/// git2 repository instance which is returned to the `GitBackend` when dropped.
+pub struct PooledGitRepo {
+    repo: Option<git2::Repository>,
+    generation: Cell<u64>,
+    pool: Arc<Mutex<GitRepoPool>>,
+}
+
+impl PooledGitRepo {
+    /// Drops the other instances of the pool. Must be called after modifying
+    /// the Git config through this instance (e.g. adding a remote), since the
+    /// other instances may have cached the old config.
+    pub fn clear_pool(&self) {
+        let mut pool = self.pool.lock().unwrap();
+        pool.clear();
+        self.generation.set(pool.generation);
+    }
+}
+
+impl Deref for PooledGitRepo {
+    type Target = git2::Repository;
+
+    fn deref(&self) -> &Self::Target {
+        self.repo.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledGitRepo {
+    fn drop(&mut self) {
+        if let Some(repo) = self.repo.take() {
+            let mut pool = self.pool.lock().unwrap();
+            if pool.generation == self.generation.get() {
+                pool.idle.push(repo);
+            }
+        }
+    }
+}
+
 impl Debug for GitBackend {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         f.debug_struct("GitBackend")
@@ -1067,7 +1169,11 @@ impl Backend for GitBackend {
             let git_object = locked_repo
                 .find_object(git_commit_id)
                 .map_err(|err| map_not_found_err(err, id))?;
-            commit_from_git_without_root_parent(id, &git_object, false)?
+            let is_shallow = locked_repo
+                .shallow_commits()
+                .map_err(|err| BackendError::Other(err.into()))?
+                .is_some_and(|shallow| shallow.contains(&git_commit_id));
+            commit_from_git_without_root_parent(id, &git_object, false, is_shallow)?
         };
         if commit.parents.is_empty() {
             commit.parents.push(self.root_commit_id.clone());
@@ -1222,7 +1328,11 @@ impl Backend for GitBackend {
         // preserved by the keep_newer timestamp though)
         // TODO: remove unreachable extras table segments
         // TODO: pass in keep_newer to "git gc" command
-        run_git_gc(self.git_repo_path()).map_err(|err| BackendError::Other(err.into()))
+        let result =
+            run_git_gc(self.git_repo_path()).map_err(|err| BackendError::Other(err.into()));
+        // "git gc" repacks objects, which libgit2 might have cached.
+        self.clear_git_repo_pool();
+        result
     }
 }
 
@@ -1594,6 +1704,61 @@ mod tests {
         assert_eq!(std::str::from_utf8(&sig.data).unwrap(), commit_buf);
     }
 
+    #[test]
+    fn pooled_git_repo_is_reused() {
+        let settings = user_settings();
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let git_repo_path = temp_dir.path().join("git");
+        let git_repo = git2::Repository::init(git_repo_path).unwrap();
+        let backend = GitBackend::init_external(&settings, store_path, git_repo.path()).unwrap();
+        let idle_count = || backend.idle_git2_repos.lock().unwrap().idle.len();
+
+        let repo1 = backend.pooled_git_repo().unwrap();
+        let repo2 = backend.pooled_git_repo().unwrap();
+        assert_eq!(repo1.path(), repo2.path());
+        assert_eq!(idle_count(), 0);
+        drop(repo1);
+        drop(repo2);
+        assert_eq!(idle_count(), 2);
+
+        // The idle instances are handed out again
+        let _repo3 = backend.pooled_git_repo().unwrap();
+        assert_eq!(idle_count(), 1);
+        backend.clear_git_repo_pool();
+        assert_eq!(idle_count(), 0);
+    }
+
+    #[test]
+    fn pooled_git_repo_is_dropped_after_config_change() {
+        let settings = user_settings();
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let git_repo_path = temp_dir.path().join("git");
+        let git_repo = git2::Repository::init(git_repo_path).unwrap();
+        let backend = GitBackend::init_external(&settings, store_path, git_repo.path()).unwrap();
+        let idle_count = || backend.idle_git2_repos.lock().unwrap().idle.len();
+
+        let repo1 = backend.pooled_git_repo().unwrap();
+        let repo2 = backend.pooled_git_repo().unwrap();
+        drop(backend.pooled_git_repo().unwrap());
+        assert_eq!(idle_count(), 1);
+
+        // Neither the idle instance nor the one in use is reused after the
+        // config was modified through another instance
+        repo1.remote("origin", "https://example.com/repo").unwrap();
+        repo1.clear_pool();
+        assert_eq!(idle_count(), 0);
+        drop(repo2);
+        assert_eq!(idle_count(), 0);
+
+        // The modifying instance is up to date, so it's reused
+        drop(repo1);
+        assert_eq!(idle_count(), 1);
+        let repo3 = backend.pooled_git_repo().unwrap();
+        assert!(repo3.find_remote("origin").is_ok());
+    }
+
     #[test]
     fn read_empty_string_placeholder() {
         let git_signature1 = gix::actor::SignatureRef {
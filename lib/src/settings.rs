@@ -42,6 +42,12 @@ pub struct RepoSettings {
 pub struct GitSettings {
     pub auto_local_branch: bool,
     pub abandon_unreachable_commits: bool,
+    /// Number of commits to fetch from the tip of each branch, or `None` to
+    /// fetch the full history.
+    pub fetch_depth: Option<u32>,
+    /// Names of the notes refs to import, relative to `refs/notes/`. Notes
+    /// aren't imported by default since there can be a lot of them.
+    pub import_notes: Vec<String>,
 }
 
 impl GitSettings {
@@ -51,6 +57,11 @@ impl GitSettings {
             abandon_unreachable_commits: config
                 .get_bool("git.abandon-unreachable-commits")
                 .unwrap_or(true),
+            fetch_depth: config
+                .get::<u32>("git.fetch-depth")
+                .ok()
+                .filter(|&depth| depth > 0),
+            import_notes: config.get("git.import-notes").unwrap_or_default(),
         }
     }
 }
@@ -60,6 +71,8 @@ impl Default for GitSettings {
         GitSettings {
             auto_local_branch: false,
             abandon_unreachable_commits: true,
+            fetch_depth: None,
+            import_notes: vec![],
         }
     }
 }
@@ -26,7 +26,7 @@ use jj_lib::commit_builder::CommitBuilder;
 use jj_lib::git;
 use jj_lib::git::{
     FailedRefExportReason, GitBranchPushTargets, GitFetchError, GitImportError, GitPushError,
-    GitRefUpdate, RefName, SubmoduleConfig,
+    GitRefUpdate, RefImportDecision, RefName, SubmoduleConfig,
 };
 use jj_lib::git_backend::GitBackend;
 use jj_lib::object_id::ObjectId;
@@ -349,6 +349,50 @@ fn test_import_refs_reimport_head_removed() {
     assert!(!tx.mut_repo().view().heads().contains(&commit_id));
 }
 
+#[test]
+fn test_import_refs_pending() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = &test_repo.repo;
+    let git_repo = get_git_repo(repo);
+
+    let commit = empty_git_commit(&git_repo, "refs/heads/main", &[]);
+    let commit_id = jj_id(&commit);
+
+    // Aborting the import leaves the repo unchanged
+    let pending = git::import_refs_pending(repo, &settings).unwrap();
+    assert_eq!(
+        pending.stats().changed_remote_refs.keys().collect_vec(),
+        vec![&RefName::LocalBranch("main".to_owned())]
+    );
+    assert!(pending.repo().view().heads().contains(&commit_id));
+    pending.abort();
+    let repo = repo.reload_at_head(&settings).unwrap();
+    assert!(repo.view().get_local_branch("main").is_absent());
+    assert!(!repo.view().heads().contains(&commit_id));
+
+    // Committing the import publishes it
+    let pending = git::import_refs_pending(&repo, &settings).unwrap();
+    let repo = pending.commit("import git refs").unwrap();
+    assert_eq!(
+        *repo.view().get_local_branch("main"),
+        RefTarget::normal(commit_id.clone())
+    );
+    let repo = repo.reload_at_head(&settings).unwrap();
+    assert!(repo.view().heads().contains(&commit_id));
+
+    // Abandoned commits are reported before the descendants are rebased
+    git_repo
+        .find_reference("refs/heads/main")
+        .unwrap()
+        .delete()
+        .unwrap();
+    let pending = git::import_refs_pending(&repo, &settings).unwrap();
+    assert_eq!(pending.stats().abandoned_commits, vec![commit_id.clone()]);
+    let repo = pending.commit("import git refs").unwrap();
+    assert!(!repo.view().heads().contains(&commit_id));
+}
+
 #[test]
 fn test_import_refs_reimport_git_head_counts() {
     // Test that if a branch is removed but the Git HEAD points to the commit (or a
@@ -938,6 +982,77 @@ fn test_import_refs_reserved_remote_name() {
     assert_matches!(result, Err(GitImportError::RemoteReservedForLocalGitRepo));
 }
 
+#[test]
+fn test_import_refs_notes() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = &test_repo.repo;
+    let git_repo = get_git_repo(repo);
+
+    let commit = empty_git_commit(&git_repo, "refs/heads/main", &[]);
+    let signature = git2::Signature::now("Someone", "someone@example.com").unwrap();
+    git_repo
+        .note(&signature, &signature, None, commit.id(), "LGTM", false)
+        .unwrap();
+    git_repo
+        .note(
+            &signature,
+            &signature,
+            Some("refs/notes/other"),
+            commit.id(),
+            "other",
+            false,
+        )
+        .unwrap();
+    let notes_commit = git_repo
+        .find_reference("refs/notes/commits")
+        .unwrap()
+        .peel_to_commit()
+        .unwrap();
+
+    // Notes aren't imported by default
+    let mut tx = repo.start_transaction(&settings);
+    let stats = git::import_refs(tx.mut_repo(), &GitSettings::default()).unwrap();
+    assert!(!stats
+        .changed_remote_refs
+        .contains_key(&RefName::Note("commits".to_owned())));
+    assert!(tx.mut_repo().get_git_ref("refs/notes/commits").is_absent());
+
+    // Only the configured notes refs are imported
+    let git_settings = GitSettings {
+        import_notes: vec!["commits".to_owned()],
+        ..Default::default()
+    };
+    let stats = git::import_refs(tx.mut_repo(), &git_settings).unwrap();
+    assert_eq!(
+        stats
+            .changed_remote_refs
+            .keys()
+            .filter(|ref_name| matches!(ref_name, RefName::Note(_)))
+            .collect_vec(),
+        [&RefName::Note("commits".to_owned())]
+    );
+    let view = tx.mut_repo().view();
+    assert_eq!(
+        *view.get_git_ref("refs/notes/commits"),
+        RefTarget::normal(jj_id(&notes_commit))
+    );
+    assert!(view.get_git_ref("refs/notes/other").is_absent());
+    assert!(view.heads().contains(&jj_id(&notes_commit)));
+    // The notes ref can be referred to like other Git refs
+    assert_eq!(
+        git::to_git_ref_name(&RefName::Note("commits".to_owned())),
+        Some("refs/notes/commits".to_owned())
+    );
+
+    // Deleted notes refs are removed on reimport
+    delete_git_ref(&git_repo, "refs/notes/commits");
+    let stats = git::import_refs(tx.mut_repo(), &git_settings).unwrap();
+    let (_, new_target) = &stats.changed_remote_refs[&RefName::Note("commits".to_owned())];
+    assert!(new_target.is_absent());
+    assert!(tx.mut_repo().get_git_ref("refs/notes/commits").is_absent());
+}
+
 #[test]
 fn test_import_some_refs() {
     let settings = testutils::user_settings();
@@ -1115,6 +1230,118 @@ fn test_import_some_refs() {
     assert_eq!(*view.heads(), expected_heads);
 }
 
+#[test]
+fn test_import_classified_refs() {
+    let settings = testutils::user_settings();
+    let git_settings = GitSettings::default();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = &test_repo.repo;
+    let git_repo = get_git_repo(repo);
+
+    let commit_main = empty_git_commit(&git_repo, "refs/heads/main", &[]);
+    let commit_pull = empty_git_commit(&git_repo, "refs/remotes/origin/pull/1/head", &[]);
+    let commit_tag = empty_git_commit(&git_repo, "refs/tags/v1", &[]);
+    // Pull requests are imported as `pr/*` branches, and tags are skipped
+    let classify_ref = |ref_name: &RefName| match ref_name {
+        RefName::RemoteBranch { branch, remote } => match branch.strip_prefix("pull/") {
+            Some(rest) => RefImportDecision::RenameTo(RefName::RemoteBranch {
+                branch: format!("pr/{}", rest.trim_end_matches("/head")),
+                remote: remote.clone(),
+            }),
+            None => RefImportDecision::Import,
+        },
+        RefName::Tag(_) => RefImportDecision::Skip,
+        _ => RefImportDecision::Import,
+    };
+
+    let mut tx = repo.start_transaction(&settings);
+    let stats = git::import_classified_refs(tx.mut_repo(), &git_settings, classify_ref).unwrap();
+    assert_eq!(
+        stats.changed_remote_refs.keys().collect_vec(),
+        [
+            &RefName::LocalBranch("main".to_owned()),
+            &RefName::RemoteBranch {
+                branch: "pr/1".to_owned(),
+                remote: "origin".to_owned()
+            },
+        ]
+    );
+    let view = tx.mut_repo().view();
+    assert_eq!(
+        view.get_remote_branch("pr/1", "origin").target,
+        RefTarget::normal(jj_id(&commit_pull))
+    );
+    assert!(view.get_remote_branch("pull/1/head", "origin").is_absent());
+    assert_eq!(
+        *view.get_local_branch("main"),
+        RefTarget::normal(jj_id(&commit_main))
+    );
+    assert!(view.get_tag("v1").is_absent());
+    // The Git ref is recorded under its own name
+    assert_eq!(
+        *view.get_git_ref("refs/remotes/origin/pull/1/head"),
+        RefTarget::normal(jj_id(&commit_pull))
+    );
+    assert!(view.get_git_ref("refs/tags/v1").is_absent());
+    assert!(!view.heads().contains(&jj_id(&commit_tag)));
+
+    // The renamed branch is deleted along with the Git ref
+    delete_git_ref(&git_repo, "refs/remotes/origin/pull/1/head");
+    let stats = git::import_classified_refs(tx.mut_repo(), &git_settings, classify_ref).unwrap();
+    assert_eq!(
+        stats.changed_remote_refs.keys().collect_vec(),
+        [&RefName::RemoteBranch {
+            branch: "pr/1".to_owned(),
+            remote: "origin".to_owned()
+        }]
+    );
+    let view = tx.mut_repo().view();
+    assert!(view.get_remote_branch("pr/1", "origin").is_absent());
+    assert!(view
+        .get_git_ref("refs/remotes/origin/pull/1/head")
+        .is_absent());
+}
+
+#[test]
+fn test_import_classified_refs_duplicate_name() {
+    let settings = testutils::user_settings();
+    let git_settings = GitSettings::default();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = &test_repo.repo;
+    let git_repo = get_git_repo(repo);
+
+    empty_git_commit(&git_repo, "refs/remotes/origin/pull/1/head", &[]);
+    empty_git_commit(&git_repo, "refs/remotes/origin/pull/1/merge", &[]);
+    // Both refs of the pull request are renamed to `pr/1`
+    let classify_ref = |ref_name: &RefName| match ref_name {
+        RefName::RemoteBranch { branch, remote } => match branch.strip_prefix("pull/") {
+            Some(rest) => RefImportDecision::RenameTo(RefName::RemoteBranch {
+                branch: format!("pr/{}", rest.split('/').next().unwrap()),
+                remote: remote.clone(),
+            }),
+            None => RefImportDecision::Import,
+        },
+        _ => RefImportDecision::Import,
+    };
+
+    let mut tx = repo.start_transaction(&settings);
+    let result = git::import_classified_refs(tx.mut_repo(), &git_settings, classify_ref);
+    assert_matches!(
+        result,
+        Err(GitImportError::DuplicateRefName { ref_name, first, second })
+            if ref_name == RefName::RemoteBranch {
+                branch: "pr/1".to_owned(),
+                remote: "origin".to_owned(),
+            } && first == "refs/remotes/origin/pull/1/head"
+                && second == "refs/remotes/origin/pull/1/merge"
+    );
+    assert!(tx
+        .mut_repo()
+        .view()
+        .get_remote_branch("pr/1", "origin")
+        .is_absent());
+}
+
 fn git_ref(git_repo: &git2::Repository, name: &str, target: Oid) {
     git_repo.reference(name, target, true, "").unwrap();
 }
@@ -1187,6 +1414,43 @@ fn test_import_refs_empty_git_repo() {
     assert_eq!(repo.view().git_head(), RefTarget::absent_ref());
 }
 
+#[test]
+fn test_import_refs_shallow_git_repo() {
+    let settings = testutils::user_settings();
+    let git_settings = GitSettings::default();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = &test_repo.repo;
+    let git_repo = get_git_repo(repo);
+
+    // Simulate a shallow fetch of commit2, whose parent wasn't fetched
+    let commit1 = empty_git_commit(&git_repo, "refs/heads/main", &[]);
+    let commit2 = empty_git_commit(&git_repo, "refs/heads/main", &[&commit1]);
+    let commit1_hex = commit1.id().to_string();
+    std::fs::remove_file(
+        git_repo
+            .path()
+            .join("objects")
+            .join(&commit1_hex[..2])
+            .join(&commit1_hex[2..]),
+    )
+    .unwrap();
+    std::fs::write(
+        git_repo.path().join("shallow"),
+        format!("{}\n", commit2.id()),
+    )
+    .unwrap();
+    assert_eq!(git::shallow_root_ids(&git_repo), vec![jj_id(&commit2)]);
+
+    let mut tx = repo.start_transaction(&settings);
+    let stats = git::import_refs(tx.mut_repo(), &git_settings).unwrap();
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    assert_eq!(stats.changed_remote_refs.len(), 1);
+    assert!(repo.view().heads().contains(&jj_id(&commit2)));
+    let commit = repo.store().get_commit(&jj_id(&commit2)).unwrap();
+    assert_eq!(commit.parent_ids(), [repo.store().root_commit_id().clone()]);
+}
+
 #[test]
 fn test_import_refs_missing_git_commit() {
     let settings = testutils::user_settings();
@@ -1648,6 +1912,100 @@ fn test_export_branch_on_root_commit() {
     assert_matches!(failed[0].reason, FailedRefExportReason::OnRootCommit);
 }
 
+#[test]
+fn test_preview_export_refs() {
+    // The preview reports the same failures as the export without touching Git
+    let test_data = GitRepoData::create();
+    let git_repo = test_data.git_repo;
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &test_data.settings);
+    let commit_b = write_random_commit(mut_repo, &test_data.settings);
+    mut_repo.set_local_branch_target("HEAD", RefTarget::normal(commit_a.id().clone()));
+    mut_repo.set_local_branch_target("main", RefTarget::normal(commit_a.id().clone()));
+    mut_repo.set_local_branch_target("main/sub", RefTarget::normal(commit_a.id().clone()));
+    mut_repo.set_local_branch_target("feature", RefTarget::normal(commit_a.id().clone()));
+    // `feature` was added in Git with a different target
+    git_repo
+        .reference("refs/heads/feature", git_id(&commit_b), false, "test")
+        .unwrap();
+
+    let preview = git::preview_export_refs(mut_repo).unwrap();
+    assert_eq!(
+        preview.iter().map(|failed| &failed.name).collect_vec(),
+        vec![
+            &RefName::LocalBranch("HEAD".to_string()),
+            &RefName::LocalBranch("feature".to_string()),
+            &RefName::LocalBranch("main/sub".to_string()),
+        ]
+    );
+    assert_matches!(preview[0].reason, FailedRefExportReason::InvalidGitName);
+    assert_matches!(
+        preview[1].reason,
+        FailedRefExportReason::AddedInJjAddedInGit
+    );
+    assert_matches!(
+        &preview[2].reason,
+        FailedRefExportReason::ConflictsWithGitRef(name) if name == "refs/heads/main"
+    );
+    assert!(git_repo.find_reference("refs/heads/main").is_err());
+    assert!(mut_repo.view().git_refs().is_empty());
+
+    let failed = git::export_refs(mut_repo).unwrap();
+    assert_eq!(
+        failed.iter().map(|failed| &failed.name).collect_vec(),
+        preview.iter().map(|failed| &failed.name).collect_vec()
+    );
+    assert!(git_repo.find_reference("refs/heads/main").is_ok());
+}
+
+#[test]
+fn test_export_branches() {
+    // Only the named local and remote branches are exported
+    let test_data = GitRepoData::create();
+    let git_repo = test_data.git_repo;
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &test_data.settings);
+    let target = RefTarget::normal(commit_a.id().clone());
+    mut_repo.set_local_branch_target("a", target.clone());
+    mut_repo.set_local_branch_target("b", target.clone());
+    mut_repo.set_local_branch_target("HEAD", target.clone());
+    let remote_ref = RemoteRef {
+        target: target.clone(),
+        state: RemoteRefState::New,
+    };
+    mut_repo.set_remote_branch("a", "origin", remote_ref.clone());
+    mut_repo.set_remote_branch("b", "origin", remote_ref);
+    let branch_names = ["a".to_owned(), "HEAD".to_owned()];
+
+    let preview = git::preview_export_branches(mut_repo, &branch_names).unwrap();
+    assert_eq!(
+        preview.iter().map(|failed| &failed.name).collect_vec(),
+        vec![&RefName::LocalBranch("HEAD".to_string())]
+    );
+    let failed = git::export_branches(mut_repo, &branch_names).unwrap();
+    assert_eq!(
+        failed.iter().map(|failed| &failed.name).collect_vec(),
+        vec![&RefName::LocalBranch("HEAD".to_string())]
+    );
+    assert_matches!(failed[0].reason, FailedRefExportReason::InvalidGitName);
+    assert!(git_repo.find_reference("refs/heads/a").is_ok());
+    assert!(git_repo.find_reference("refs/remotes/origin/a").is_ok());
+    assert!(git_repo.find_reference("refs/heads/b").is_err());
+    assert!(git_repo.find_reference("refs/remotes/origin/b").is_err());
+    assert_eq!(
+        mut_repo.view().git_refs().keys().collect_vec(),
+        vec!["refs/heads/a", "refs/remotes/origin/a"]
+    );
+
+    // The other branches are still exported by a full export
+    let failed = git::export_refs(mut_repo).unwrap();
+    assert_eq!(failed.len(), 1);
+    assert!(git_repo.find_reference("refs/heads/b").is_ok());
+    assert!(git_repo.find_reference("refs/remotes/origin/b").is_ok());
+}
+
 #[test]
 fn test_export_partial_failure() {
     // Check that we skip branches that fail to export
@@ -1672,7 +2030,18 @@ fn test_export_partial_failure() {
     assert_eq!(failed[1].name, RefName::LocalBranch("HEAD".to_string()));
     assert_matches!(failed[1].reason, FailedRefExportReason::InvalidGitName);
     assert_eq!(failed[2].name, RefName::LocalBranch("main/sub".to_string()));
-    assert_matches!(failed[2].reason, FailedRefExportReason::FailedToSet(_));
+    assert_matches!(
+        &failed[2].reason,
+        FailedRefExportReason::ConflictsWithGitRef(name) if name == "refs/heads/main"
+    );
+    assert_eq!(
+        git::find_conflicting_git_ref(mut_repo.view(), &failed[2].name),
+        Some("refs/heads/main")
+    );
+    assert_eq!(
+        git::find_conflicting_git_ref(mut_repo.view(), &RefName::LocalBranch("mai".to_owned())),
+        None
+    );
 
     // The `main` branch should have succeeded but the other should have failed
     assert!(git_repo.find_reference("refs/heads/").is_err());
@@ -2077,6 +2446,67 @@ fn test_fetch_initial_commit() {
     );
 }
 
+#[test]
+fn test_fetch_progress() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    empty_git_commit(&test_data.origin_repo, "refs/heads/main", &[]);
+
+    let mut last_progress = None;
+    let mut progress_cb = |progress: &git::Progress| {
+        last_progress = Some((
+            progress.received_objects,
+            progress.total_objects,
+            progress.received_bytes,
+        ));
+    };
+    let mut callbacks = git::RemoteCallbacks::default();
+    callbacks.progress = Some(&mut progress_cb);
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    git::fetch(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "origin",
+        &[StringPattern::everything()],
+        callbacks,
+        &git_settings,
+    )
+    .unwrap();
+    let (received_objects, total_objects, received_bytes) = last_progress.unwrap();
+    assert!(total_objects > 0);
+    assert_eq!(received_objects, total_objects);
+    assert!(received_bytes > 0);
+}
+
+#[test]
+fn test_fetch_cancelled() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    empty_git_commit(&test_data.origin_repo, "refs/heads/main", &[]);
+
+    let is_cancelled = || true;
+    let mut callbacks = git::RemoteCallbacks::default();
+    callbacks.is_cancelled = Some(&is_cancelled);
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let result = git::fetch(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "origin",
+        &[StringPattern::everything()],
+        callbacks,
+        &git_settings,
+    );
+    assert_matches!(
+        result,
+        Err(GitFetchError::InternalGitError(err)) if err.code() == git2::ErrorCode::User
+    );
+    assert!(!tx.mut_repo().has_changes());
+    assert!(test_data
+        .git_repo
+        .find_reference("refs/remotes/origin/main")
+        .is_err());
+}
+
 #[test]
 fn test_fetch_success() {
     let mut test_data = GitRepoData::create();
@@ -2119,8 +2549,12 @@ fn test_fetch_success() {
         &git_settings,
     )
     .unwrap();
-    // The default branch is "main"
+    // The default branch is "main", and it's recorded in the Git repo
     assert_eq!(stats.default_branch, Some("main".to_string()));
+    assert_eq!(
+        git::get_remote_default_branch(&test_data.git_repo, "origin"),
+        Some("main".to_string())
+    );
     assert!(stats.import_stats.abandoned_commits.is_empty());
     let repo = tx.commit("test");
     // The new commit is visible after we fetch again
@@ -2199,6 +2633,54 @@ fn test_fetch_prune_deleted_ref() {
     assert!(!tx.mut_repo().has_branch("main"));
 }
 
+#[test]
+fn test_fetch_prune_only_fetched_remote() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    test_data
+        .git_repo
+        .remote("upstream", test_data.origin_repo.path().to_str().unwrap())
+        .unwrap();
+    let commit = empty_git_commit(&test_data.origin_repo, "refs/heads/main", &[]);
+    let fetch = |mut_repo: &mut MutableRepo, remote_name| {
+        git::fetch(
+            mut_repo,
+            &test_data.git_repo,
+            remote_name,
+            &[StringPattern::everything()],
+            git::RemoteCallbacks::default(),
+            &git_settings,
+        )
+        .unwrap()
+    };
+
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    fetch(tx.mut_repo(), "origin");
+    fetch(tx.mut_repo(), "upstream");
+    test_data
+        .origin_repo
+        .find_reference("refs/heads/main")
+        .unwrap()
+        .delete()
+        .unwrap();
+
+    // The deletion is reported, but the branch of the other remote is kept
+    let stats = fetch(tx.mut_repo(), "origin");
+    let main_origin = RefName::RemoteBranch {
+        branch: "main".to_owned(),
+        remote: "origin".to_owned(),
+    };
+    let (old_remote_ref, new_target) = &stats.import_stats.changed_remote_refs[&main_origin];
+    assert_eq!(old_remote_ref.target, RefTarget::normal(jj_id(&commit)));
+    assert!(new_target.is_absent());
+    let view = tx.mut_repo().view();
+    assert!(view.get_remote_branch("main", "origin").is_absent());
+    assert_eq!(
+        view.get_remote_branch("main", "upstream").target,
+        RefTarget::normal(jj_id(&commit))
+    );
+}
+
 #[test]
 fn test_fetch_no_default_branch() {
     let test_data = GitRepoData::create();
@@ -2243,6 +2725,10 @@ fn test_fetch_no_default_branch() {
     .unwrap();
     // There is no default branch
     assert_eq!(stats.default_branch, None);
+    assert_eq!(
+        git::get_remote_default_branch(&test_data.git_repo, "origin"),
+        None
+    );
 }
 
 #[test]
@@ -2275,21 +2761,236 @@ fn test_fetch_empty_refspecs() {
 }
 
 #[test]
-fn test_fetch_no_such_remote() {
+fn test_fetch_refspecs() {
     let test_data = GitRepoData::create();
     let git_settings = GitSettings::default();
+    empty_git_commit(&test_data.origin_repo, "refs/heads/main", &[]);
+    let pr_commit = empty_git_commit(&test_data.origin_repo, "refs/pull/1/head", &[]);
+
     let mut tx = test_data.repo.start_transaction(&test_data.settings);
-    let result = git::fetch(
+    let stats = git::fetch_refspecs(
         tx.mut_repo(),
         &test_data.git_repo,
-        "invalid-remote",
-        &[StringPattern::everything()],
+        "origin",
+        &["+refs/pull/*/head:refs/remotes/origin/pr/*".to_owned()],
         git::RemoteCallbacks::default(),
         &git_settings,
-    );
+    )
+    .unwrap();
+    // Only the mapped refs are fetched, and they are imported as remote branches
+    assert_eq!(
+        stats.import_stats.changed_remote_refs.keys().collect_vec(),
+        vec![&RefName::RemoteBranch {
+            branch: "pr/1".to_owned(),
+            remote: "origin".to_owned(),
+        }]
+    );
+    let view = tx.mut_repo().view();
+    assert_eq!(
+        view.get_remote_branch("pr/1", "origin").target,
+        RefTarget::normal(jj_id(&pr_commit))
+    );
+    assert!(view.get_remote_branch("main", "origin").is_absent());
+}
+
+#[test]
+fn test_fetch_refspecs_invalid_destination() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    for refspec in [
+        "+refs/pull/*/head:refs/heads/pr/*",
+        "+refs/pull/*/head:refs/remotes/upstream/pr/*",
+        "+refs/pull/*/head:refs/remotes/origin/pr",
+        "refs/heads/main",
+    ] {
+        let result = git::fetch_refspecs(
+            tx.mut_repo(),
+            &test_data.git_repo,
+            "origin",
+            &[refspec.to_owned()],
+            git::RemoteCallbacks::default(),
+            &git_settings,
+        );
+        assert!(
+            matches!(result, Err(GitFetchError::InvalidRefspec(_))),
+            "{refspec}"
+        );
+    }
+}
+
+#[test]
+fn test_fetched_ref_refspec() {
+    assert_eq!(
+        git::fetched_ref_refspec("origin", "fetched", "pull/1/head").as_deref(),
+        Some("+refs/pull/1/head:refs/remotes/origin/fetched/pull/1/head")
+    );
+    assert_eq!(
+        git::fetched_ref_refspec("origin", "fetched/", "refs/pull/1/head").as_deref(),
+        Some("+refs/pull/1/head:refs/remotes/origin/fetched/pull/1/head")
+    );
+    assert_eq!(
+        git::fetched_ref_refspec("origin", "", "HEAD").as_deref(),
+        Some("+HEAD:refs/remotes/origin/HEAD")
+    );
+    let id = "A".repeat(40);
+    assert_eq!(
+        git::fetched_ref_refspec("origin", "pr", &id),
+        Some(format!("+{0}:refs/remotes/origin/pr/{0}", "a".repeat(40)))
+    );
+    for source in ["a..b", "pull/*/head", "refs/", "a b", "a:b"] {
+        assert_eq!(
+            git::fetched_ref_refspec("origin", "fetched", source),
+            None,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn test_fetch_fetched_ref_refspec() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    let main_commit = empty_git_commit(&test_data.origin_repo, "refs/heads/main", &[]);
+    let pr_commit = empty_git_commit(&test_data.origin_repo, "refs/pull/1/head", &[]);
+
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let refspecs = ["pull/1/head".to_owned(), main_commit.id().to_string()]
+        .iter()
+        .map(|source| git::fetched_ref_refspec("origin", "fetched", source).unwrap())
+        .collect_vec();
+    let stats = git::fetch_refspecs(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "origin",
+        &refspecs,
+        git::RemoteCallbacks::default(),
+        &git_settings,
+    )
+    .unwrap();
+    let main_name = format!("fetched/{}", main_commit.id());
+    assert_eq!(
+        stats.import_stats.changed_remote_refs.keys().collect_vec(),
+        vec![
+            &RefName::RemoteBranch {
+                branch: main_name.clone(),
+                remote: "origin".to_owned(),
+            },
+            &RefName::RemoteBranch {
+                branch: "fetched/pull/1/head".to_owned(),
+                remote: "origin".to_owned(),
+            },
+        ]
+    );
+    let view = tx.mut_repo().view();
+    assert_eq!(
+        view.get_remote_branch(&main_name, "origin").target,
+        RefTarget::normal(jj_id(&main_commit))
+    );
+    assert_eq!(
+        view.get_remote_branch("fetched/pull/1/head", "origin")
+            .target,
+        RefTarget::normal(jj_id(&pr_commit))
+    );
+}
+
+#[test]
+fn test_fetch_rewritten_url() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    let commit = empty_git_commit(&test_data.origin_repo, "refs/heads/main", &[]);
+    let origin_url = test_data
+        .git_repo
+        .find_remote("origin")
+        .unwrap()
+        .url()
+        .unwrap()
+        .to_owned();
+    test_data
+        .git_repo
+        .remote_set_url("origin", "/nonexistent/origin")
+        .unwrap();
+
+    // The rewritten URL is connected to, and the remote-tracking refs of the
+    // remote are updated
+    let mut rewrite_url = |url: &str| {
+        assert_eq!(url, "/nonexistent/origin");
+        Some(origin_url.clone())
+    };
+    let mut callbacks = git::RemoteCallbacks::default();
+    callbacks.rewrite_url = Some(&mut rewrite_url);
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    git::fetch(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "origin",
+        &[StringPattern::everything()],
+        callbacks,
+        &git_settings,
+    )
+    .unwrap();
+    assert_eq!(
+        tx.mut_repo()
+            .view()
+            .get_remote_branch("main", "origin")
+            .target,
+        RefTarget::normal(jj_id(&commit))
+    );
+    assert_eq!(
+        test_data
+            .git_repo
+            .refname_to_id("refs/remotes/origin/main")
+            .unwrap(),
+        commit.id()
+    );
+}
+
+#[test]
+fn test_fetch_no_such_remote() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let result = git::fetch(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "invalid-remote",
+        &[StringPattern::everything()],
+        git::RemoteCallbacks::default(),
+        &git_settings,
+    );
     assert!(matches!(result, Err(GitFetchError::NoSuchRemote(_))));
 }
 
+#[test]
+fn test_list_remote_refs() {
+    let test_data = GitRepoData::create();
+    let list_refs = |remote_name: &str| {
+        git::list_remote_refs(
+            &test_data.git_repo,
+            remote_name,
+            git::RemoteCallbacks::default(),
+        )
+    };
+    assert_eq!(list_refs("origin").unwrap(), vec![]);
+
+    let main_commit = empty_git_commit(&test_data.origin_repo, "refs/heads/main", &[]);
+    let tag_commit = empty_git_commit(&test_data.origin_repo, "refs/tags/v1", &[&main_commit]);
+    assert_eq!(
+        list_refs("origin").unwrap(),
+        vec![
+            ("refs/heads/main".to_owned(), jj_id(&main_commit)),
+            ("refs/tags/v1".to_owned(), jj_id(&tag_commit)),
+        ]
+    );
+    // Nothing is fetched
+    assert!(test_data.git_repo.find_commit(main_commit.id()).is_err());
+
+    assert!(matches!(
+        list_refs("invalid-remote"),
+        Err(GitFetchError::NoSuchRemote(_))
+    ));
+}
+
 struct PushTestSetup {
     source_repo_dir: PathBuf,
     jj_repo: Arc<ReadonlyRepo>,
@@ -2375,6 +3076,7 @@ fn test_push_branches_success() {
             },
         )],
         force_pushed_branches: hashset! {},
+        force_with_lease: false,
     };
     let result = git::push_branches(
         tx.mut_repo(),
@@ -2424,6 +3126,46 @@ fn test_push_branches_success() {
     assert!(!tx.mut_repo().has_changes());
 }
 
+#[test]
+fn test_push_branches_rewritten_url() {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let setup = set_up_push_repos(&settings, &temp_dir);
+    let clone_repo = get_git_repo(&setup.jj_repo);
+    clone_repo
+        .remote_set_url("origin", "/nonexistent/origin")
+        .unwrap();
+    let mut tx = setup.jj_repo.start_transaction(&settings);
+
+    let targets = GitBranchPushTargets {
+        branch_updates: vec![(
+            "main".to_owned(),
+            BranchPushUpdate {
+                old_target: Some(setup.initial_commit.id().clone()),
+                new_target: Some(setup.new_commit.id().clone()),
+            },
+        )],
+        force_pushed_branches: hashset! {},
+        force_with_lease: false,
+    };
+    let source_url = setup.source_repo_dir.to_str().unwrap().to_owned();
+    let mut rewrite_url = |_: &str| Some(source_url.clone());
+    let mut callbacks = git::RemoteCallbacks::default();
+    callbacks.rewrite_url = Some(&mut rewrite_url);
+    let result = git::push_branches(tx.mut_repo(), &clone_repo, "origin", &targets, callbacks);
+    assert_eq!(result, Ok(()));
+
+    // The branch is pushed to the rewritten URL, and the remote-tracking ref
+    // is updated as when pushing to the remote's own URL
+    let new_oid = git_id(&setup.new_commit);
+    let source_repo = git2::Repository::open(&setup.source_repo_dir).unwrap();
+    assert_eq!(source_repo.refname_to_id("refs/heads/main"), Ok(new_oid));
+    assert_eq!(
+        clone_repo.refname_to_id("refs/remotes/origin/main"),
+        Ok(new_oid)
+    );
+}
+
 #[test]
 fn test_push_branches_deletion() {
     let settings = testutils::user_settings();
@@ -2445,6 +3187,7 @@ fn test_push_branches_deletion() {
             },
         )],
         force_pushed_branches: hashset! {},
+        force_with_lease: false,
     };
     let result = git::push_branches(
         tx.mut_repo(),
@@ -2503,6 +3246,7 @@ fn test_push_branches_mixed_deletion_and_addition() {
             ),
         ],
         force_pushed_branches: hashset! {},
+        force_with_lease: false,
     };
     let result = git::push_branches(
         tx.mut_repo(),
@@ -2566,6 +3310,7 @@ fn test_push_branches_not_fast_forward() {
             },
         )],
         force_pushed_branches: hashset! {},
+        force_with_lease: false,
     };
     let result = git::push_branches(
         tx.mut_repo(),
@@ -2598,6 +3343,7 @@ fn test_push_branches_not_fast_forward_with_force() {
         force_pushed_branches: hashset! {
             "main".to_owned(),
         },
+        force_with_lease: false,
     };
     let result = git::push_branches(
         tx.mut_repo(),
@@ -2617,6 +3363,75 @@ fn test_push_branches_not_fast_forward_with_force() {
     assert_eq!(new_target, Some(git_id(&new_commit)));
 }
 
+#[test]
+fn test_push_branches_force_with_lease() {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let mut setup = set_up_push_repos(&settings, &temp_dir);
+    let mut tx = setup.jj_repo.start_transaction(&settings);
+    let new_commit = write_random_commit(tx.mut_repo(), &settings);
+    setup.jj_repo = tx.commit("test");
+    let targets = GitBranchPushTargets {
+        branch_updates: vec![(
+            "main".to_owned(),
+            BranchPushUpdate {
+                old_target: Some(setup.initial_commit.id().clone()),
+                new_target: Some(new_commit.id().clone()),
+            },
+        )],
+        force_pushed_branches: hashset! {
+            "main".to_owned(),
+        },
+        force_with_lease: true,
+    };
+
+    // Someone else moves the branch on the remote
+    let source_repo = git2::Repository::open(&setup.source_repo_dir).unwrap();
+    let initial_git_commit = source_repo
+        .find_commit(git_id(&setup.initial_commit))
+        .unwrap();
+    let other_git_commit =
+        empty_git_commit(&source_repo, "refs/heads/main", &[&initial_git_commit]);
+    let mut tx = setup.jj_repo.start_transaction(&settings);
+    let result = git::push_branches(
+        tx.mut_repo(),
+        &get_git_repo(&setup.jj_repo),
+        "origin",
+        &targets,
+        git::RemoteCallbacks::default(),
+    );
+    assert_eq!(
+        result,
+        Err(GitPushError::RefChangedOnRemote(vec![
+            "refs/heads/main".to_owned()
+        ]))
+    );
+    let current_target = source_repo
+        .find_reference("refs/heads/main")
+        .unwrap()
+        .target();
+    assert_eq!(current_target, Some(other_git_commit.id()));
+
+    // The push succeeds once the branch is back where it was last fetched
+    source_repo
+        .reference("refs/heads/main", initial_git_commit.id(), true, "test")
+        .unwrap();
+    let mut tx = setup.jj_repo.start_transaction(&settings);
+    let result = git::push_branches(
+        tx.mut_repo(),
+        &get_git_repo(&setup.jj_repo),
+        "origin",
+        &targets,
+        git::RemoteCallbacks::default(),
+    );
+    assert_eq!(result, Ok(()));
+    let current_target = source_repo
+        .find_reference("refs/heads/main")
+        .unwrap()
+        .target();
+    assert_eq!(current_target, Some(git_id(&new_commit)));
+}
+
 #[test]
 fn test_push_updates_success() {
     let settings = testutils::user_settings();
@@ -2629,6 +3444,7 @@ fn test_push_updates_success() {
         &[GitRefUpdate {
             qualified_name: "refs/heads/main".to_string(),
             force: false,
+            expected_current_target: None,
             new_target: Some(setup.new_commit.id().clone()),
         }],
         git::RemoteCallbacks::default(),
@@ -2665,6 +3481,7 @@ fn test_push_updates_no_such_remote() {
         &[GitRefUpdate {
             qualified_name: "refs/heads/main".to_string(),
             force: false,
+            expected_current_target: None,
             new_target: Some(setup.new_commit.id().clone()),
         }],
         git::RemoteCallbacks::default(),
@@ -2683,6 +3500,7 @@ fn test_push_updates_invalid_remote() {
         &[GitRefUpdate {
             qualified_name: "refs/heads/main".to_string(),
             force: false,
+            expected_current_target: None,
             new_target: Some(setup.new_commit.id().clone()),
         }],
         git::RemoteCallbacks::default(),
@@ -2690,6 +3508,175 @@ fn test_push_updates_invalid_remote() {
     assert!(matches!(result, Err(GitPushError::NoSuchRemote(_))));
 }
 
+#[test]
+fn test_push_mirror() {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let mut setup = set_up_push_repos(&settings, &temp_dir);
+    let clone_repo = get_git_repo(&setup.jj_repo);
+    let source_repo = git2::Repository::open(&setup.source_repo_dir).unwrap();
+    let refspecs = vec!["+refs/heads/*:refs/heads/*".to_owned()];
+
+    // Move "main" and add "feature", which are pushed once exported
+    let mut tx = setup.jj_repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    mut_repo.set_local_branch_target("main", RefTarget::normal(setup.new_commit.id().clone()));
+    mut_repo.set_local_branch_target("feature", RefTarget::normal(setup.new_commit.id().clone()));
+    assert!(git::export_refs(mut_repo).unwrap().is_empty());
+    let updates =
+        git::mirror_push_updates(mut_repo, &clone_repo, "origin", &refspecs, false).unwrap();
+    assert_eq!(
+        updates
+            .iter()
+            .map(|update| (
+                update.qualified_name.as_str(),
+                update.source.as_deref(),
+                update.old_target.as_ref(),
+                update.new_target.as_ref(),
+            ))
+            .collect_vec(),
+        vec![
+            (
+                "refs/heads/feature",
+                Some("refs/heads/feature"),
+                None,
+                Some(setup.new_commit.id()),
+            ),
+            (
+                "refs/heads/main",
+                Some("refs/heads/main"),
+                Some(setup.initial_commit.id()),
+                Some(setup.new_commit.id()),
+            ),
+        ]
+    );
+    let result = git::push_mirror(
+        mut_repo,
+        &clone_repo,
+        "origin",
+        &updates,
+        false,
+        git::RemoteCallbacks::default(),
+    );
+    assert_eq!(result, Ok(()));
+    let new_oid = git_id(&setup.new_commit);
+    assert_eq!(
+        source_repo.refname_to_id("refs/heads/main").unwrap(),
+        new_oid
+    );
+    assert_eq!(
+        source_repo.refname_to_id("refs/heads/feature").unwrap(),
+        new_oid
+    );
+    assert_eq!(
+        mut_repo.get_remote_branch("feature", "origin"),
+        RemoteRef {
+            target: RefTarget::normal(setup.new_commit.id().clone()),
+            state: RemoteRefState::Tracking,
+        }
+    );
+    setup.jj_repo = tx.commit("test");
+
+    // A deleted branch is only deleted on the remote if pruning
+    let mut tx = setup.jj_repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    mut_repo.set_local_branch_target("feature", RefTarget::absent());
+    assert!(git::export_refs(mut_repo).unwrap().is_empty());
+    let updates =
+        git::mirror_push_updates(mut_repo, &clone_repo, "origin", &refspecs, false).unwrap();
+    assert_eq!(updates, vec![]);
+    let updates =
+        git::mirror_push_updates(mut_repo, &clone_repo, "origin", &refspecs, true).unwrap();
+    assert_eq!(
+        updates
+            .iter()
+            .map(|update| (update.qualified_name.as_str(), update.source.as_deref()))
+            .collect_vec(),
+        vec![("refs/heads/feature", None)]
+    );
+    let result = git::push_mirror(
+        mut_repo,
+        &clone_repo,
+        "origin",
+        &updates,
+        true,
+        git::RemoteCallbacks::default(),
+    );
+    assert_eq!(result, Ok(()));
+    assert!(source_repo.find_reference("refs/heads/feature").is_err());
+    assert!(mut_repo.get_remote_branch("feature", "origin").is_absent());
+}
+
+#[test]
+fn test_preview_mirror_push_updates() {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let setup = set_up_push_repos(&settings, &temp_dir);
+    let clone_repo = get_git_repo(&setup.jj_repo);
+    let refspecs = vec!["+refs/heads/*:refs/heads/*".to_owned()];
+
+    // The updates are the same as after exporting, but nothing is exported
+    let mut tx = setup.jj_repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    mut_repo.set_local_branch_target("main", RefTarget::normal(setup.new_commit.id().clone()));
+    mut_repo.set_local_branch_target("feature", RefTarget::normal(setup.new_commit.id().clone()));
+    let exported_refs = git::preview_exported_refs(mut_repo).unwrap();
+    assert_eq!(
+        exported_refs,
+        btreemap! {
+            "refs/heads/feature".to_owned() => Some(setup.new_commit.id().clone()),
+            "refs/heads/main".to_owned() => Some(setup.new_commit.id().clone()),
+        }
+    );
+    let preview_updates = git::preview_mirror_push_updates(
+        mut_repo,
+        &clone_repo,
+        "origin",
+        &refspecs,
+        false,
+        &exported_refs,
+    )
+    .unwrap();
+    assert!(clone_repo.find_reference("refs/heads/feature").is_err());
+    assert!(mut_repo.get_git_ref("refs/heads/feature").is_absent());
+    assert!(git::export_refs(mut_repo).unwrap().is_empty());
+    let updates =
+        git::mirror_push_updates(mut_repo, &clone_repo, "origin", &refspecs, false).unwrap();
+    assert_eq!(preview_updates, updates);
+    assert_eq!(
+        updates
+            .iter()
+            .map(|update| update.qualified_name.as_str())
+            .collect_vec(),
+        vec!["refs/heads/feature", "refs/heads/main"]
+    );
+}
+
+#[test]
+fn test_push_mirror_invalid_refspec() {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let setup = set_up_push_repos(&settings, &temp_dir);
+    let clone_repo = get_git_repo(&setup.jj_repo);
+    for refspec in [
+        "refs/heads/*:refs/heads/main",
+        "main:main",
+        ":refs/heads/main",
+    ] {
+        let result = git::mirror_push_updates(
+            setup.jj_repo.as_ref(),
+            &clone_repo,
+            "origin",
+            &[refspec.to_owned()],
+            false,
+        );
+        assert_eq!(
+            result,
+            Err(GitPushError::InvalidRefspec(refspec.to_owned()))
+        );
+    }
+}
+
 #[test]
 fn test_bulk_update_extra_on_import_refs() {
     let settings = testutils::user_settings();